@@ -1,5 +1,10 @@
-use is_elevated::is_elevated;
+#[cfg(windows)]
+pub fn check_admin() -> bool {
+    is_elevated::is_elevated()
+}
 
+// root is what counts on Linux, /proc and the raw logs need it
+#[cfg(unix)]
 pub fn check_admin() -> bool {
-    is_elevated()
+    unsafe { libc::geteuid() == 0 }
 }
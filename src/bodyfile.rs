@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+
+/// One line of a TSK 3.x bodyfile:
+/// MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+pub struct BodyEntry {
+    pub md5: String,
+    pub name: String,
+    pub inode: String,
+    pub mode: String,
+    pub uid: String,
+    pub gid: String,
+    pub size: u64,
+    pub atime: Option<DateTime<Utc>>,
+    pub mtime: Option<DateTime<Utc>>,
+    pub ctime: Option<DateTime<Utc>>,
+    pub crtime: Option<DateTime<Utc>>,
+}
+
+// Seconds since the epoch, newer TSK versions add a fraction. 0 means "not set".
+fn parse_epoch(value: &str) -> Option<DateTime<Utc>> {
+    let seconds: f64 = value.trim().parse().ok()?;
+    if seconds <= 0.0 {
+        return None;
+    }
+    DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+}
+
+pub fn parse_line(line: &str) -> Option<BodyEntry> {
+    // The name may contain '|' itself, so the fixed fields are taken from both ends
+    let fields: Vec<&str> = line.split('|').collect();
+    if fields.len() < 11 {
+        return None;
+    }
+    let tail = &fields[fields.len() - 9..];
+    Some(BodyEntry {
+        md5: fields[0].to_string(),
+        name: fields[1..fields.len() - 9].join("|"),
+        inode: tail[0].to_string(),
+        mode: tail[1].to_string(),
+        uid: tail[2].to_string(),
+        gid: tail[3].to_string(),
+        size: tail[4].trim().parse().unwrap_or(0),
+        atime: parse_epoch(tail[5]),
+        mtime: parse_epoch(tail[6]),
+        ctime: parse_epoch(tail[7]),
+        crtime: parse_epoch(tail[8]),
+    })
+}
+
+/// Expands an entry the way mactime does: one event per distinct time, flagged with
+/// every timestamp that falls on it ("m.c." = modified and changed at the same time).
+pub fn macb_events(entry: &BodyEntry) -> Vec<(DateTime<Utc>, String)> {
+    let times = [(entry.mtime, 'm'), (entry.atime, 'a'), (entry.ctime, 'c'), (entry.crtime, 'b')];
+
+    let mut distinct: Vec<DateTime<Utc>> = times.iter().filter_map(|(t, _)| *t).collect();
+    distinct.sort();
+    distinct.dedup();
+
+    distinct.into_iter()
+        .map(|time| {
+            let flags = times.iter()
+                .map(|(t, flag)| if *t == Some(time) { *flag } else { '.' })
+                .collect();
+            (time, flags)
+        })
+        .collect()
+}
+
+impl BodyEntry {
+    /// Formats the entry as a TSK 3.x bodyfile line, unset times are written as 0.
+    pub fn to_line(&self) -> String {
+        let epoch = |t: Option<DateTime<Utc>>| t.map(|t| t.timestamp()).unwrap_or(0);
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.md5, self.name, self.inode, self.mode, self.uid, self.gid, self.size,
+            epoch(self.atime), epoch(self.mtime), epoch(self.ctime), epoch(self.crtime),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_with_pipes_and_fractional_times() {
+        let entry = parse_line("0|/tmp/a|b|c.txt|1234-128-1|r/rrw-r--r--|1000|1000|42|1700000000.25|1700000100|0|1699999999").unwrap();
+        assert_eq!(entry.name, "/tmp/a|b|c.txt");
+        assert_eq!(entry.inode, "1234-128-1");
+        assert_eq!(entry.uid, "1000");
+        assert_eq!(entry.size, 42);
+        assert_eq!(entry.atime.unwrap().to_rfc3339(), "2023-11-14T22:13:20.250+00:00");
+        assert_eq!(entry.mtime.unwrap().to_rfc3339(), "2023-11-14T22:15:00+00:00");
+        // 0 means not set
+        assert!(entry.ctime.is_none());
+        assert!(parse_line("0|/tmp/short|1|r|0|0|0|0|0").is_none());
+    }
+
+    #[test]
+    fn macb_flags_merge_per_time() {
+        let entry = parse_line("0|/etc/passwd|5|r/rrw-r--r--|0|0|1024|1700000000|1700000000|1700000500|1700000000").unwrap();
+        let events: Vec<(i64, String)> = macb_events(&entry).into_iter().map(|(t, flags)| (t.timestamp(), flags)).collect();
+        assert_eq!(events, vec![(1_700_000_000, "ma.b".to_string()), (1_700_000_500, "..c.".to_string())]);
+        assert_eq!(entry.to_line(), "0|/etc/passwd|5|r/rrw-r--r--|0|0|1024|1700000000|1700000000|1700000500|1700000000");
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Read-only bbolt (BoltDB) access, enough to walk the buckets of containerd's databases.
+// Free list and writes are never needed, the current meta page decides what's live.
+
+const MAGIC: u32 = 0xED0C_DAED;
+const PAGE_HEADER: usize = 16;
+const ELEMENT_SIZE: usize = 16;
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+// Leaf element flag: the value is a nested bucket
+const BUCKET_LEAF: u32 = 0x01;
+// Bucket value header: root page id and sequence, root 0 means the bucket's page follows inline
+const BUCKET_HEADER: usize = 16;
+
+pub struct Db {
+    data: Vec<u8>,
+    page_size: usize,
+    root: u64,
+}
+
+#[derive(Clone, Copy)]
+pub enum Bucket<'a> {
+    Page(u64),
+    Inline(&'a [u8]),
+}
+
+pub enum Item<'a> {
+    Value(&'a [u8]),
+    Bucket(Bucket<'a>),
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(pos..pos.checked_add(8)?)?.try_into().ok()?))
+}
+
+// Checksum of the meta page, FNV-1a 64
+fn fnv64a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// magic, version, page size, flags, root bucket, free list, page count, txid, checksum
+fn read_meta(data: &[u8], offset: usize) -> Option<(u64, usize, u64)> {
+    let meta = offset.checked_add(PAGE_HEADER)?;
+    if u32_at(data, meta)? != MAGIC || fnv64a(data.get(meta..meta + 56)?) != u64_at(data, meta + 56)? {
+        return None;
+    }
+    let page_size = u32_at(data, meta + 8)? as usize;
+    if !page_size.is_power_of_two() || !(512..=65536).contains(&page_size) {
+        return None;
+    }
+    Some((u64_at(data, meta + 48)?, page_size, u64_at(data, meta + 16)?))
+}
+
+/// Reads the whole file, the two meta pages decide which root is current (higher txid wins).
+pub fn open(path: &Path) -> io::Result<Db> {
+    let data = fs::read(path)?;
+    let first = read_meta(&data, 0);
+    // The second meta page sits one page further, a torn first one leaves the usual 4 KiB to try
+    let second = read_meta(&data, first.map(|(_, size, _)| size).unwrap_or(4096));
+    let (_, page_size, root) = match (first, second) {
+        (Some(a), Some(b)) => if b.0 > a.0 { b } else { a },
+        (Some(meta), None) | (None, Some(meta)) => meta,
+        (None, None) => return Err(io::Error::new(io::ErrorKind::InvalidData, "no valid bolt meta page")),
+    };
+    Ok(Db { data, page_size, root })
+}
+
+impl Db {
+    pub fn root(&self) -> Bucket<'_> {
+        Bucket::Page(self.root)
+    }
+
+    // A page with its overflow pages, None if it runs past the file
+    fn page(&self, id: u64) -> Option<&[u8]> {
+        let start = usize::try_from(id).ok()?.checked_mul(self.page_size)?;
+        let overflow = u32_at(&self.data, start.checked_add(12)?)? as usize;
+        let end = overflow.checked_add(1)?.checked_mul(self.page_size)?.checked_add(start)?;
+        self.data.get(start..end)
+    }
+
+    /// All keys of a bucket in page order, values and nested buckets.
+    pub fn items<'a>(&'a self, bucket: Bucket<'a>) -> Vec<(&'a [u8], Item<'a>)> {
+        let mut items = Vec::new();
+        match bucket {
+            Bucket::Page(id) => self.collect_page(id, &mut HashSet::new(), &mut items),
+            Bucket::Inline(page) => collect_leaf(page, &mut items),
+        }
+        items
+    }
+
+    // Visited pages are remembered, a broken file could otherwise point back up the tree
+    fn collect_page<'a>(&'a self, id: u64, visited: &mut HashSet<u64>, items: &mut Vec<(&'a [u8], Item<'a>)>) {
+        if !visited.insert(id) {
+            return;
+        }
+        let Some(page) = self.page(id) else {
+            return;
+        };
+        match u16_at(page, 8) {
+            Some(BRANCH_PAGE) => {
+                let count = u16_at(page, 10).unwrap_or(0) as usize;
+                for i in 0..count {
+                    if let Some(child) = u64_at(page, PAGE_HEADER + i * ELEMENT_SIZE + 8) {
+                        self.collect_page(child, visited, items);
+                    }
+                }
+            }
+            Some(LEAF_PAGE) => collect_leaf(page, items),
+            _ => {}
+        }
+    }
+
+    pub fn get<'a>(&'a self, bucket: Bucket<'a>, key: &[u8]) -> Option<Item<'a>> {
+        self.items(bucket).into_iter().find(|(k, _)| *k == key).map(|(_, item)| item)
+    }
+
+    pub fn bucket<'a>(&'a self, bucket: Bucket<'a>, key: &[u8]) -> Option<Bucket<'a>> {
+        match self.get(bucket, key)? {
+            Item::Bucket(nested) => Some(nested),
+            Item::Value(_) => None,
+        }
+    }
+
+    pub fn value<'a>(&'a self, bucket: Bucket<'a>, key: &[u8]) -> Option<&'a [u8]> {
+        match self.get(bucket, key)? {
+            Item::Value(value) => Some(value),
+            Item::Bucket(_) => None,
+        }
+    }
+
+    /// Nested buckets from the root down, e.g. ["v1", "snapshots"].
+    pub fn path(&self, keys: &[&str]) -> Option<Bucket<'_>> {
+        keys.iter().try_fold(self.root(), |bucket, key| self.bucket(bucket, key.as_bytes()))
+    }
+}
+
+// Leaf element: flags, position of the key relative to the element, key size, value size
+fn collect_leaf<'a>(page: &'a [u8], items: &mut Vec<(&'a [u8], Item<'a>)>) {
+    if u16_at(page, 8) != Some(LEAF_PAGE) {
+        return;
+    }
+    let count = u16_at(page, 10).unwrap_or(0) as usize;
+    for i in 0..count {
+        let element = PAGE_HEADER + i * ELEMENT_SIZE;
+        let (Some(flags), Some(pos), Some(key_size), Some(value_size)) =
+            (u32_at(page, element), u32_at(page, element + 4), u32_at(page, element + 8), u32_at(page, element + 12))
+        else {
+            return;
+        };
+        let key_start = element + pos as usize;
+        let value_start = key_start + key_size as usize;
+        let (Some(key), Some(value)) = (
+            page.get(key_start..value_start),
+            page.get(value_start..value_start + value_size as usize),
+        ) else {
+            continue;
+        };
+        let item = if flags & BUCKET_LEAF == 0 {
+            Item::Value(value)
+        } else {
+            match u64_at(value, 0) {
+                Some(0) => Item::Bucket(Bucket::Inline(value.get(BUCKET_HEADER..).unwrap_or_default())),
+                Some(root) => Item::Bucket(Bucket::Page(root)),
+                None => continue,
+            }
+        };
+        items.push((key, item));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub enum Node {
+        Value(Vec<u8>),
+        Bucket(Vec<(&'static str, Node)>),
+    }
+
+    // One leaf page, nested buckets inline like bolt stores small ones
+    fn leaf(entries: &[(&'static str, Node)]) -> Vec<u8> {
+        let values: Vec<(u32, Vec<u8>)> = entries.iter().map(|(_, node)| match node {
+            Node::Value(value) => (0, value.clone()),
+            Node::Bucket(children) => (BUCKET_LEAF, [vec![0; BUCKET_HEADER], leaf(children)].concat()),
+        }).collect();
+        let mut page = vec![0; PAGE_HEADER];
+        page[8..10].copy_from_slice(&LEAF_PAGE.to_le_bytes());
+        page[10..12].copy_from_slice(&(entries.len() as u16).to_le_bytes());
+        let mut data = Vec::new();
+        for (i, ((key, _), (flags, value))) in entries.iter().zip(&values).enumerate() {
+            let element = PAGE_HEADER + i * ELEMENT_SIZE;
+            let pos = PAGE_HEADER + entries.len() * ELEMENT_SIZE + data.len() - element;
+            for field in [*flags, pos as u32, key.len() as u32, value.len() as u32] {
+                page.extend_from_slice(&field.to_le_bytes());
+            }
+            data.extend_from_slice(key.as_bytes());
+            data.extend_from_slice(value);
+        }
+        page.extend(data);
+        page
+    }
+
+    fn meta(txid: u64, root: u64) -> Vec<u8> {
+        let mut page = vec![0; 4096];
+        page[8..10].copy_from_slice(&0x04u16.to_le_bytes());
+        let meta = &mut page[PAGE_HEADER..];
+        meta[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        meta[4..8].copy_from_slice(&2u32.to_le_bytes());
+        meta[8..12].copy_from_slice(&4096u32.to_le_bytes());
+        meta[16..24].copy_from_slice(&root.to_le_bytes());
+        meta[48..56].copy_from_slice(&txid.to_le_bytes());
+        let checksum = fnv64a(&meta[..56]);
+        meta[56..64].copy_from_slice(&checksum.to_le_bytes());
+        page
+    }
+
+    /// A database whose root leaf (page 3) holds `entries`. Meta page 1 is current, page 0 points nowhere.
+    pub fn build(entries: &[(&'static str, Node)]) -> Vec<u8> {
+        let mut root = leaf(entries);
+        let pages = root.len().div_ceil(4096);
+        root[0..8].copy_from_slice(&3u64.to_le_bytes());
+        root[12..16].copy_from_slice(&(pages as u32 - 1).to_le_bytes());
+        root.resize(pages * 4096, 0);
+        [meta(1, 2), meta(2, 3), vec![0; 4096], root].concat()
+    }
+
+    #[test]
+    fn nested_buckets() {
+        let path = std::env::temp_dir().join(format!("tracenexus-bolt-{}.db", std::process::id()));
+        fs::write(&path, build(&[
+            ("v1", Node::Bucket(vec![
+                ("name", Node::Value(b"web".to_vec())),
+                ("labels", Node::Bucket(vec![("app", Node::Value(b"nginx".to_vec()))])),
+            ])),
+        ])).unwrap();
+        let db = open(&path).unwrap();
+        let v1 = db.path(&["v1"]).unwrap();
+        assert_eq!(db.value(v1, b"name"), Some(&b"web"[..]));
+        assert!(db.value(v1, b"labels").is_none());
+        assert_eq!(db.value(db.path(&["v1", "labels"]).unwrap(), b"app"), Some(&b"nginx"[..]));
+        assert!(db.path(&["v2"]).is_none());
+
+        // Broken checksums on both meta pages
+        let mut data = fs::read(&path).unwrap();
+        data[PAGE_HEADER + 56] ^= 1;
+        data[4096 + PAGE_HEADER + 56] ^= 1;
+        fs::write(&path, data).unwrap();
+        assert!(open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::DateTime;
+use rusqlite::{Connection, OpenFlags};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::ui;
+use crate::users::UserProfile;
+
+// Microseconds between 1601-01-01 (WebKit/Chromium epoch) and 1970-01-01
+const WEBKIT_UNIX_DIFF_US: i64 = 11_644_473_600_000_000;
+
+// Chromium based browsers share the same History layout
+const CHROMIUM_BROWSERS: &[(&str, &str)] = &[
+    ("Chrome", "AppData/Local/Google/Chrome/User Data"),
+    ("Edge", "AppData/Local/Microsoft/Edge/User Data"),
+];
+
+const FIREFOX_PROFILES: &str = "AppData/Roaming/Mozilla/Firefox/Profiles";
+
+const CHROMIUM_VISITS: &str = "
+    SELECT urls.url, urls.title, visits.visit_time, urls.visit_count, visits.transition
+    FROM visits JOIN urls ON visits.url = urls.id";
+
+const CHROMIUM_DOWNLOADS: &str = "
+    SELECT d.target_path, d.start_time, d.end_time, d.received_bytes, d.total_bytes,
+           (SELECT c.url FROM downloads_url_chains c WHERE c.id = d.id ORDER BY c.chain_index DESC LIMIT 1)
+    FROM downloads d";
+
+const FIREFOX_VISITS: &str = "
+    SELECT p.url, p.title, v.visit_date, p.visit_count, v.visit_type
+    FROM moz_historyvisits v JOIN moz_places p ON v.place_id = p.id";
+
+const FIREFOX_DOWNLOADS: &str = "
+    SELECT p.url, a.content, a.dateAdded
+    FROM moz_annos a
+    JOIN moz_places p ON a.place_id = p.id
+    JOIN moz_anno_attributes n ON a.anno_attribute_id = n.id
+    WHERE n.name = 'downloads/destinationFileURI'";
+
+/// Copies and parses the Chromium and Firefox history databases of every user profile.
+/// Results are written as Browser_<browser>_<user>.json into the output directory.
+pub fn collect(out_dir: &str, profiles: &[UserProfile]) {
+    ui::info("Executing: Browser history collection");
+
+    let copy_dir = Path::new(out_dir).join("browser");
+    fs::create_dir_all(&copy_dir).ok();
+
+    for profile in profiles {
+        for (browser, db_path) in find_databases(Path::new(&profile.profile_path)) {
+            // e.g. S-1-5-21-..._Chrome_Default_History, browser profile names repeat across users
+            let browser_profile = db_path.parent()
+                .and_then(|p| p.file_name())
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let db_name = db_path.file_name().unwrap().to_string_lossy().to_string();
+            let copy_path = copy_dir.join(format!("{}_{}_{}_{}", profile.file_id(), browser, browser_profile, db_name));
+
+            if let Err(e) = copy_locked(&db_path, &copy_path) {
+                ui::error(&format!("Could not copy {}: {}", db_path.display(), e));
+                continue;
+            }
+
+            let result = if browser == "Firefox" {
+                parse_firefox(&copy_path)
+            } else {
+                parse_chromium(&copy_path)
+            };
+
+            match result {
+                Ok(mut records) => {
+                    for record in records.iter_mut() {
+                        if let Some(obj) = record.as_object_mut() {
+                            obj.insert("Browser".into(), Value::String(browser.to_string()));
+                            obj.insert("BrowserProfile".into(), Value::String(browser_profile.clone()));
+                            obj.insert("UserName".into(), Value::String(profile.username.clone()));
+                            obj.insert("UserSid".into(), Value::String(profile.sid.clone()));
+                        }
+                    }
+
+                    let target = Path::new(out_dir).join(format!("Browser_{}_{}_{}.json", browser, browser_profile, profile.file_id()));
+                    let _ = fs::write(&target, serde_json::to_string(&records).unwrap());
+                    ui::success(&format!("{} ({} / {}): {} records", browser, profile.username, browser_profile, records.len()));
+                },
+                Err(e) => ui::error(&format!("Could not parse {}: {}", copy_path.display(), e)),
+            }
+        }
+    }
+}
+
+fn find_databases(profile_dir: &Path) -> Vec<(&'static str, PathBuf)> {
+    let mut found = Vec::new();
+
+    for (browser, user_data) in CHROMIUM_BROWSERS {
+        // "Default", "Profile 1", ... each have their own History database
+        if let Ok(entries) = fs::read_dir(profile_dir.join(user_data)) {
+            for entry in entries.flatten() {
+                let history = entry.path().join("History");
+                if history.is_file() {
+                    found.push((*browser, history));
+                }
+            }
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(profile_dir.join(FIREFOX_PROFILES)) {
+        for entry in entries.flatten() {
+            let places = entry.path().join("places.sqlite");
+            if places.is_file() {
+                found.push(("Firefox", places));
+            }
+        }
+    }
+    found
+}
+
+/// Copies a database that the running browser holds open, including its WAL/journal
+/// so uncommitted visits are not lost. Falls back to a shadow copy if a plain read fails.
+fn copy_locked(source: &Path, target: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if fs::copy(source, target).is_err() {
+        let status = std::process::Command::new("C:\\Windows\\System32\\esentutl.exe")
+            .args(["/y", &source.to_string_lossy(), "/vss", "/d", &target.to_string_lossy()])
+            .output()?
+            .status;
+        if !status.success() {
+            return Err("esentutl shadow copy failed".into());
+        }
+    }
+
+    for suffix in ["-wal", "-journal"] {
+        let side_file = PathBuf::from(format!("{}{}", source.display(), suffix));
+        if side_file.exists() {
+            let _ = fs::copy(&side_file, format!("{}{}", target.display(), suffix));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a Chromium History database (visits and downloads).
+pub fn parse_chromium(db_path: &Path) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut records = Vec::new();
+
+    let mut stmt = conn.prepare(CHROMIUM_VISITS)?;
+    let rows = stmt.query_map([], |row| {
+        Ok(json!({
+            "Event": "Visit",
+            "Url": row.get::<_, Option<String>>(0)?,
+            "Title": row.get::<_, Option<String>>(1)?,
+            "VisitTime": webkit_to_rfc3339(row.get::<_, Option<i64>>(2)?.unwrap_or(0)),
+            "VisitCount": row.get::<_, Option<i64>>(3)?,
+            "Transition": chromium_transition(row.get::<_, Option<i64>>(4)?.unwrap_or(0)),
+        }))
+    })?;
+    records.extend(rows.filter_map(|r| r.ok()));
+
+    // Older History files may not have the downloads tables
+    if let Ok(mut stmt) = conn.prepare(CHROMIUM_DOWNLOADS) {
+        let rows = stmt.query_map([], |row| {
+            Ok(json!({
+                "Event": "Download",
+                "TargetPath": row.get::<_, Option<String>>(0)?,
+                "StartTime": webkit_to_rfc3339(row.get::<_, Option<i64>>(1)?.unwrap_or(0)),
+                "EndTime": webkit_to_rfc3339(row.get::<_, Option<i64>>(2)?.unwrap_or(0)),
+                "ReceivedBytes": row.get::<_, Option<i64>>(3)?,
+                "TotalBytes": row.get::<_, Option<i64>>(4)?,
+                "Url": row.get::<_, Option<String>>(5)?,
+            }))
+        })?;
+        records.extend(rows.filter_map(|r| r.ok()));
+    }
+
+    Ok(records)
+}
+
+/// Parses a Firefox places.sqlite database (visits and downloads).
+pub fn parse_firefox(db_path: &Path) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut records = Vec::new();
+
+    let mut stmt = conn.prepare(FIREFOX_VISITS)?;
+    let rows = stmt.query_map([], |row| {
+        Ok(json!({
+            "Event": "Visit",
+            "Url": row.get::<_, Option<String>>(0)?,
+            "Title": row.get::<_, Option<String>>(1)?,
+            "VisitTime": prtime_to_rfc3339(row.get::<_, Option<i64>>(2)?.unwrap_or(0)),
+            "VisitCount": row.get::<_, Option<i64>>(3)?,
+            "Transition": firefox_transition(row.get::<_, Option<i64>>(4)?.unwrap_or(0)),
+        }))
+    })?;
+    records.extend(rows.filter_map(|r| r.ok()));
+
+    // Downloads are stored as annotations on the source URL
+    if let Ok(mut stmt) = conn.prepare(FIREFOX_DOWNLOADS) {
+        let rows = stmt.query_map([], |row| {
+            Ok(json!({
+                "Event": "Download",
+                "Url": row.get::<_, Option<String>>(0)?,
+                "TargetPath": row.get::<_, Option<String>>(1)?,
+                "StartTime": prtime_to_rfc3339(row.get::<_, Option<i64>>(2)?.unwrap_or(0)),
+            }))
+        })?;
+        records.extend(rows.filter_map(|r| r.ok()));
+    }
+
+    Ok(records)
+}
+
+/// WebKit time: microseconds since 1601-01-01 UTC.
+fn webkit_to_rfc3339(webkit: i64) -> String {
+    if webkit <= 0 {
+        return String::new();
+    }
+    prtime_to_rfc3339(webkit - WEBKIT_UNIX_DIFF_US)
+}
+
+/// PRTime: microseconds since 1970-01-01 UTC.
+fn prtime_to_rfc3339(prtime: i64) -> String {
+    if prtime <= 0 {
+        return String::new();
+    }
+    DateTime::from_timestamp_micros(prtime)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+// The lower byte of the transition holds the core type
+fn chromium_transition(transition: i64) -> &'static str {
+    match transition & 0xFF {
+        0 => "LINK",
+        1 => "TYPED",
+        2 => "AUTO_BOOKMARK",
+        3 => "AUTO_SUBFRAME",
+        4 => "MANUAL_SUBFRAME",
+        5 => "GENERATED",
+        6 => "AUTO_TOPLEVEL",
+        7 => "FORM_SUBMIT",
+        8 => "RELOAD",
+        9 => "KEYWORD",
+        10 => "KEYWORD_GENERATED",
+        _ => "UNKNOWN",
+    }
+}
+
+// moz_historyvisits.visit_type, named like the Chromium core type where there is one
+fn firefox_transition(visit_type: i64) -> &'static str {
+    match visit_type {
+        1 => "LINK",
+        2 => "TYPED",
+        3 => "AUTO_BOOKMARK",
+        4 => "AUTO_SUBFRAME",
+        5 => "REDIRECT_PERMANENT",
+        6 => "REDIRECT_TEMPORARY",
+        7 => "DOWNLOAD",
+        8 => "MANUAL_SUBFRAME",
+        9 => "RELOAD",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(name: &str, schema: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracenexus-browser-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        Connection::open(&path).unwrap().execute_batch(schema).unwrap();
+        path
+    }
+
+    #[test]
+    fn chromium_history() {
+        let path = database("History", "
+            CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT, title TEXT, visit_count INTEGER);
+            CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER, transition INTEGER);
+            CREATE TABLE downloads (id INTEGER PRIMARY KEY, target_path TEXT, start_time INTEGER, end_time INTEGER,
+                                    received_bytes INTEGER, total_bytes INTEGER);
+            CREATE TABLE downloads_url_chains (id INTEGER, chain_index INTEGER, url TEXT);
+            INSERT INTO urls VALUES (1, 'https://example.com/', 'Example', 3);
+            -- TYPED with the CHAIN_START qualifier set
+            INSERT INTO visits VALUES (1, 1, 13300000000000000, 268435457);
+            INSERT INTO downloads VALUES (1, 'C:\\Users\\bob\\Downloads\\tool.exe', 13300000000000000, 0, 10, 10);
+            INSERT INTO downloads_url_chains VALUES (1, 0, 'https://example.com/redirect'), (1, 1, 'https://cdn.example.com/tool.exe');
+        ");
+        let records = parse_chromium(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Url"], "https://example.com/");
+        assert_eq!(records[0]["VisitTime"], "2022-06-18T04:26:40+00:00");
+        assert_eq!(records[0]["Transition"], "TYPED");
+        assert_eq!(records[1]["Url"], "https://cdn.example.com/tool.exe");
+        assert_eq!(records[1]["StartTime"], "2022-06-18T04:26:40+00:00");
+        // Unfinished downloads have end_time 0
+        assert_eq!(records[1]["EndTime"], "");
+    }
+
+    #[test]
+    fn firefox_places() {
+        let path = database("places.sqlite", "
+            CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT, visit_count INTEGER);
+            CREATE TABLE moz_historyvisits (id INTEGER PRIMARY KEY, place_id INTEGER, visit_date INTEGER, visit_type INTEGER);
+            CREATE TABLE moz_anno_attributes (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE moz_annos (id INTEGER PRIMARY KEY, place_id INTEGER, anno_attribute_id INTEGER, content TEXT, dateAdded INTEGER);
+            INSERT INTO moz_places VALUES (1, 'https://example.org/', 'Example', 1);
+            INSERT INTO moz_historyvisits VALUES (1, 1, 1700000000123456, 2);
+            INSERT INTO moz_anno_attributes VALUES (1, 'downloads/destinationFileURI');
+            INSERT INTO moz_annos VALUES (1, 1, 1, 'file:///home/bob/Downloads/a.zip', 1700000000123456);
+        ");
+        let records = parse_firefox(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["VisitTime"], "2023-11-14T22:13:20.123456+00:00");
+        assert_eq!(records[0]["Transition"], "TYPED");
+        assert_eq!(records[1]["TargetPath"], "file:///home/bob/Downloads/a.zip");
+        assert_eq!(records[1]["StartTime"], "2023-11-14T22:13:20.123456+00:00");
+    }
+
+    #[test]
+    fn time_conversions() {
+        assert_eq!(webkit_to_rfc3339(WEBKIT_UNIX_DIFF_US), "");
+        assert_eq!(webkit_to_rfc3339(WEBKIT_UNIX_DIFF_US + 1_000_000), "1970-01-01T00:00:01+00:00");
+        assert_eq!(prtime_to_rfc3339(0), "");
+        assert_eq!(prtime_to_rfc3339(i64::MAX), "");
+    }
+}
@@ -0,0 +1,279 @@
+use rusqlite::{params, params_from_iter, Connection};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::refiner;
+use crate::ui;
+
+// Written into refined/ so it travels with the refined package
+pub const CASE_DB_FILE: &str = "case.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE case_info (key TEXT PRIMARY KEY, value TEXT);
+    CREATE TABLE sources (table_name TEXT PRIMARY KEY, file TEXT, category TEXT, records INTEGER);
+    CREATE TABLE timeline (
+        id INTEGER PRIMARY KEY,
+        ts TEXT,
+        ts_raw TEXT,
+        ts_field TEXT,
+        ts_desc TEXT,
+        category TEXT,
+        subcategory TEXT,
+        attack TEXT,
+        source TEXT,
+        suspicious_time INTEGER,
+        data TEXT
+    );
+    CREATE INDEX timeline_ts ON timeline (ts);
+    CREATE INDEX timeline_category ON timeline (category);
+    CREATE INDEX timeline_source ON timeline (source);
+    -- SELECT * FROM timeline WHERE id IN (SELECT rowid FROM timeline_fts WHERE timeline_fts MATCH 'mimikatz')
+    CREATE VIRTUAL TABLE timeline_fts USING fts5 (data, content = 'timeline', content_rowid = 'id');
+    -- One row per record of every source table, record_id is its rowid there
+    CREATE VIRTUAL TABLE records_fts USING fts5 (table_name UNINDEXED, record_id UNINDEXED, data);
+";
+
+/// Builds refined/case.sqlite from the refined JSON, the master timeline and the case summary.
+/// An existing database is replaced.
+pub fn build(refined_path: &Path) {
+    ui::info("Building case database...");
+    let db_path = refined_path.join(CASE_DB_FILE);
+    let _ = fs::remove_file(&db_path);
+
+    match write_db(refined_path, &db_path) {
+        Ok(sources) => ui::success(&format!("Created {} ({} source tables)", CASE_DB_FILE, sources)),
+        Err(e) => {
+            ui::error(&format!("Could not build {}: {}", CASE_DB_FILE, e));
+            // Half written databases only confuse whoever opens them
+            let _ = fs::remove_file(&db_path);
+        }
+    }
+}
+
+fn write_db(refined_path: &Path, db_path: &Path) -> rusqlite::Result<usize> {
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    insert_case_info(&tx, refined_path)?;
+    insert_timeline(&tx, refined_path)?;
+
+    let mut sources = 0;
+    let mut table_names: HashMap<String, usize> = HashMap::new();
+    for entry in WalkDir::new(refined_path).sort_by_file_name().into_iter().filter_map(|e| e.ok()).filter(|e| e.path().is_file()) {
+        let path = entry.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        if !file_name.ends_with(".json") || refiner::is_case_file(&file_name) {
+            continue;
+        }
+        let category = path.parent()
+            .filter(|p| *p != refined_path)
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Logs_Linux_AuthLog, a second file with the same name gets _2
+        let mut table = table_name(&category, &file_name);
+        let seen = table_names.entry(table.to_lowercase()).or_default();
+        *seen += 1;
+        if *seen > 1 {
+            table = format!("{}_{}", table, seen);
+        }
+
+        let records = refiner::read_json_records(path);
+        insert_source(&tx, &table, &records)?;
+        tx.execute(
+            "INSERT INTO sources (table_name, file, category, records) VALUES (?1, ?2, ?3, ?4)",
+            params![table, file_name, category, records.len()],
+        )?;
+        sources += 1;
+    }
+
+    tx.execute("INSERT INTO timeline_fts (timeline_fts) VALUES ('rebuild')", [])?;
+    tx.commit()?;
+    Ok(sources)
+}
+
+// case_summary.json flattened to dotted keys ("system.hostname")
+fn insert_case_info(tx: &Connection, refined_path: &Path) -> rusqlite::Result<()> {
+    let Some(summary) = fs::read_to_string(refined_path.join("case_summary.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+    else {
+        return Ok(());
+    };
+    let mut rows = Vec::new();
+    refiner::flatten_json("", &summary, &mut rows);
+    let mut stmt = tx.prepare("INSERT OR REPLACE INTO case_info (key, value) VALUES (?1, ?2)")?;
+    for (key, value) in rows {
+        let value = match value {
+            Value::String(s) => s,
+            other => other.to_string(),
+        };
+        stmt.execute(params![key, value])?;
+    }
+    Ok(())
+}
+
+fn insert_timeline(tx: &Connection, refined_path: &Path) -> rusqlite::Result<()> {
+    let events = refiner::read_json_records(&refined_path.join("master_timeline.json"));
+    let mut stmt = tx.prepare(
+        "INSERT INTO timeline (ts, ts_raw, ts_field, ts_desc, category, subcategory, attack, source, suspicious_time, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )?;
+    for event in &events {
+        let text = |key: &str| event.get(key).and_then(|v| v.as_str());
+        // "T1078, T1021" is easier to LIKE than a JSON array
+        let attack = event.get("attack").and_then(|v| v.as_array()).map(|tags| {
+            tags.iter().filter_map(|t| t.as_str()).collect::<Vec<_>>().join(", ")
+        });
+        stmt.execute(params![
+            text("ts"),
+            text("ts_raw"),
+            text("ts_field"),
+            text("ts_desc"),
+            text("cat"),
+            text("subcat"),
+            attack,
+            text("src"),
+            event.get("suspicious_time").and_then(|v| v.as_bool()).unwrap_or(false),
+            event.get("data").map(|d| d.to_string()),
+        ])?;
+    }
+    Ok(())
+}
+
+fn table_name(category: &str, file_name: &str) -> String {
+    let stem = file_name.trim_end_matches(".json");
+    let name = if category.is_empty() { stem.to_string() } else { format!("{}_{}", category, stem) };
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+// One column per top-level field, nested values as JSON text. Column names in SQLite ignore case,
+// "Timestamp" and "timestamp" share the first one. A field called record_id becomes data_record_id,
+// the name belongs to our key.
+fn insert_source(tx: &Connection, table: &str, records: &[Value]) -> rusqlite::Result<()> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records.iter().filter_map(|r| r.as_object()) {
+        for key in record.keys() {
+            index.entry(key.to_lowercase()).or_insert_with(|| {
+                columns.push(key.clone());
+                columns.len() - 1
+            });
+        }
+    }
+    if let Some(&i) = index.get("record_id") {
+        let mut renamed = format!("data_{}", columns[i]);
+        while index.contains_key(&renamed.to_lowercase()) {
+            renamed = format!("data_{}", renamed);
+        }
+        columns[i] = renamed;
+    }
+
+    let mut definition: Vec<String> = vec!["record_id INTEGER PRIMARY KEY".to_string()];
+    definition.extend(columns.iter().map(|c| quote(c)));
+    tx.execute_batch(&format!("CREATE TABLE {} ({});", quote(table), definition.join(", ")))?;
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders: Vec<String> = (1..=columns.len() + 1).map(|i| format!("?{}", i)).collect();
+    let mut insert = tx.prepare(&format!(
+        "INSERT INTO {} (record_id{}) VALUES ({})",
+        quote(table),
+        columns.iter().map(|c| format!(", {}", quote(c))).collect::<String>(),
+        placeholders.join(", "),
+    ))?;
+    let mut fts = tx.prepare("INSERT INTO records_fts (table_name, record_id, data) VALUES (?1, ?2, ?3)")?;
+
+    for (record_id, record) in records.iter().enumerate() {
+        let record_id = record_id as i64 + 1;
+        let mut values: Vec<rusqlite::types::Value> = vec![rusqlite::types::Value::Null; columns.len()];
+        if let Some(map) = record.as_object() {
+            fill_row(map, &index, &mut values);
+        }
+        insert.execute(params_from_iter(std::iter::once(rusqlite::types::Value::Integer(record_id)).chain(values)))?;
+        fts.execute(params![table, record_id, record.to_string()])?;
+    }
+    Ok(())
+}
+
+fn fill_row(record: &Map<String, Value>, index: &HashMap<String, usize>, values: &mut [rusqlite::types::Value]) {
+    use rusqlite::types::Value as Sql;
+    for (key, value) in record {
+        let Some(&i) = index.get(&key.to_lowercase()) else {
+            continue;
+        };
+        if values[i] != Sql::Null {
+            continue;
+        }
+        values[i] = match value {
+            Value::Null => Sql::Null,
+            Value::Bool(b) => Sql::Integer(*b as i64),
+            Value::Number(n) => n.as_i64().map(Sql::Integer)
+                .or_else(|| n.as_f64().map(Sql::Real))
+                .unwrap_or_else(|| Sql::Text(n.to_string())),
+            Value::String(s) => Sql::Text(s.clone()),
+            other => Sql::Text(other.to_string()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn database_from_refined_dir() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-casedb-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Logs")).unwrap();
+        fs::write(dir.join("case_summary.json"), json!({"case_id": "INC-TEST", "system": {"hostname": "web01"}}).to_string()).unwrap();
+        fs::write(dir.join("master_timeline.json"), json!([
+            {"ts": "2024-03-01T10:00:00Z", "cat": "Logs", "src": "AuthLog.json", "attack": ["T1078", "T1021"], "data": {"message": "Accepted password for root"}},
+            {"ts": "2024-03-01T11:00:00Z", "cat": "Logs", "src": "AuthLog.json", "data": {"message": "session closed"}},
+        ]).to_string()).unwrap();
+        // record_id and data_record_id of the source must both survive next to our key
+        fs::write(dir.join("Logs").join("AuthLog.json"), json!([
+            {"record_id": 77, "data_record_id": "x", "message": "Accepted password for root", "user": "root"},
+            {"record_id": 78, "Message": "mimikatz.exe started"},
+        ]).to_string()).unwrap();
+
+        let db_path = dir.join(CASE_DB_FILE);
+        assert_eq!(write_db(&dir, &db_path).unwrap(), 1);
+        let conn = Connection::open(&db_path).unwrap();
+
+        let hostname: String = conn.query_row("SELECT value FROM case_info WHERE key = 'system.hostname'", [], |r| r.get(0)).unwrap();
+        assert_eq!(hostname, "web01");
+
+        let rows: Vec<(i64, i64, Option<String>, String)> = conn
+            .prepare("SELECT record_id, data_data_record_id, data_record_id, message FROM Logs_AuthLog ORDER BY record_id").unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))).unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(rows, vec![
+            (1, 77, Some("x".to_string()), "Accepted password for root".to_string()),
+            (2, 78, None, "mimikatz.exe started".to_string()),
+        ]);
+
+        let hit: (String, i64) = conn.query_row(
+            "SELECT table_name, record_id FROM records_fts WHERE records_fts MATCH 'mimikatz'", [], |r| Ok((r.get(0)?, r.get(1)?)),
+        ).unwrap();
+        assert_eq!(hit, ("Logs_AuthLog".to_string(), 2));
+
+        let (ts, attack): (String, String) = conn.query_row(
+            "SELECT ts, attack FROM timeline WHERE id IN (SELECT rowid FROM timeline_fts WHERE timeline_fts MATCH 'accepted')",
+            [], |r| Ok((r.get(0)?, r.get(1)?)),
+        ).unwrap();
+        assert_eq!((ts.as_str(), attack.as_str()), ("2024-03-01T10:00:00Z", "T1078, T1021"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
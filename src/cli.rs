@@ -1,4 +1,6 @@
-use clap::Parser;
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::timezone;
 
 #[derive(Parser, Debug)]
 #[command(name = "trace-nexus")]
@@ -6,9 +8,153 @@ use clap::Parser;
 #[command(version = "0.1.0")]
 #[command(about = "Lightweight forensic artifact collector", long_about = None)]
 pub struct Cli {
-    #[arg(long, conflicts_with = "full")]
+    #[arg(long, conflicts_with_all = ["full", "linux"])]
     pub light: bool,
 
-    #[arg(long, conflicts_with = "light")]
+    #[arg(long, conflicts_with_all = ["light", "linux"])]
     pub full: bool,
+
+    /// Native triage for Linux hosts, needs no external tools
+    #[arg(long, conflicts_with_all = ["light", "full"])]
+    pub linux: bool,
+
+    /// Collect from a mounted or extracted Linux root instead of the live system
+    #[arg(long, requires = "linux")]
+    pub source_root: Option<String>,
+
+    /// Read systemd journal files from this directory instead of /var/log/journal (e.g. copied from another host)
+    #[arg(long, requires = "linux")]
+    pub journal_dir: Option<String>,
+
+    /// Process an E01 or raw (dd) disk image instead of the live system, runs FULL unless --light is set
+    #[arg(long, conflicts_with = "linux")]
+    pub image: Option<String>,
+
+    /// Zone the source host wrote local times in (IANA or Windows name), overrides the collected
+    /// TimeZoneInformation / /etc/timezone
+    #[arg(long, global = true, value_parser = parse_source_tz)]
+    pub source_tz: Option<Tz>,
+
+    /// Keep every CSV cell a string in the refined JSON (no number, boolean or null inference)
+    #[arg(long)]
+    pub strings_only: bool,
+
+    /// Exit with code 2 (and skip the upload) when refinement reports more errors than this
+    #[arg(long)]
+    pub max_errors: Option<usize>,
+
+    /// Map known sources onto a common event schema, the tool's fields are kept under "original"
+    #[arg(long, value_enum)]
+    pub normalize: Option<EventSchema>,
+
+    /// Layout of the refined files and master_timeline.json, defaults to json (an import keeps the case's format)
+    #[arg(long, value_enum, global = true)]
+    pub output_format: Option<OutputFormat>,
+
+    /// Also write refined/case.sqlite (one table per source, timeline and FTS5 full-text indexes)
+    #[arg(long)]
+    pub sqlite: bool,
+
+    /// Also write refined/timesketch.jsonl (message, datetime, timestamp_desc and the record as attributes)
+    #[arg(long)]
+    pub timesketch: bool,
+
+    /// Write timesketch.jsonl and import it into Timesketch set in .env (TIMESKETCH_URL, TIMESKETCH_USER, TIMESKETCH_PASSWORD)
+    #[arg(long)]
+    pub timesketch_upload: bool,
+
+    /// Category rules file, defaults to rules/categories.json or the built-in rules
+    #[arg(long, global = true)]
+    pub rules: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Imports of collections made by other tools and helpers around the refinement
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Parse a KAPE target collection (the folder holding C\Windows\... and *_CopyLog.csv)
+    ImportKape {
+        dir: String,
+    },
+    /// Parse a Velociraptor offline collection ZIP (results/*.json and uploads/)
+    ImportVelociraptor {
+        zip: String,
+    },
+    /// Merge a timeline from another tool into master_timeline.json of the case in ./output
+    ImportTimeline {
+        file: String,
+
+        #[arg(long, value_enum)]
+        format: TimelineFormat,
+
+        /// Name the events are labeled with in the timeline, defaults to the file name
+        #[arg(long)]
+        label: Option<String>,
+
+        /// Column mapping for --format csv, e.g. --map timestamp=EventTime --map message=Details
+        #[arg(long = "map", value_parser = parse_mapping)]
+        mapping: Vec<(String, String)>,
+
+        /// strftime format of the CSV timestamp column, e.g. "%d.%m.%Y %H:%M"
+        #[arg(long)]
+        time_format: Option<String>,
+    },
+    /// Inspect the category rules
+    Categories {
+        #[command(subcommand)]
+        action: CategoriesAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CategoriesAction {
+    /// Show which rule categorizes a file and which rules tag its records
+    Test {
+        file: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// One minified JSON array per file
+    #[default]
+    Json,
+    /// JSON Lines, one record per line
+    Ndjson,
+    /// JSON arrays plus typed Parquet copies in refined/parquet/
+    Parquet,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum EventSchema {
+    /// Elastic Common Schema (process.executable, file.path, user.name, event.code, ...)
+    Ecs,
+    /// Open Cybersecurity Schema Framework
+    Ocsf,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum TimelineFormat {
+    /// TSK 3.x bodyfile (mactime input)
+    Bodyfile,
+    /// plaso psort -o l2tcsv
+    L2tcsv,
+    /// plaso psort -o json_line
+    #[value(name = "json_line")]
+    JsonLine,
+    /// Any CSV, columns assigned with --map
+    Csv,
+}
+
+fn parse_mapping(value: &str) -> Result<(String, String), String> {
+    value.split_once('=')
+        .map(|(key, column)| (key.trim().to_string(), column.trim().to_string()))
+        .ok_or_else(|| format!("expected key=column, got '{}'", value))
+}
+
+fn parse_source_tz(value: &str) -> Result<Tz, String> {
+    timezone::parse_zone(value)
+        .ok_or_else(|| format!("unknown time zone '{}', expected e.g. Europe/Berlin or \"W. Europe Standard Time\"", value))
 }
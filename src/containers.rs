@@ -0,0 +1,544 @@
+use chrono::DateTime;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::bolt::{self, Item};
+use crate::linux;
+use crate::ui;
+use crate::volatile::hash_file;
+
+const DOCKER_ROOT: &str = "var/lib/docker";
+const CONTAINERD_ROOT: &str = "var/lib/containerd";
+const CONTAINERD_META: &str = "io.containerd.metadata.v1.bolt/meta.db";
+const OVERLAYFS_SNAPSHOTTER: &str = "io.containerd.snapshotter.v1.overlayfs";
+const CONTAINERD_TASKS: &str = "run/containerd/io.containerd.runtime.v2.task";
+const CRI_LOGS: &str = "var/log/containers";
+
+// Whiteout file that hides all lower-layer content of its directory
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+// Upper dirs of busy containers can be huge, hashing stops above this size
+const MAX_HASH_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Collects container metadata, overlay upper-dir changes and container logs below `root`.
+/// Every record carries the ContainerId so the timeline can be pivoted per container.
+pub fn collect(root: &Path, out_dir: &str) {
+    let docker_root = root.join(DOCKER_ROOT);
+    let containerd_root = root.join(CONTAINERD_ROOT);
+    let tasks_root = root.join(CONTAINERD_TASKS);
+    if !docker_root.exists() && !containerd_root.join(CONTAINERD_META).exists() && !tasks_root.exists() {
+        ui::info("No Docker or containerd state found, skipping container collection.");
+        return;
+    }
+
+    ui::info("Executing: Container collection");
+    let mut inventory = Vec::new();
+    let mut changes = Vec::new();
+    let mut logs = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(docker_root.join("containers")) {
+        for entry in entries.flatten() {
+            let container_dir = entry.path();
+            let id = entry.file_name().to_string_lossy().to_string();
+
+            let Some(container) = read_docker_container(&container_dir, &id) else {
+                continue;
+            };
+            inventory.push(container);
+
+            if let Some(upper_dir) = docker_upper_dir(&docker_root, &id) {
+                changes.extend(upper_dir_changes(&upper_dir, &id));
+            }
+            logs.extend(read_json_log(&container_dir.join(format!("{}-json.log", id)), &id));
+        }
+    }
+
+    // containerd keeps runtime state only for running tasks: <namespace>/<id>/config.json (OCI spec)
+    let tasks: Vec<Value> = WalkDir::new(&tasks_root).min_depth(2).max_depth(2).into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| read_containerd_task(entry.path()))
+        .collect();
+    let running: HashSet<&str> = tasks.iter().filter_map(|t| t["ContainerId"].as_str()).collect();
+
+    // meta.db knows every container, stopped ones included
+    let mut known = HashSet::new();
+    for (mut container, upper_dir) in read_containerd_metadata(&containerd_root) {
+        let id = container["ContainerId"].as_str().unwrap_or("").to_string();
+        container["Running"] = json!(running.contains(id.as_str()));
+        if let Some(upper_dir) = upper_dir {
+            changes.extend(upper_dir_changes(&upper_dir, &id));
+        }
+        inventory.push(container);
+        known.insert(id);
+    }
+    let unknown_tasks: Vec<Value> = tasks.iter()
+        .filter(|t| !known.contains(t["ContainerId"].as_str().unwrap_or("")))
+        .cloned()
+        .collect();
+    inventory.extend(unknown_tasks);
+    logs.extend(read_cri_logs(root, &root.join(CRI_LOGS)));
+
+    let datasets = [
+        ("Container_Inventory", inventory),
+        ("Container_FileChanges", changes),
+        ("Container_Logs", logs),
+    ];
+    for (name, records) in datasets {
+        let count = records.len();
+        let target = Path::new(out_dir).join(format!("{}.json", name));
+        let _ = fs::write(target, serde_json::to_string(&records).unwrap());
+        ui::success(&format!("{}: {} records", name, count));
+    }
+}
+
+fn read_json_file(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_docker_container(container_dir: &Path, id: &str) -> Option<Value> {
+    let config = read_json_file(&container_dir.join("config.v2.json"))?;
+    let host_config = read_json_file(&container_dir.join("hostconfig.json")).unwrap_or(Value::Null);
+
+    // Mounts is a map keyed by destination in config.v2.json
+    let mounts: Vec<Value> = config["MountPoints"]
+        .as_object()
+        .map(|m| m.values().map(|mp| json!({
+            "Source": mp["Source"],
+            "Destination": mp["Destination"],
+            "ReadWrite": mp["RW"],
+            "Type": mp["Type"],
+        })).collect())
+        .unwrap_or_default();
+
+    let command = [config["Path"].as_str().unwrap_or("")]
+        .into_iter()
+        .chain(config["Args"].as_array().into_iter().flatten().filter_map(|a| a.as_str()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(json!({
+        "Event": "ContainerCreated",
+        "Timestamp": config["Created"],
+        "ContainerId": id,
+        "Runtime": "docker",
+        "Name": config["Name"].as_str().unwrap_or("").trim_start_matches('/'),
+        "Image": config["Config"]["Image"],
+        "ImageId": config["Image"],
+        "Command": command,
+        "Mounts": mounts,
+        "RestartPolicy": host_config["RestartPolicy"]["Name"],
+        "Privileged": host_config["Privileged"],
+        "Running": config["State"]["Running"],
+        "StartedAt": config["State"]["StartedAt"],
+        "FinishedAt": config["State"]["FinishedAt"],
+    }))
+}
+
+// image/overlay2/layerdb/mounts/<id>/mount-id holds the overlay2 directory name
+fn docker_upper_dir(docker_root: &Path, id: &str) -> Option<std::path::PathBuf> {
+    let mount_id = fs::read_to_string(
+        docker_root.join("image/overlay2/layerdb/mounts").join(id).join("mount-id")
+    ).ok()?;
+    let upper = docker_root.join("overlay2").join(mount_id.trim()).join("diff");
+    upper.is_dir().then_some(upper)
+}
+
+/// Everything in the upper dir was written, changed or deleted inside the container.
+fn upper_dir_changes(upper_dir: &Path, id: &str) -> Vec<Value> {
+    let mut changes = Vec::new();
+
+    for entry in WalkDir::new(upper_dir).min_depth(1).into_iter().filter_map(|e| e.ok()) {
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        let relative = format!("/{}", entry.path().strip_prefix(upper_dir).unwrap_or(entry.path()).to_string_lossy());
+        let file_name = entry.file_name().to_string_lossy();
+
+        // overlayfs marks deletions with a 0/0 character device, layer tarballs with a ".wh." prefix
+        let (change, path) = if is_whiteout(&metadata) {
+            ("Deleted", relative)
+        } else if file_name == OPAQUE_WHITEOUT {
+            // The directory was replaced, nothing below it comes from the image anymore
+            let dir = relative.trim_end_matches(OPAQUE_WHITEOUT).trim_end_matches('/');
+            ("Opaque", if dir.is_empty() { "/".to_string() } else { dir.to_string() })
+        } else if let Some(original) = file_name.strip_prefix(".wh.") {
+            ("Deleted", relative.replace(&format!(".wh.{}", original), original))
+        } else if metadata.is_dir() {
+            continue;
+        } else {
+            ("Written", relative)
+        };
+
+        let sha256 = if change == "Written" && metadata.is_file() && metadata.len() <= MAX_HASH_SIZE {
+            hash_file(entry.path())
+        } else {
+            String::new()
+        };
+
+        changes.push(json!({
+            "Event": "ContainerFileChanged",
+            "Timestamp": metadata.modified().map(|t| DateTime::<chrono::Utc>::from(t).to_rfc3339()).unwrap_or_default(),
+            "ContainerId": id,
+            "Change": change,
+            "Path": path,
+            "Size": metadata.len(),
+            "Sha256": sha256,
+        }));
+    }
+    changes
+}
+
+#[cfg(unix)]
+fn is_whiteout(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    metadata.file_type().is_char_device() && metadata.rdev() == 0
+}
+
+#[cfg(not(unix))]
+fn is_whiteout(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+// Docker json-file driver: one {"log": ..., "stream": ..., "time": ...} object per line
+fn read_json_log(path: &Path, id: &str) -> Vec<Value> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+        .map(|entry| json!({
+            "Event": "ContainerLog",
+            "Timestamp": entry["time"],
+            "ContainerId": id,
+            "Stream": entry["stream"],
+            "Message": entry["log"].as_str().unwrap_or("").trim_end(),
+        }))
+        .collect()
+}
+
+fn read_containerd_task(task_dir: &Path) -> Option<Value> {
+    let spec = read_json_file(&task_dir.join("config.json"))?;
+    let id = task_dir.file_name()?.to_string_lossy().to_string();
+    let namespace = task_dir.parent()?.file_name()?.to_string_lossy().to_string();
+
+    let created = fs::metadata(task_dir.join("config.json"))
+        .and_then(|m| m.modified())
+        .map(|t| DateTime::<chrono::Utc>::from(t).to_rfc3339())
+        .unwrap_or_default();
+
+    let command = spec["process"]["args"]
+        .as_array()
+        .map(|args| args.iter().filter_map(|a| a.as_str()).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+
+    Some(json!({
+        "Event": "ContainerCreated",
+        "Timestamp": created,
+        "ContainerId": id,
+        "Runtime": "containerd",
+        "Namespace": namespace,
+        "Name": spec["annotations"]["io.kubernetes.cri.container-name"],
+        "Image": spec["annotations"]["io.kubernetes.cri.image-name"],
+        "Command": command,
+        "Mounts": spec["mounts"],
+        "RootPath": spec["root"]["path"],
+    }))
+}
+
+// Go's encoding/binary uvarint, returns the value and the bytes it took
+fn uvarint(raw: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &b) in raw.iter().enumerate().take(10) {
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+// Go's time.MarshalBinary: version, seconds since 0001-01-01, nanoseconds, zone offset
+fn go_time(raw: &[u8]) -> Option<String> {
+    if !matches!(raw.first()?, 1 | 2) {
+        return None;
+    }
+    let seconds = i64::from_be_bytes(raw.get(1..9)?.try_into().ok()?);
+    let nanos = u32::from_be_bytes(raw.get(9..13)?.try_into().ok()?);
+    DateTime::from_timestamp(seconds.checked_sub(62_135_596_800)?, nanos).map(|t| t.to_rfc3339())
+}
+
+// The spec is a protobuf Any: field 1 the type URL, field 2 the OCI spec as JSON
+fn any_value(raw: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+    while pos < raw.len() {
+        let (tag, n) = uvarint(&raw[pos..])?;
+        pos += n;
+        if tag & 7 != 2 {
+            return None;
+        }
+        let (len, n) = uvarint(&raw[pos..])?;
+        pos += n;
+        let end = pos.checked_add(usize::try_from(len).ok()?)?;
+        let field = raw.get(pos..end)?;
+        if tag >> 3 == 2 {
+            return Some(field);
+        }
+        pos = end;
+    }
+    None
+}
+
+fn text(value: Option<&[u8]>) -> String {
+    value.map(|v| String::from_utf8_lossy(v).to_string()).unwrap_or_default()
+}
+
+/// Every container in containerd's meta.db (v1/<namespace>/containers/<id>), with the upper dir
+/// of its overlayfs snapshot when that still exists.
+fn read_containerd_metadata(containerd_root: &Path) -> Vec<(Value, Option<PathBuf>)> {
+    let mut containers = Vec::new();
+    let meta_path = containerd_root.join(CONTAINERD_META);
+    if !meta_path.exists() {
+        return containers;
+    }
+    let meta = match bolt::open(&meta_path) {
+        Ok(meta) => meta,
+        Err(e) => {
+            ui::warn(&format!("{}: {}", meta_path.display(), e));
+            return containers;
+        }
+    };
+    let snapshots = bolt::open(&containerd_root.join(OVERLAYFS_SNAPSHOTTER).join("metadata.db")).ok();
+    let Some(v1) = meta.path(&["v1"]) else {
+        return containers;
+    };
+
+    for (namespace_name, item) in meta.items(v1) {
+        let Item::Bucket(namespace) = item else {
+            continue;
+        };
+        let Some(bucket) = meta.bucket(namespace, b"containers") else {
+            continue;
+        };
+        for (id, item) in meta.items(bucket) {
+            let Item::Bucket(container) = item else {
+                continue;
+            };
+            let labels: serde_json::Map<String, Value> = meta.bucket(container, b"labels")
+                .map(|labels| meta.items(labels).into_iter()
+                    .filter_map(|(key, item)| match item {
+                        Item::Value(value) => Some((text(Some(key)), json!(text(Some(value))))),
+                        Item::Bucket(_) => None,
+                    })
+                    .collect())
+                .unwrap_or_default();
+            let spec = meta.value(container, b"spec")
+                .and_then(any_value)
+                .and_then(|json| serde_json::from_slice::<Value>(json).ok())
+                .unwrap_or(Value::Null);
+            let command = spec["process"]["args"]
+                .as_array()
+                .map(|args| args.iter().filter_map(|a| a.as_str()).collect::<Vec<_>>().join(" "))
+                .unwrap_or_default();
+            let snapshotter = text(meta.value(container, b"snapshotter"));
+            let snapshot_key = text(meta.value(container, b"snapshotKey"));
+
+            let upper_dir = match &snapshots {
+                Some(snapshots) if snapshotter == "overlayfs" => overlayfs_upper_dir(&meta, namespace, &snapshot_key, snapshots, containerd_root),
+                _ => None,
+            };
+            let name = labels.get("io.kubernetes.container.name")
+                .or_else(|| labels.get("nerdctl/name"))
+                .cloned()
+                .unwrap_or(Value::Null);
+            containers.push((json!({
+                "Event": "ContainerCreated",
+                "Timestamp": meta.value(container, b"createdat").and_then(go_time).unwrap_or_default(),
+                "ContainerId": text(Some(id)),
+                "Runtime": "containerd",
+                "Namespace": text(Some(namespace_name)),
+                "Name": name,
+                "Image": text(meta.value(container, b"image")),
+                "Command": command,
+                "Mounts": spec["mounts"],
+                "Snapshotter": snapshotter,
+                "Labels": labels,
+            }), upper_dir));
+        }
+    }
+    containers
+}
+
+// meta.db: v1/<namespace>/snapshots/overlayfs/<key>/name is the snapshotter's own key,
+// the snapshotter's metadata.db: v1/snapshots/<name>/id numbers the snapshots/<id>/fs dir
+fn overlayfs_upper_dir(meta: &bolt::Db, namespace: bolt::Bucket, key: &str, snapshots: &bolt::Db, containerd_root: &Path) -> Option<PathBuf> {
+    let snapshot = meta.bucket(meta.bucket(meta.bucket(namespace, b"snapshots")?, b"overlayfs")?, key.as_bytes())?;
+    let name = meta.value(snapshot, b"name")?;
+    let entry = snapshots.bucket(snapshots.path(&["v1", "snapshots"])?, name)?;
+    let (id, _) = uvarint(snapshots.value(entry, b"id")?)?;
+    let upper = containerd_root.join(OVERLAYFS_SNAPSHOTTER).join("snapshots").join(id.to_string()).join("fs");
+    upper.is_dir().then_some(upper)
+}
+
+// CRI log format: "<RFC 3339 time> <stream> <P|F> <message>", file names end in -<container id>.log.
+// They are absolute symlinks into /var/log/pods, which have to be followed below the source root.
+fn read_cri_logs(root: &Path, log_dir: &Path) -> Vec<Value> {
+    let mut logs = Vec::new();
+    let Ok(entries) = fs::read_dir(log_dir) else {
+        return logs;
+    };
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let id = file_name.trim_end_matches(".log").rsplit('-').next().unwrap_or("").to_string();
+        let Ok(file) = fs::File::open(linux::resolve(root, &entry.path())) else {
+            continue;
+        };
+
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let mut parts = line.splitn(4, ' ');
+            let (Some(time), Some(stream), Some(_tag)) = (parts.next(), parts.next(), parts.next()) else {
+                continue;
+            };
+            logs.push(json!({
+                "Event": "ContainerLog",
+                "Timestamp": time,
+                "ContainerId": id,
+                "Stream": stream,
+                "Message": parts.next().unwrap_or(""),
+                "SourceFile": file_name,
+            }));
+        }
+    }
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("tracenexus-containers-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn whiteouts_in_upper_dir() {
+        let upper = temp_root("upper");
+        fs::create_dir_all(upper.join("etc/ssl")).unwrap();
+        fs::write(upper.join("etc/.wh.shadow"), "").unwrap();
+        fs::write(upper.join("etc/ssl/.wh..wh..opq"), "").unwrap();
+        fs::write(upper.join("etc/ssl/cert.pem"), "pem").unwrap();
+
+        let mut changes: Vec<(String, String)> = upper_dir_changes(&upper, "abc")
+            .iter()
+            .map(|c| (c["Change"].as_str().unwrap().to_string(), c["Path"].as_str().unwrap().to_string()))
+            .collect();
+        changes.sort();
+        assert_eq!(changes, vec![
+            ("Deleted".to_string(), "/etc/shadow".to_string()),
+            ("Opaque".to_string(), "/etc/ssl".to_string()),
+            ("Written".to_string(), "/etc/ssl/cert.pem".to_string()),
+        ]);
+        let _ = fs::remove_dir_all(upper);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cri_log_symlinks_below_root() {
+        let root = temp_root("cri");
+        let pod_dir = root.join("var/log/pods/default_web_1234/nginx");
+        fs::create_dir_all(&pod_dir).unwrap();
+        fs::create_dir_all(root.join(CRI_LOGS)).unwrap();
+        fs::write(pod_dir.join("0.log"), "2024-05-01T10:00:00.123456789Z stdout F GET / 200\n").unwrap();
+        std::os::unix::fs::symlink(
+            "/var/log/pods/default_web_1234/nginx/0.log",
+            root.join(CRI_LOGS).join("web_default_nginx-c0ffee.log"),
+        ).unwrap();
+
+        let logs = read_cri_logs(&root, &root.join(CRI_LOGS));
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["ContainerId"], "c0ffee");
+        assert_eq!(logs[0]["Stream"], "stdout");
+        assert_eq!(logs[0]["Message"], "GET / 200");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn containerd_metadata_and_snapshots() {
+        use crate::bolt::tests::{build, Node};
+        let value = |v: &[u8]| Node::Value(v.to_vec());
+
+        let root = temp_root("containerd");
+        let containerd_root = root.join(CONTAINERD_ROOT);
+        let upper = containerd_root.join(OVERLAYFS_SNAPSHOTTER).join("snapshots/7/fs");
+        fs::create_dir_all(upper.join("tmp")).unwrap();
+        fs::write(upper.join("tmp/payload.sh"), "curl | sh").unwrap();
+        fs::write(upper.join("tmp/.wh.old.log"), "").unwrap();
+
+        // 2024-05-01T10:00:00Z as Go's MarshalBinary, seconds counted from year 1
+        let mut created = vec![1];
+        created.extend_from_slice(&(1_714_557_600i64 + 62_135_596_800).to_be_bytes());
+        created.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff]);
+        let spec_json = br#"{"process":{"args":["nginx","-g","daemon off;"]},"mounts":[]}"#;
+        let type_url = b"types.containerd.io/opencontainers/runtime-spec/1/Spec";
+        let spec = [&[0x0a, type_url.len() as u8][..], type_url, &[0x12, spec_json.len() as u8], spec_json].concat();
+
+        fs::create_dir_all(containerd_root.join(CONTAINERD_META).parent().unwrap()).unwrap();
+        fs::write(containerd_root.join(CONTAINERD_META), build(&[
+            ("v1", Node::Bucket(vec![
+                ("k8s.io", Node::Bucket(vec![
+                    ("containers", Node::Bucket(vec![
+                        ("c0ffee", Node::Bucket(vec![
+                            ("createdat", Node::Value(created)),
+                            ("image", value(b"docker.io/library/nginx:latest")),
+                            ("labels", Node::Bucket(vec![("io.kubernetes.container.name", value(b"web"))])),
+                            ("snapshotKey", value(b"c0ffee")),
+                            ("snapshotter", value(b"overlayfs")),
+                            ("spec", Node::Value(spec)),
+                        ])),
+                    ])),
+                    ("snapshots", Node::Bucket(vec![
+                        ("overlayfs", Node::Bucket(vec![
+                            ("c0ffee", Node::Bucket(vec![("name", value(b"k8s.io/42/c0ffee"))])),
+                        ])),
+                    ])),
+                ])),
+            ])),
+        ])).unwrap();
+        fs::write(containerd_root.join(OVERLAYFS_SNAPSHOTTER).join("metadata.db"), build(&[
+            ("v1", Node::Bucket(vec![
+                ("snapshots", Node::Bucket(vec![
+                    ("k8s.io/42/c0ffee", Node::Bucket(vec![("id", value(&[7])), ("kind", value(&[2]))])),
+                ])),
+            ])),
+        ])).unwrap();
+
+        let out_dir = root.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        collect(&root, out_dir.to_str().unwrap());
+
+        let inventory: Vec<Value> = serde_json::from_str(&fs::read_to_string(out_dir.join("Container_Inventory.json")).unwrap()).unwrap();
+        assert_eq!(inventory.len(), 1);
+        assert_eq!(inventory[0]["ContainerId"], "c0ffee");
+        assert_eq!(inventory[0]["Namespace"], "k8s.io");
+        assert_eq!(inventory[0]["Name"], "web");
+        assert_eq!(inventory[0]["Timestamp"], "2024-05-01T10:00:00+00:00");
+        assert_eq!(inventory[0]["Command"], "nginx -g daemon off;");
+        assert_eq!(inventory[0]["Running"], false);
+
+        let changes: Vec<Value> = serde_json::from_str(&fs::read_to_string(out_dir.join("Container_FileChanges.json")).unwrap()).unwrap();
+        let mut changes: Vec<(&str, &str)> = changes.iter()
+            .map(|c| (c["Change"].as_str().unwrap(), c["Path"].as_str().unwrap()))
+            .collect();
+        changes.sort();
+        assert_eq!(changes, vec![("Deleted", "/tmp/old.log"), ("Written", "/tmp/payload.sh")]);
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,173 @@
+use chardetng::EncodingDetector;
+use csv::ReaderBuilder;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+// Checked in this order, a tie keeps the earlier one
+const DELIMITERS: &[u8] = b",;\t|";
+
+/// How a CSV file was actually written.
+pub struct Dialect {
+    pub encoding: &'static str,
+    pub bom: bool,
+    // No BOM and not valid UTF-8, the encoding is a best guess
+    pub encoding_guessed: bool,
+    pub delimiter: u8,
+}
+
+impl Dialect {
+    /// Plain UTF-8 with commas, nothing worth reporting
+    pub fn is_default(&self) -> bool {
+        self.encoding == UTF_8.name() && self.delimiter == b','
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "encoding": self.encoding,
+            "bom": self.bom,
+            "encoding_guessed": self.encoding_guessed,
+            "transcoded": self.encoding != UTF_8.name(),
+            "delimiter": (self.delimiter as char).to_string(),
+        })
+    }
+}
+
+/// Opens a CSV whatever its encoding and delimiter. The content is transcoded to UTF-8 in memory,
+/// quoted fields spanning several lines are left to the csv reader.
+pub fn open(path: &Path) -> io::Result<(csv::Reader<Cursor<String>>, Dialect)> {
+    let bytes = fs::read(path)?;
+    let (text, encoding, bom, encoding_guessed) = decode(&bytes);
+    let delimiter = sniff_delimiter(&text);
+
+    let reader = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(Cursor::new(text));
+    Ok((reader, Dialect { encoding: encoding.name(), bom, encoding_guessed, delimiter }))
+}
+
+fn decode(bytes: &[u8]) -> (String, &'static Encoding, bool, bool) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return (text.into_owned(), encoding, true, false);
+    }
+    // UTF-16 without BOM: ASCII text has a zero in every other byte (and would pass as UTF-8)
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    let zeros_at = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|b| **b == 0).count();
+    let utf16 = if zeros_at(1) > sample.len() / 4 {
+        Some(UTF_16LE)
+    } else if zeros_at(0) > sample.len() / 4 {
+        Some(UTF_16BE)
+    } else {
+        None
+    };
+
+    if utf16.is_none()
+        && let Ok(text) = std::str::from_utf8(bytes)
+    {
+        return (text.to_string(), UTF_8, false, false);
+    }
+
+    let encoding = utf16.unwrap_or_else(|| {
+        // Mostly Windows-1252 from localized systems
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        detector.guess(None, true)
+    });
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    (text.into_owned(), encoding, false, true)
+}
+
+// Counts the candidates in the header record, ignoring anything inside quotes
+fn sniff_delimiter(text: &str) -> u8 {
+    let mut counts = [0usize; DELIMITERS.len()];
+    let mut quoted = false;
+
+    for byte in text.bytes() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'\n' if !quoted => break,
+            _ if !quoted => {
+                if let Some(i) = DELIMITERS.iter().position(|d| *d == byte) {
+                    counts[i] += 1;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // max_by_key returns the last maximum, the reversed order makes ties go to the earlier candidate
+    DELIMITERS.iter().zip(counts).rev()
+        .max_by_key(|(_, count)| *count)
+        .filter(|(_, count)| *count > 0)
+        .map(|(delimiter, _)| *delimiter)
+        .unwrap_or(b',')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Opens `bytes` as a CSV and returns the dialect plus all records, header first
+    fn read(name: &str, bytes: &[u8]) -> (Dialect, Vec<Vec<String>>) {
+        let dir = std::env::temp_dir().join(format!("tracenexus-dialect-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        let (mut rdr, dialect) = open(&path).unwrap();
+        let mut rows = vec![rdr.headers().unwrap().iter().map(String::from).collect()];
+        rows.extend(rdr.records().map(|r| r.unwrap().iter().map(String::from).collect()));
+        fs::remove_file(&path).unwrap();
+        (dialect, rows)
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn utf16_with_bom() {
+        let bytes = [&[0xff, 0xfe][..], &utf16le("Name,Size\nntuser.dat,262144\n")].concat();
+        let (dialect, rows) = read("bom.csv", &bytes);
+        assert_eq!((dialect.encoding, dialect.bom, dialect.encoding_guessed), ("UTF-16LE", true, false));
+        assert_eq!(rows, vec![vec!["Name", "Size"], vec!["ntuser.dat", "262144"]]);
+    }
+
+    #[test]
+    fn utf16_without_bom() {
+        let (dialect, rows) = read("no-bom.csv", &utf16le("Name,Size\nntuser.dat,262144\n"));
+        assert_eq!((dialect.encoding, dialect.bom, dialect.encoding_guessed), ("UTF-16LE", false, true));
+        assert_eq!(rows[1], vec!["ntuser.dat", "262144"]);
+    }
+
+    #[test]
+    fn windows_1252() {
+        let (text, _, _) = encoding_rs::WINDOWS_1252.encode("Benutzer;Ort;Notiz\nJürgen Müller;Köln;Größe geändert, Straße\n");
+        let (dialect, rows) = read("1252.csv", &text);
+        assert_eq!(dialect.encoding, "windows-1252");
+        assert!(dialect.encoding_guessed);
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(rows[1], vec!["Jürgen Müller", "Köln", "Größe geändert, Straße"]);
+    }
+
+    #[test]
+    fn delimiters_inside_quotes_are_not_counted() {
+        let (dialect, rows) = read("quoted.csv", b"\"Path, full\";\"Size, bytes\";Owner\nC:\\a.txt;1;bob\n");
+        assert_eq!(dialect.delimiter, b';');
+        assert!(!dialect.is_default());
+        assert_eq!(rows[0], vec!["Path, full", "Size, bytes", "Owner"]);
+    }
+
+    #[test]
+    fn quoted_multiline_field() {
+        let (dialect, rows) = read("multiline.csv", b"Id,Message,Level\n1,\"first line\nsecond, line\",Info\n2,short,Warn\n");
+        assert!(dialect.is_default());
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], vec!["1", "first line\nsecond, line", "Info"]);
+        assert_eq!(rows[2], vec!["2", "short", "Warn"]);
+    }
+}
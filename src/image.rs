@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use crate::ui;
+
+mod ewf;
+mod ntfs;
+
+use ewf::EwfImage;
+use ntfs::NtfsVolume;
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+const SECTOR_SIZE: u64 = 512;
+
+const MBR_EXTENDED_TYPES: &[u8] = &[0x05, 0x0F, 0x85];
+const MBR_GPT_PROTECTIVE: u8 = 0xEE;
+
+// Paths below the volume root, '*' matches within one path segment, '**' any number of segments.
+// The same pipeline as on a live host runs on these, so everything run_full reads is listed.
+const ARTIFACT_PATTERNS: &[&str] = &[
+    "$MFT",
+    "$Extend/$UsnJrnl",
+    "Windows/System32/config/SYSTEM*",
+    "Windows/System32/config/SOFTWARE*",
+    "Windows/System32/config/SAM*",
+    "Windows/System32/config/SECURITY*",
+    "Windows/System32/config/DEFAULT*",
+    "Windows/System32/winevt/Logs/*.evtx",
+    "Windows/AppCompat/Programs/Amcache.hve*",
+    "Windows/Prefetch/*.pf",
+    "Windows/System32/sru/SRUDB.dat",
+    "Users/*/NTUSER.DAT*",
+    "Users/*/AppData/Local/Microsoft/Windows/UsrClass.dat*",
+    "Users/*/AppData/Roaming/Microsoft/Windows/Recent/**",
+    "Users/*/AppData/Local/Google/Chrome/User Data/*/History*",
+    "Users/*/AppData/Local/Microsoft/Edge/User Data/*/History*",
+    "Users/*/AppData/Roaming/Mozilla/Firefox/Profiles/*/places.sqlite*",
+    "$Recycle.Bin/*/$I*",
+];
+
+/// Opens an E01 (all segments) or a raw dd image.
+fn open_image(path: &Path) -> Result<Box<dyn ReadSeek>, Box<dyn std::error::Error>> {
+    if ewf::is_ewf(path) {
+        ui::info("Detected Expert Witness (E01) image");
+        Ok(Box::new(EwfImage::open(path)?))
+    } else {
+        ui::info("Treating input as raw (dd) image");
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+fn read_sector(reader: &mut dyn ReadSeek, lba: u64) -> Option<[u8; 512]> {
+    let mut sector = [0u8; 512];
+    reader.seek(SeekFrom::Start(lba * SECTOR_SIZE)).ok()?;
+    reader.read_exact(&mut sector).ok()?;
+    Some(sector)
+}
+
+/// Returns the byte offsets of all partitions from the MBR (incl. extended partitions) or GPT.
+fn partition_offsets(reader: &mut dyn ReadSeek) -> Vec<u64> {
+    let Some(mbr) = read_sector(reader, 0) else {
+        return Vec::new();
+    };
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Vec::new();
+    }
+
+    let mut offsets = Vec::new();
+    for i in 0..4 {
+        let entry = &mbr[446 + i * 16..446 + (i + 1) * 16];
+        let partition_type = entry[4];
+        let start = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64;
+
+        match partition_type {
+            0 => {},
+            MBR_GPT_PROTECTIVE => return gpt_offsets(reader),
+            t if MBR_EXTENDED_TYPES.contains(&t) => offsets.extend(extended_offsets(reader, start)),
+            _ => offsets.push(start * SECTOR_SIZE),
+        }
+    }
+    offsets
+}
+
+// Logical partitions are chained through EBRs, each relative to the extended partition start
+fn extended_offsets(reader: &mut dyn ReadSeek, extended_start: u64) -> Vec<u64> {
+    let mut offsets = Vec::new();
+    let mut ebr_lba = extended_start;
+
+    for _ in 0..128 {
+        let Some(ebr) = read_sector(reader, ebr_lba) else {
+            break;
+        };
+        if ebr[510..512] != [0x55, 0xAA] {
+            break;
+        }
+        let first = &ebr[446..462];
+        let second = &ebr[462..478];
+        if first[4] != 0 {
+            offsets.push((ebr_lba + u32::from_le_bytes(first[8..12].try_into().unwrap()) as u64) * SECTOR_SIZE);
+        }
+        let next = u32::from_le_bytes(second[8..12].try_into().unwrap()) as u64;
+        if second[4] == 0 || next == 0 {
+            break;
+        }
+        ebr_lba = extended_start + next;
+    }
+    offsets
+}
+
+fn gpt_offsets(reader: &mut dyn ReadSeek) -> Vec<u64> {
+    let Some(header) = read_sector(reader, 1) else {
+        return Vec::new();
+    };
+    if &header[0..8] != b"EFI PART" {
+        return Vec::new();
+    }
+    let entries_lba = u64::from_le_bytes(header[72..80].try_into().unwrap());
+    let entry_count = u32::from_le_bytes(header[80..84].try_into().unwrap()) as u64;
+    let entry_size = u32::from_le_bytes(header[84..88].try_into().unwrap()) as u64;
+    // 128 bytes per entry and 128 entries is what everyone writes, the spec allows a bit more
+    if !(128..=4096).contains(&entry_size) || entry_count > 4096 {
+        return Vec::new();
+    }
+
+    let mut table = vec![0u8; (entry_count * entry_size) as usize];
+    if reader.seek(SeekFrom::Start(entries_lba * SECTOR_SIZE)).is_err() || reader.read_exact(&mut table).is_err() {
+        return Vec::new();
+    }
+
+    table.chunks_exact(entry_size as usize)
+        // An all-zero type GUID marks an unused entry
+        .filter(|entry| entry[0..16].iter().any(|&b| b != 0))
+        .map(|entry| u64::from_le_bytes(entry[32..40].try_into().unwrap()) * SECTOR_SIZE)
+        .collect()
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let segment = segment.to_lowercase();
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => segment.len() >= prefix.len() + suffix.len() && segment.starts_with(prefix) && segment.ends_with(suffix),
+        None => pattern == segment,
+    }
+}
+
+fn path_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => path_matches(&pattern[1..], path) || (!path.is_empty() && path_matches(pattern, &path[1..])),
+        (Some(p), Some(s)) => segment_matches(p, s) && path_matches(&pattern[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+fn is_artifact(path: &str) -> bool {
+    let segments: Vec<&str> = path.split('/').collect();
+    ARTIFACT_PATTERNS.iter().any(|pattern| {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        path_matches(&pattern, &segments)
+    })
+}
+
+/// Reads the image, finds the Windows NTFS volume and extracts the artifact files into `target_root`.
+/// Nothing is mounted, everything happens in-process so this works on Linux as well.
+pub fn extract_artifacts(image_path: &Path, target_root: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut reader = open_image(image_path)?;
+
+    // A bare volume image has the NTFS boot sector at offset 0
+    let mut candidates = partition_offsets(reader.as_mut());
+    candidates.insert(0, 0);
+
+    let ntfs_offsets: Vec<u64> = candidates.into_iter()
+        .filter(|&offset| ntfs::is_ntfs(reader.as_mut(), offset))
+        .collect();
+    if ntfs_offsets.is_empty() {
+        return Err("no NTFS volume found in image".into());
+    }
+
+    for (i, offset) in ntfs_offsets.iter().enumerate() {
+        ui::info(&format!("Scanning NTFS volume at offset {} ({}/{})", offset, i + 1, ntfs_offsets.len()));
+        let volume_root = target_root.join(format!("vol{}", i));
+
+        let mut volume = NtfsVolume::open(reader.as_mut(), *offset)?;
+        let (extracted, failed) = volume.extract(&volume_root, is_artifact)?;
+        for file in &failed {
+            ui::warn(&format!("Could not extract {}: {}", file.path, file.error));
+        }
+        let total: u64 = extracted.iter().map(|f| f.size).sum();
+        ui::success(&format!("Extracted {} files ({} bytes) from volume {}", extracted.len(), total, i));
+
+        // The system volume is the one with registry hives, recovery/EFI partitions are skipped
+        if extracted.iter().any(|f| f.path.eq_ignore_ascii_case("Windows/System32/config/SYSTEM")) {
+            return Ok(volume_root);
+        }
+    }
+    Err("no Windows system volume found in image".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mbr_entry(sector: &mut [u8], slot: usize, partition_type: u8, start: u32) {
+        let entry = &mut sector[446 + slot * 16..446 + (slot + 1) * 16];
+        entry[4] = partition_type;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+    }
+
+    fn disk(sectors: usize) -> Vec<u8> {
+        let mut disk = vec![0u8; sectors * 512];
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        disk
+    }
+
+    #[test]
+    fn mbr_with_extended_partition() {
+        let mut disk = disk(16);
+        mbr_entry(&mut disk[..512], 0, 0x07, 2);
+        mbr_entry(&mut disk[..512], 1, 0x0F, 8);
+        // EBR at LBA 8: logical partition 1 sector behind it, next EBR at extended start + 4
+        let ebr = &mut disk[8 * 512..9 * 512];
+        ebr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        mbr_entry(ebr, 0, 0x07, 1);
+        mbr_entry(ebr, 1, 0x05, 4);
+        let ebr = &mut disk[12 * 512..13 * 512];
+        ebr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        mbr_entry(ebr, 0, 0x07, 1);
+
+        let offsets = partition_offsets(&mut Cursor::new(disk));
+        assert_eq!(offsets, vec![2 * 512, 9 * 512, 13 * 512]);
+    }
+
+    fn gpt_disk(entry_size: u32) -> Vec<u8> {
+        let mut disk = disk(8);
+        mbr_entry(&mut disk[..512], 0, MBR_GPT_PROTECTIVE, 1);
+        let header = &mut disk[512..1024];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&entry_size.to_le_bytes());
+        // Entries 0 and 2 are used, 1 and 3 are empty
+        for (index, first_lba) in [(0usize, 34u64), (2, 2048)] {
+            let entry = &mut disk[1024 + index * 128..1024 + (index + 1) * 128];
+            entry[0] = 0xA2;
+            entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+        }
+        disk
+    }
+
+    #[test]
+    fn gpt_partitions() {
+        assert_eq!(partition_offsets(&mut Cursor::new(gpt_disk(128))), vec![34 * 512, 2048 * 512]);
+    }
+
+    #[test]
+    fn gpt_with_corrupt_entry_size() {
+        assert!(partition_offsets(&mut Cursor::new(gpt_disk(0))).is_empty());
+        assert!(partition_offsets(&mut Cursor::new(gpt_disk(16))).is_empty());
+    }
+
+    #[test]
+    fn artifact_patterns() {
+        assert!(is_artifact("Windows/System32/config/SYSTEM"));
+        assert!(is_artifact("Users/bob/AppData/Roaming/Microsoft/Windows/Recent/a/b.lnk"));
+        assert!(!is_artifact("Users/bob/Documents/report.docx"));
+    }
+}
@@ -0,0 +1,347 @@
+use flate2::read::ZlibDecoder;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// "EVF\x09\x0d\x0a\xff\x00" followed by fields_start, segment number and fields_end
+const EVF_SIGNATURE: &[u8; 8] = b"EVF\x09\x0d\x0a\xff\x00";
+const FILE_HEADER_SIZE: u64 = 13;
+const SECTION_DESCRIPTOR_SIZE: u64 = 76;
+
+const COMPRESSED_FLAG: u32 = 0x8000_0000;
+
+// EnCase writes 32 KiB chunks, anything past this is a corrupt volume section
+const MAX_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+struct Chunk {
+    segment: usize,
+    offset: u64,
+    size: u64,
+    compressed: bool,
+}
+
+/// Expert Witness (E01) image reader. Chunks are located through the table sections
+/// of all segment files and inflated on demand, the last chunk is cached.
+pub struct EwfImage {
+    segments: Vec<File>,
+    chunks: Vec<Chunk>,
+    chunk_size: u64,
+    media_size: u64,
+    position: u64,
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+pub fn is_ewf(path: &Path) -> bool {
+    let mut signature = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut signature))
+        .is_ok_and(|_| &signature == EVF_SIGNATURE)
+}
+
+// image.E01 -> image.E02 ... image.E99 -> image.EAA ... image.EZZ
+fn segment_paths(first: &Path) -> Vec<PathBuf> {
+    let extension = first.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let prefix = extension.chars().next().unwrap_or('E');
+
+    let mut paths = vec![first.to_path_buf()];
+    let mut suffixes: Vec<String> = (2..=99).map(|n| format!("{:02}", n)).collect();
+    for a in 'A'..='Z' {
+        for b in 'A'..='Z' {
+            suffixes.push(format!("{}{}", a, b));
+        }
+    }
+
+    for suffix in suffixes {
+        let next = first.with_extension(format!("{}{}", prefix, suffix));
+        if !next.exists() {
+            break;
+        }
+        paths.push(next);
+    }
+    paths
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+impl EwfImage {
+    pub fn open(first_segment: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut image = EwfImage {
+            segments: Vec::new(),
+            chunks: Vec::new(),
+            chunk_size: 0,
+            media_size: 0,
+            position: 0,
+            cached: None,
+        };
+
+        for path in segment_paths(first_segment) {
+            let mut file = File::open(&path)?;
+            let segment = image.segments.len();
+            image.read_sections(&mut file, segment)?;
+            image.segments.push(file);
+        }
+
+        if image.chunk_size == 0 || image.chunks.is_empty() {
+            return Err("E01 image has no volume or table section".into());
+        }
+        Ok(image)
+    }
+
+    /// Walks the section chain of one segment file and collects volume info and chunk tables.
+    fn read_sections(&mut self, file: &mut File, segment: usize) -> Result<(), Box<dyn std::error::Error>> {
+        let file_size = file.metadata()?.len();
+        let mut offset = FILE_HEADER_SIZE;
+        let mut sectors_end: Option<u64> = None;
+
+        while offset.checked_add(SECTION_DESCRIPTOR_SIZE).is_some_and(|end| end <= file_size) {
+            let mut descriptor = [0u8; SECTION_DESCRIPTOR_SIZE as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut descriptor)?;
+
+            let section_type = String::from_utf8_lossy(&descriptor[..16]).trim_end_matches('\0').to_string();
+            let next = read_u64(&descriptor, 16);
+            let size = read_u64(&descriptor, 24);
+
+            match section_type.as_str() {
+                "volume" | "disk" => {
+                    let mut volume = [0u8; 24];
+                    file.read_exact(&mut volume)?;
+                    let sectors_per_chunk = read_u32(&volume, 8) as u64;
+                    let bytes_per_sector = read_u32(&volume, 12) as u64;
+                    self.chunk_size = sectors_per_chunk.checked_mul(bytes_per_sector)
+                        .filter(|size| (1..=MAX_CHUNK_SIZE).contains(size))
+                        .ok_or("E01 volume section has an invalid chunk size")?;
+                    self.media_size = read_u64(&volume, 16).checked_mul(bytes_per_sector)
+                        .ok_or("E01 volume section has an invalid media size")?;
+                },
+                "sectors" => sectors_end = Some(offset.checked_add(size).ok_or("E01 sectors section overflows")?),
+                // table2 is a mirror of table
+                "table" => {
+                    let mut header = [0u8; 24];
+                    file.read_exact(&mut header)?;
+                    let entry_count = read_u32(&header, 0) as u64;
+                    let base_offset = read_u64(&header, 8);
+                    // The entries have to fit into the rest of the segment file
+                    let entries_start = offset + SECTION_DESCRIPTOR_SIZE + 24;
+                    if entries_start.checked_add(entry_count * 4).is_none_or(|end| end > file_size) {
+                        return Err("E01 table section is larger than its segment file".into());
+                    }
+
+                    let mut entries = vec![0u8; (entry_count * 4) as usize];
+                    file.read_exact(&mut entries)?;
+                    let entries: Vec<u32> = entries.chunks_exact(4).map(|e| read_u32(e, 0)).collect();
+
+                    // The last chunk runs until the end of its sectors section
+                    let data_end = sectors_end.or(offset.checked_add(size)).unwrap_or(file_size).min(file_size);
+                    let chunk_offset = |entry: u32| base_offset.checked_add((entry & !COMPRESSED_FLAG) as u64);
+                    for (i, entry) in entries.iter().enumerate() {
+                        let start = chunk_offset(*entry).ok_or("E01 chunk offset overflows")?;
+                        let end = match entries.get(i + 1) {
+                            Some(next) => chunk_offset(*next).ok_or("E01 chunk offset overflows")?,
+                            None => data_end,
+                        };
+                        if start > file_size || end > file_size {
+                            return Err("E01 chunk table points past the segment end".into());
+                        }
+                        self.chunks.push(Chunk {
+                            segment,
+                            offset: start,
+                            size: end.saturating_sub(start),
+                            compressed: entry & COMPRESSED_FLAG != 0,
+                        });
+                    }
+                },
+                "next" | "done" => break,
+                _ => {},
+            }
+
+            if next <= offset {
+                break;
+            }
+            offset = next;
+        }
+        Ok(())
+    }
+
+    fn load_chunk(&mut self, index: usize) -> io::Result<&[u8]> {
+        if self.cached.as_ref().is_none_or(|(cached, _)| *cached != index) {
+            let chunk = self.chunks.get(index).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "chunk out of range"))?;
+            let file = &mut self.segments[chunk.segment];
+            let mut raw = vec![0u8; chunk.size as usize];
+            file.seek(SeekFrom::Start(chunk.offset))?;
+            file.read_exact(&mut raw)?;
+
+            let data = if chunk.compressed {
+                let mut inflated = Vec::with_capacity(self.chunk_size as usize);
+                ZlibDecoder::new(&raw[..]).take(self.chunk_size).read_to_end(&mut inflated)?;
+                inflated
+            } else {
+                // Uncompressed chunks carry a trailing Adler-32 checksum
+                raw.truncate(self.chunk_size as usize);
+                raw
+            };
+            self.cached = Some((index, data));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+impl Read for EwfImage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.media_size {
+            return Ok(0);
+        }
+        let index = (self.position / self.chunk_size) as usize;
+        let within = (self.position % self.chunk_size) as usize;
+        let remaining = (self.media_size - self.position) as usize;
+
+        let chunk = self.load_chunk(index)?;
+        let available = chunk.len().saturating_sub(within).min(remaining);
+        let count = available.min(buf.len());
+        buf[..count].copy_from_slice(&chunk[within..within + count]);
+
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for EwfImage {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.media_size as i64 + p,
+            SeekFrom::Current(p) => self.position as i64 + p,
+        };
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of image"));
+        }
+        self.position = target as u64;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const CHUNK_SIZE: usize = 512;
+
+    fn descriptor(section_type: &str, offset: u64, size: u64) -> Vec<u8> {
+        let mut descriptor = vec![0u8; SECTION_DESCRIPTOR_SIZE as usize];
+        descriptor[..section_type.len()].copy_from_slice(section_type.as_bytes());
+        descriptor[16..24].copy_from_slice(&(offset + size).to_le_bytes());
+        descriptor[24..32].copy_from_slice(&size.to_le_bytes());
+        descriptor
+    }
+
+    // One segment with a zlib compressed and a raw chunk (plus its Adler-32), laid out the way read_sections walks it
+    fn segment(first: &[u8], second: &[u8]) -> Vec<u8> {
+        let mut file = EVF_SIGNATURE.to_vec();
+        file.extend_from_slice(&[1, 1, 0, 0, 0]);
+
+        let mut volume = vec![0u8; 24];
+        volume[8..12].copy_from_slice(&1u32.to_le_bytes());
+        volume[12..16].copy_from_slice(&(CHUNK_SIZE as u32).to_le_bytes());
+        volume[16..24].copy_from_slice(&2u64.to_le_bytes());
+        file.extend(descriptor("volume", file.len() as u64, SECTION_DESCRIPTOR_SIZE + 24));
+        file.extend(volume);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(first).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let sectors_start = file.len() as u64;
+        let data_start = sectors_start + SECTION_DESCRIPTOR_SIZE;
+        let second_start = data_start + compressed.len() as u64;
+        let sectors_size = SECTION_DESCRIPTOR_SIZE + compressed.len() as u64 + second.len() as u64 + 4;
+        file.extend(descriptor("sectors", sectors_start, sectors_size));
+        file.extend(&compressed);
+        file.extend(second);
+        file.extend([0u8; 4]);
+
+        let mut table = vec![0u8; 24];
+        table[0..4].copy_from_slice(&2u32.to_le_bytes());
+        table.extend(&(data_start as u32 | COMPRESSED_FLAG).to_le_bytes());
+        table.extend(&(second_start as u32).to_le_bytes());
+        file.extend(descriptor("table", file.len() as u64, SECTION_DESCRIPTOR_SIZE + table.len() as u64));
+        file.extend(table);
+        file.extend(descriptor("done", file.len() as u64, 0));
+        file
+    }
+
+    fn open_bytes(name: &str, data: &[u8]) -> Result<EwfImage, Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("tracenexus-ewf-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.E01");
+        std::fs::write(&path, data).unwrap();
+        let image = EwfImage::open(&path);
+        let _ = std::fs::remove_dir_all(dir);
+        image
+    }
+
+    #[test]
+    fn corrupt_segments_are_errors() {
+        let first = vec![1u8; CHUNK_SIZE];
+        let second = vec![2u8; CHUNK_SIZE];
+        let valid = segment(&first, &second);
+        let volume_data = FILE_HEADER_SIZE as usize + SECTION_DESCRIPTOR_SIZE as usize;
+        let sectors = volume_data + 24;
+        let table = valid.len() - 2 * SECTION_DESCRIPTOR_SIZE as usize - 24 - 8;
+
+        // sectors_per_chunk * bytes_per_sector and media size overflowing
+        let mut corrupt = valid.clone();
+        corrupt[volume_data + 8..volume_data + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[volume_data + 12..volume_data + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(open_bytes("chunk-size", &corrupt).is_err());
+        let mut corrupt = valid.clone();
+        corrupt[volume_data + 16..volume_data + 24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(open_bytes("media-size", &corrupt).is_err());
+
+        // Sectors section size overflowing its offset
+        let mut corrupt = valid.clone();
+        corrupt[sectors + 24..sectors + 32].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(open_bytes("sectors", &corrupt).is_err());
+
+        // 4 billion table entries and a base offset overflowing with the entry
+        let table_data = table + SECTION_DESCRIPTOR_SIZE as usize;
+        let mut corrupt = valid.clone();
+        corrupt[table_data..table_data + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(open_bytes("entries", &corrupt).is_err());
+        let mut corrupt = valid.clone();
+        corrupt[table_data + 8..table_data + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(open_bytes("base-offset", &corrupt).is_err());
+
+        assert!(open_bytes("valid", &valid).is_ok());
+    }
+
+    #[test]
+    fn reads_compressed_and_raw_chunks() {
+        let first: Vec<u8> = (0..CHUNK_SIZE).map(|i| (i % 7) as u8).collect();
+        let second = vec![0xAB; CHUNK_SIZE];
+        let dir = std::env::temp_dir().join(format!("tracenexus-ewf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.E01");
+        std::fs::write(&path, segment(&first, &second)).unwrap();
+
+        assert!(is_ewf(&path));
+        let mut image = EwfImage::open(&path).unwrap();
+        let mut content = Vec::new();
+        image.read_to_end(&mut content).unwrap();
+        assert_eq!(content, [first.clone(), second].concat());
+
+        // Reads across the chunk border
+        let mut buffer = [0u8; 4];
+        image.seek(SeekFrom::Start(CHUNK_SIZE as u64 - 2)).unwrap();
+        image.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [first[CHUNK_SIZE - 2], first[CHUNK_SIZE - 1], 0xAB, 0xAB]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
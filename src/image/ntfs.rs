@@ -0,0 +1,691 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use super::ReadSeek;
+
+const ROOT_RECORD: u64 = 5;
+
+const ATTR_FILE_NAME: u32 = 0x30;
+const ATTR_DATA: u32 = 0x80;
+const ATTR_END: u32 = 0xFFFF_FFFF;
+
+// Attribute headers up to the value offset / the initialized size, anything shorter is corrupt
+const RESIDENT_HEADER_SIZE: usize = 0x18;
+const NON_RESIDENT_HEADER_SIZE: usize = 0x40;
+
+const RECORD_IN_USE: u16 = 0x0001;
+const RECORD_IS_DIRECTORY: u16 = 0x0002;
+
+const ATTR_FLAG_COMPRESSED: u16 = 0x0001;
+const ATTR_FLAG_ENCRYPTED: u16 = 0x4000;
+
+// Win32 and Win32+DOS names are preferred over the 8.3 DOS name
+const NAMESPACE_DOS: u8 = 2;
+
+// NTFS allows up to 2 MiB clusters, anything bigger is a corrupt boot sector
+const MAX_CLUSTER_SIZE: u64 = 2 * 1024 * 1024;
+const COPY_BUFFER_SIZE: u64 = 1024 * 1024;
+
+/// One piece of a (possibly fragmented) $DATA attribute. Big files spread their
+/// runs over extension records, ordered by the starting VCN.
+struct DataPiece {
+    start_vcn: u64,
+    runs: Vec<(Option<u64>, u64)>,
+    real_size: Option<u64>,
+    resident: Option<Vec<u8>>,
+    flags: u16,
+}
+
+struct FileName {
+    parent: u64,
+    name: String,
+    namespace: u8,
+}
+
+pub struct ExtractedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// A wanted file that could not be written, the others are still extracted.
+pub struct FailedFile {
+    pub path: String,
+    pub error: String,
+}
+
+/// Minimal read-only NTFS reader: resolves paths by scanning the whole MFT
+/// (no index B-trees) and reads $DATA streams through their data runs.
+pub struct NtfsVolume<'a> {
+    reader: &'a mut dyn ReadSeek,
+    offset: u64,
+    cluster_size: u64,
+    record_size: u64,
+    mft_runs: Vec<(Option<u64>, u64)>,
+    mft_size: u64,
+}
+
+pub fn is_ntfs(reader: &mut dyn ReadSeek, offset: u64) -> bool {
+    let mut boot = [0u8; 512];
+    reader.seek(SeekFrom::Start(offset)).is_ok()
+        && reader.read_exact(&mut boot).is_ok()
+        && &boot[3..11] == b"NTFS    "
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Decodes a mapping pairs array into (LCN, cluster count). A missing LCN is a sparse run.
+fn decode_runs(data: &[u8]) -> Vec<(Option<u64>, u64)> {
+    let mut runs = Vec::new();
+    let mut pos = 0;
+    let mut lcn: i64 = 0;
+
+    while pos < data.len() && data[pos] != 0 {
+        let header = data[pos];
+        let length_size = (header & 0x0F) as usize;
+        let offset_size = (header >> 4) as usize;
+        pos += 1;
+        // Fields wider than 8 bytes don't exist, it's garbage after the end of the list
+        if length_size > 8 || offset_size > 8 || pos + length_size + offset_size > data.len() {
+            break;
+        }
+
+        let mut length: u64 = 0;
+        for i in 0..length_size {
+            length |= (data[pos + i] as u64) << (8 * i);
+        }
+        pos += length_size;
+
+        if offset_size == 0 {
+            runs.push((None, length));
+            continue;
+        }
+
+        // Signed, relative to the previous run
+        let mut delta: i64 = 0;
+        for i in 0..offset_size {
+            delta |= (data[pos + i] as i64) << (8 * i);
+        }
+        let shift = 64 - 8 * offset_size as u32;
+        delta = (delta << shift) >> shift;
+        pos += offset_size;
+
+        let Some(next) = lcn.checked_add(delta).filter(|l| *l >= 0) else {
+            break;
+        };
+        lcn = next;
+        runs.push((Some(lcn as u64), length));
+    }
+    runs
+}
+
+/// Applies the update sequence array. Every 512 byte stride ends with the USN that has to be replaced.
+fn apply_fixups(record: &mut [u8]) -> bool {
+    if &record[0..4] != b"FILE" {
+        return false;
+    }
+    let usa_offset = u16_at(record, 4) as usize;
+    let usa_count = u16_at(record, 6) as usize;
+
+    for i in 1..usa_count {
+        let sector_end = i * 512 - 2;
+        let usa_entry = usa_offset + i * 2;
+        if sector_end + 2 > record.len() || usa_entry + 2 > record.len() {
+            return false;
+        }
+        record[sector_end] = record[usa_entry];
+        record[sector_end + 1] = record[usa_entry + 1];
+    }
+    true
+}
+
+/// Iterates over the attributes of a fixed-up MFT record: (type, attribute bytes).
+fn attributes(record: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut attrs = Vec::new();
+    let mut pos = u16_at(record, 0x14) as usize;
+
+    while pos + 8 <= record.len() {
+        let attr_type = u32_at(record, pos);
+        let length = u32_at(record, pos + 4) as usize;
+        if attr_type == ATTR_END || length == 0 || pos + length > record.len() {
+            break;
+        }
+        // Callers read the header fields without checking, corrupt records just lose the attribute
+        let minimum = if length > 8 && record[pos + 8] != 0 { NON_RESIDENT_HEADER_SIZE } else { RESIDENT_HEADER_SIZE };
+        if length >= minimum {
+            attrs.push((attr_type, &record[pos..pos + length]));
+        }
+        pos += length;
+    }
+    attrs
+}
+
+fn attribute_name(attr: &[u8]) -> String {
+    let name_length = attr[9] as usize;
+    let name_offset = u16_at(attr, 10) as usize;
+    let utf16: Vec<u16> = attr.get(name_offset..name_offset + name_length * 2)
+        .unwrap_or(&[])
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&utf16)
+}
+
+fn resident_value(attr: &[u8]) -> &[u8] {
+    let length = u32_at(attr, 0x10) as usize;
+    let offset = u16_at(attr, 0x14) as usize;
+    attr.get(offset..offset + length).unwrap_or(&[])
+}
+
+fn data_piece(attr: &[u8]) -> DataPiece {
+    let flags = u16_at(attr, 12);
+    if attr[8] == 0 {
+        let value = resident_value(attr).to_vec();
+        return DataPiece {
+            start_vcn: 0,
+            runs: Vec::new(),
+            real_size: Some(value.len() as u64),
+            resident: Some(value),
+            flags,
+        };
+    }
+
+    let start_vcn = u64_at(attr, 0x10);
+    let runs_offset = u16_at(attr, 0x20) as usize;
+    DataPiece {
+        start_vcn,
+        runs: decode_runs(attr.get(runs_offset..).unwrap_or(&[])),
+        // Only the first piece carries the valid sizes
+        real_size: (start_vcn == 0).then(|| u64_at(attr, 0x30)),
+        resident: None,
+        flags,
+    }
+}
+
+impl<'a> NtfsVolume<'a> {
+    pub fn open(reader: &'a mut dyn ReadSeek, offset: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut boot = [0u8; 512];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut boot)?;
+        if &boot[3..11] != b"NTFS    " {
+            return Err("no NTFS boot sector".into());
+        }
+
+        let bytes_per_sector = u16_at(&boot, 0x0B) as u64;
+        if !bytes_per_sector.is_power_of_two() || !(256..=4096).contains(&bytes_per_sector) {
+            return Err("NTFS boot sector has an invalid sector size".into());
+        }
+        // Clusters above 64 KiB are stored as a negative exponent: 0xF4 -> 2^12 sectors
+        let sectors_per_cluster = match boot[0x0D] {
+            n if n < 0x80 => n as u64,
+            n => 1u64.checked_shl(256 - n as u32).unwrap_or(0),
+        };
+        let cluster_size = bytes_per_sector.saturating_mul(sectors_per_cluster);
+        let mft_lcn = u64_at(&boot, 0x30);
+        // Positive: clusters per record, negative: 2^-n bytes
+        let clusters_per_record = boot[0x40] as i8;
+        let record_size = if clusters_per_record > 0 {
+            clusters_per_record as u64 * cluster_size
+        } else {
+            1u64.checked_shl(-(clusters_per_record as i32) as u32).unwrap_or(0)
+        };
+        // Real volumes use 1 or 4 KiB records
+        if cluster_size == 0 || cluster_size > MAX_CLUSTER_SIZE || !(256..=65536).contains(&record_size) {
+            return Err("NTFS boot sector has an invalid cluster or record size".into());
+        }
+
+        let mut volume = NtfsVolume {
+            reader,
+            offset,
+            cluster_size,
+            record_size,
+            mft_runs: vec![(Some(mft_lcn), 1 + record_size / cluster_size)],
+            mft_size: record_size,
+        };
+
+        // Record 0 is $MFT itself, its $DATA runs describe the whole table
+        let record = volume.read_record(0)?.ok_or("$MFT record 0 is invalid")?;
+        let piece = attributes(&record)
+            .into_iter()
+            .find(|(t, a)| *t == ATTR_DATA && a[9] == 0)
+            .map(|(_, a)| data_piece(a))
+            .ok_or("$MFT has no $DATA attribute")?;
+        volume.mft_size = piece.real_size.unwrap_or(0);
+        volume.mft_runs = piece.runs;
+        Ok(volume)
+    }
+
+    // Byte offset of a cluster in the image, None for LCNs past any real disk
+    fn disk_offset(&self, lcn: u64) -> Option<u64> {
+        lcn.checked_mul(self.cluster_size)?.checked_add(self.offset)
+    }
+
+    fn record_count(&self) -> u64 {
+        self.mft_size / self.record_size
+    }
+
+    /// Reads and fixes up MFT record `number`. None if the slot is not a valid FILE record.
+    fn read_record(&mut self, number: u64) -> io::Result<Option<Vec<u8>>> {
+        let mut vcn_byte = number * self.record_size;
+        let mut disk_offset = None;
+        for (lcn, length) in &self.mft_runs {
+            let run_bytes = length.saturating_mul(self.cluster_size);
+            if vcn_byte < run_bytes {
+                disk_offset = lcn.and_then(|l| self.disk_offset(l)).and_then(|o| o.checked_add(vcn_byte));
+                break;
+            }
+            vcn_byte -= run_bytes;
+        }
+        let Some(disk_offset) = disk_offset else {
+            return Ok(None);
+        };
+
+        let mut record = vec![0u8; self.record_size as usize];
+        self.reader.seek(SeekFrom::Start(disk_offset))?;
+        self.reader.read_exact(&mut record)?;
+        Ok(apply_fixups(&mut record).then_some(record))
+    }
+
+    /// Scans the MFT once for names and builds the full path of every in-use record.
+    fn build_paths(&mut self) -> io::Result<HashMap<u64, String>> {
+        let mut names: HashMap<u64, FileName> = HashMap::new();
+        let mut directories: HashSet<u64> = HashSet::new();
+
+        for number in 0..self.record_count() {
+            let Some(record) = self.read_record(number)? else {
+                continue;
+            };
+            let flags = u16_at(&record, 0x16);
+            if flags & RECORD_IN_USE == 0 {
+                continue;
+            }
+            if flags & RECORD_IS_DIRECTORY != 0 {
+                directories.insert(number);
+            }
+
+            for (_, attr) in attributes(&record).into_iter().filter(|(t, a)| *t == ATTR_FILE_NAME && a[8] == 0) {
+                let value = resident_value(attr);
+                if value.len() < 66 {
+                    continue;
+                }
+                let parent = u64_at(value, 0) & 0x0000_FFFF_FFFF_FFFF;
+                let name_length = value[64] as usize;
+                let namespace = value[65];
+                let utf16: Vec<u16> = value.get(66..66 + name_length * 2)
+                    .unwrap_or(&[])
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+
+                let replace = names.get(&number).is_none_or(|existing| existing.namespace == NAMESPACE_DOS);
+                if replace {
+                    names.insert(number, FileName { parent, name: String::from_utf16_lossy(&utf16), namespace });
+                }
+            }
+        }
+
+        let mut paths = HashMap::new();
+        for &number in names.keys() {
+            if let Some(path) = resolve_path(number, &names, &mut paths, 0) {
+                paths.insert(number, path);
+            }
+        }
+        // Directories are only needed to resolve their children
+        paths.retain(|number, _| !directories.contains(number));
+        Ok(paths)
+    }
+
+    /// Collects the $DATA pieces of the wanted records, including those stored in extension records.
+    fn collect_data(&mut self, wanted: &HashSet<u64>) -> io::Result<HashMap<(u64, String), Vec<DataPiece>>> {
+        let mut data: HashMap<(u64, String), Vec<DataPiece>> = HashMap::new();
+
+        for number in 0..self.record_count() {
+            let Some(record) = self.read_record(number)? else {
+                continue;
+            };
+            if u16_at(&record, 0x16) & RECORD_IN_USE == 0 {
+                continue;
+            }
+            let base = u64_at(&record, 0x20) & 0x0000_FFFF_FFFF_FFFF;
+            let owner = if base == 0 { number } else { base };
+            if !wanted.contains(&owner) {
+                continue;
+            }
+
+            for (_, attr) in attributes(&record).into_iter().filter(|(t, _)| *t == ATTR_DATA) {
+                data.entry((owner, attribute_name(attr))).or_default().push(data_piece(attr));
+            }
+        }
+
+        for pieces in data.values_mut() {
+            pieces.sort_by_key(|p| p.start_vcn);
+        }
+        Ok(data)
+    }
+
+    /// Copies one stream to `target`. Sparse runs are skipped when `skip_sparse` is set ($J is mostly sparse).
+    fn write_stream(&mut self, pieces: &[DataPiece], target: &Path, skip_sparse: bool) -> io::Result<u64> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(target)?;
+
+        if let Some(resident) = pieces.first().and_then(|p| p.resident.as_ref()) {
+            out.write_all(resident)?;
+            return Ok(resident.len() as u64);
+        }
+
+        let real_size = pieces.first().and_then(|p| p.real_size).unwrap_or(0);
+        let mut logical: u64 = 0;
+        let mut written: u64 = 0;
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE as usize];
+
+        for (lcn, length) in pieces.iter().flat_map(|p| p.runs.iter()) {
+            if logical >= real_size {
+                break;
+            }
+            let run_bytes = length.saturating_mul(self.cluster_size).min(real_size - logical);
+            logical += run_bytes;
+
+            let Some(lcn) = lcn else {
+                if !skip_sparse {
+                    io::copy(&mut io::repeat(0).take(run_bytes), &mut out)?;
+                    written += run_bytes;
+                }
+                continue;
+            };
+
+            let disk_offset = self.disk_offset(*lcn).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "data run beyond the image"))?;
+            self.reader.seek(SeekFrom::Start(disk_offset))?;
+            let mut remaining = run_bytes;
+            while remaining > 0 {
+                let count = remaining.min(buffer.len() as u64) as usize;
+                self.reader.read_exact(&mut buffer[..count])?;
+                out.write_all(&buffer[..count])?;
+                remaining -= count as u64;
+            }
+            written += run_bytes;
+        }
+        Ok(written)
+    }
+
+    /// Extracts every file whose path matches `is_wanted` into `target_root`, keeping the directory layout.
+    /// Only unnamed streams are copied, except for $UsnJrnl where the $J stream is what we want.
+    /// Files that can't be written (unsafe names, runs past the image end) are returned as failed.
+    pub fn extract(&mut self, target_root: &Path, is_wanted: impl Fn(&str) -> bool) -> io::Result<(Vec<ExtractedFile>, Vec<FailedFile>)> {
+        let paths = self.build_paths()?;
+        let wanted: HashMap<u64, String> = paths.into_iter().filter(|(_, path)| is_wanted(path)).collect();
+        let wanted_records: HashSet<u64> = wanted.keys().copied().collect();
+        let data = self.collect_data(&wanted_records)?;
+
+        let mut extracted = Vec::new();
+        let mut failed = Vec::new();
+        for ((record, stream), pieces) in &data {
+            let path = &wanted[record];
+            let is_usn = path.eq_ignore_ascii_case("$Extend/$UsnJrnl");
+            let (target_path, skip_sparse) = match (is_usn, stream.as_str()) {
+                (true, "$J") => ("$Extend/$J".to_string(), true),
+                (false, "") => (path.clone(), false),
+                _ => continue,
+            };
+
+            // Compressed/encrypted streams would need decompression/keys, copying them raw is useless
+            if pieces.iter().any(|p| p.flags & (ATTR_FLAG_COMPRESSED | ATTR_FLAG_ENCRYPTED) != 0) {
+                continue;
+            }
+
+            let Some(relative) = safe_path(&target_path) else {
+                failed.push(FailedFile { path: target_path, error: "unsafe file name".to_string() });
+                continue;
+            };
+            let target = target_root.join(relative);
+            match self.write_stream(pieces, &target, skip_sparse) {
+                Ok(size) => extracted.push(ExtractedFile { path: target_path, size }),
+                Err(e) => {
+                    // A half written copy would pass for the real file
+                    let _ = fs::remove_file(&target);
+                    failed.push(FailedFile { path: target_path, error: e.to_string() });
+                },
+            }
+        }
+        Ok((extracted, failed))
+    }
+}
+
+// Names come from the image, ".." or "C:" in a crafted $FILE_NAME must not leave the target folder
+fn safe_path(path: &str) -> Option<PathBuf> {
+    if path.contains(['\\', ':']) {
+        return None;
+    }
+    let path = PathBuf::from(path);
+    path.components().all(|c| matches!(c, Component::Normal(_))).then_some(path)
+}
+
+fn resolve_path(number: u64, names: &HashMap<u64, FileName>, cache: &mut HashMap<u64, String>, depth: u32) -> Option<String> {
+    if number == ROOT_RECORD {
+        return Some(String::new());
+    }
+    if let Some(path) = cache.get(&number) {
+        return Some(path.clone());
+    }
+    // Orphaned or looping entries
+    if depth > 64 {
+        return None;
+    }
+
+    let entry = names.get(&number)?;
+    let parent_path = resolve_path(entry.parent, names, cache, depth + 1)?;
+    let path = if parent_path.is_empty() { entry.name.clone() } else { format!("{}/{}", parent_path, entry.name) };
+    cache.insert(number, path.clone());
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const CLUSTER: usize = 512;
+    const RECORD: usize = 1024;
+    const MFT_LCN: usize = 4;
+    const MFT_RECORDS: usize = 10;
+
+    fn header(attr_type: u32, length: usize, non_resident: bool) -> Vec<u8> {
+        let mut attr = vec![0u8; length];
+        attr[0..4].copy_from_slice(&attr_type.to_le_bytes());
+        attr[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+        attr[8] = non_resident as u8;
+        attr
+    }
+
+    fn resident(attr_type: u32, value: &[u8]) -> Vec<u8> {
+        let mut attr = header(attr_type, (RESIDENT_HEADER_SIZE + value.len()).div_ceil(8) * 8, false);
+        attr[0x10..0x14].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        attr[0x14..0x16].copy_from_slice(&(RESIDENT_HEADER_SIZE as u16).to_le_bytes());
+        attr[RESIDENT_HEADER_SIZE..RESIDENT_HEADER_SIZE + value.len()].copy_from_slice(value);
+        attr
+    }
+
+    fn non_resident(attr_type: u32, runs: &[u8], real_size: u64) -> Vec<u8> {
+        let mut attr = header(attr_type, (NON_RESIDENT_HEADER_SIZE + runs.len()).div_ceil(8) * 8, true);
+        attr[0x20..0x22].copy_from_slice(&(NON_RESIDENT_HEADER_SIZE as u16).to_le_bytes());
+        attr[0x30..0x38].copy_from_slice(&real_size.to_le_bytes());
+        attr[NON_RESIDENT_HEADER_SIZE..NON_RESIDENT_HEADER_SIZE + runs.len()].copy_from_slice(runs);
+        attr
+    }
+
+    fn file_name(parent: u64, name: &str) -> Vec<u8> {
+        let utf16: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        let mut value = vec![0u8; 66];
+        value[0..8].copy_from_slice(&parent.to_le_bytes());
+        value[64] = name.encode_utf16().count() as u8;
+        value[65] = 1;
+        value.extend(utf16);
+        resident(ATTR_FILE_NAME, &value)
+    }
+
+    // FILE record with an update sequence array that restores zeros at both sector ends
+    fn record(flags: u16, attrs: &[Vec<u8>]) -> Vec<u8> {
+        let mut record = vec![0u8; RECORD];
+        record[0..4].copy_from_slice(b"FILE");
+        record[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        record[6..8].copy_from_slice(&3u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+        let mut pos = 0x38;
+        for attr in attrs {
+            record[pos..pos + attr.len()].copy_from_slice(attr);
+            pos += attr.len();
+        }
+        record[pos..pos + 4].copy_from_slice(&ATTR_END.to_le_bytes());
+        record
+    }
+
+    fn volume(extra: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let mft_bytes = MFT_RECORDS * RECORD;
+        let mut image = vec![0u8; MFT_LCN * CLUSTER + mft_bytes];
+        image[3..11].copy_from_slice(b"NTFS    ");
+        image[0x0B..0x0D].copy_from_slice(&(CLUSTER as u16).to_le_bytes());
+        image[0x0D] = 1;
+        image[0x30..0x38].copy_from_slice(&(MFT_LCN as u64).to_le_bytes());
+        image[0x40] = (RECORD / CLUSTER) as u8;
+
+        let mft_clusters = (mft_bytes / CLUSTER) as u8;
+        let mut records = vec![
+            (0, record(RECORD_IN_USE, &[non_resident(ATTR_DATA, &[0x11, mft_clusters, MFT_LCN as u8, 0], mft_bytes as u64)])),
+            (ROOT_RECORD as usize, record(RECORD_IN_USE | RECORD_IS_DIRECTORY, &[file_name(ROOT_RECORD, ".")])),
+        ];
+        records.extend(extra.iter().cloned());
+        for (number, data) in records {
+            let start = MFT_LCN * CLUSTER + number * RECORD;
+            image[start..start + RECORD].copy_from_slice(&data);
+        }
+        image
+    }
+
+    fn extract_all(image: Vec<u8>, name: &str) -> (Vec<String>, Vec<String>, PathBuf) {
+        let target = std::env::temp_dir().join(format!("tracenexus-ntfs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&target);
+        let mut cursor = Cursor::new(image);
+        let mut volume = NtfsVolume::open(&mut cursor, 0).unwrap();
+        let (extracted, failed) = volume.extract(&target, |_| true).unwrap();
+        let mut paths: Vec<String> = extracted.into_iter().map(|f| f.path).collect();
+        paths.sort();
+        let mut failed: Vec<String> = failed.into_iter().map(|f| f.path).collect();
+        failed.sort();
+        (paths, failed, target)
+    }
+
+    #[test]
+    fn decodes_runs() {
+        // 0x10 clusters at 0x100, 0x08 clusters 0x20 before that, 4 sparse clusters
+        let runs = decode_runs(&[0x21, 0x10, 0x00, 0x01, 0x11, 0x08, 0xE0, 0x01, 0x04, 0x00]);
+        assert_eq!(runs, vec![(Some(0x100), 0x10), (Some(0xE0), 0x08), (None, 4)]);
+    }
+
+    #[test]
+    fn corrupt_runs_end_the_list() {
+        // Length field of 9 bytes, a negative LCN and an LCN overflowing i64
+        assert!(decode_runs(&[0x09, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0]).is_empty());
+        assert_eq!(decode_runs(&[0x11, 0x01, 0x10, 0x11, 0x01, 0x80, 0x00]), vec![(Some(0x10), 1)]);
+        let mut overflow = vec![0x81, 0x01];
+        overflow.extend([0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+        overflow.extend([0x81, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0x00]);
+        assert_eq!(decode_runs(&overflow).len(), 1);
+    }
+
+    #[test]
+    fn short_attributes_are_dropped() {
+        let short_resident = header(ATTR_DATA, 0x10, false);
+        let short_non_resident = header(ATTR_DATA, 0x20, true);
+        let record = record(RECORD_IN_USE, &[short_resident, short_non_resident, file_name(ROOT_RECORD, "a")]);
+        let attrs = attributes(&record);
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].0, ATTR_FILE_NAME);
+    }
+
+    #[test]
+    fn extracts_resident_file() {
+        let file = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "hello.txt"), resident(ATTR_DATA, b"hello world")]);
+        let (paths, _, target) = extract_all(volume(&[(6, file)]), "resident");
+        assert_eq!(paths, vec!["hello.txt"]);
+        assert_eq!(fs::read(target.join("hello.txt")).unwrap(), b"hello world");
+        let _ = fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn corrupt_records_dont_stop_the_scan() {
+        let truncated = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "bad.bin"), header(ATTR_DATA, 0x20, true)]);
+        // Run header claiming a 15 byte length field
+        let garbage_runs = non_resident(ATTR_DATA, &[0x9F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], 4096);
+        let far = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "far.bin"), garbage_runs]);
+        let good = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "ok.txt"), resident(ATTR_DATA, b"ok")]);
+        let (paths, _, target) = extract_all(volume(&[(6, truncated), (7, far), (8, good)]), "corrupt");
+        assert!(paths.contains(&"ok.txt".to_string()));
+        let _ = fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn rejects_invalid_record_size() {
+        let mut image = volume(&[]);
+        image[0x40] = 0x80; // 2^128 bytes
+        assert!(NtfsVolume::open(&mut Cursor::new(image), 0).is_err());
+    }
+
+    #[test]
+    fn dot_dot_names_stay_inside_the_target() {
+        let recent = record(RECORD_IN_USE | RECORD_IS_DIRECTORY, &[file_name(ROOT_RECORD, "Recent")]);
+        let up = record(RECORD_IN_USE | RECORD_IS_DIRECTORY, &[file_name(6, "..")]);
+        let escape = record(RECORD_IN_USE, &[file_name(7, "evil.txt"), resident(ATTR_DATA, b"x")]);
+        let colon = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "C:evil.txt"), resident(ATTR_DATA, b"x")]);
+        let backslash = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "..\\evil.txt"), resident(ATTR_DATA, b"x")]);
+        let (paths, failed, target) = extract_all(volume(&[(6, recent), (7, up), (8, escape), (9, colon), (4, backslash)]), "escape");
+        assert!(paths.is_empty());
+        assert_eq!(failed, vec!["..\\evil.txt", "C:evil.txt", "Recent/../evil.txt"]);
+        assert!(!target.join("evil.txt").exists());
+        let _ = fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn run_past_the_image_fails_only_that_file() {
+        // 1 cluster at LCN 0x7000, far behind the end of the image
+        let far = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "far.bin"), non_resident(ATTR_DATA, &[0x21, 0x01, 0x00, 0x70, 0x00], 512)]);
+        let good = record(RECORD_IN_USE, &[file_name(ROOT_RECORD, "ok.txt"), resident(ATTR_DATA, b"ok")]);
+        let (paths, failed, target) = extract_all(volume(&[(6, far), (7, good)]), "past-end");
+        assert_eq!(paths, vec!["ok.txt"]);
+        assert_eq!(failed, vec!["far.bin"]);
+        assert!(!target.join("far.bin").exists());
+        let _ = fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn boot_sector_geometry() {
+        // 0xF8 -> 2^8 sectors per cluster, 128 KiB clusters
+        let mut image = volume(&[]);
+        image[0x0D] = 0xF8;
+        image[0x40] = 0xF6; // 1 KiB records
+        image[0x30..0x38].copy_from_slice(&0u64.to_le_bytes());
+        // The geometry is accepted, record 0 then lands on the boot sector instead of the MFT
+        assert!(NtfsVolume::open(&mut Cursor::new(image.clone()), 0).is_err_and(|e| e.to_string().contains("$MFT record 0")));
+
+        // 2^127 sectors per cluster and sector sizes that aren't a power of two
+        image[0x0D] = 0x81;
+        assert!(NtfsVolume::open(&mut Cursor::new(image.clone()), 0).is_err_and(|e| e.to_string().contains("invalid cluster")));
+        image[0x0D] = 1;
+        image[0x0B..0x0D].copy_from_slice(&600u16.to_le_bytes());
+        assert!(NtfsVolume::open(&mut Cursor::new(image.clone()), 0).is_err_and(|e| e.to_string().contains("sector size")));
+        image[0x0B..0x0D].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        image[0x0D] = 0xFF;
+        assert!(NtfsVolume::open(&mut Cursor::new(image), 0).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::ui;
+
+// Layout from systemd's "Journal File Format" documentation
+const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+
+const HEADER_INCOMPATIBLE_FLAGS: usize = 12;
+const HEADER_ENTRY_ARRAY_OFFSET: usize = 176;
+
+const INCOMPAT_COMPACT: u32 = 1 << 4;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_ENTRY_ARRAY: u8 = 6;
+
+const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
+const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
+const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+
+// Fields that end up as top-level columns, everything else stays under "Fields"
+const KNOWN_FIELDS: &[(&str, &str)] = &[
+    ("MESSAGE", "Message"),
+    ("_SYSTEMD_UNIT", "Unit"),
+    ("_PID", "Pid"),
+    ("_UID", "Uid"),
+    ("_COMM", "Process"),
+    ("_EXE", "Executable"),
+    ("SYSLOG_IDENTIFIER", "SyslogIdentifier"),
+    ("PRIORITY", "Priority"),
+    ("_HOSTNAME", "Host"),
+    ("_BOOT_ID", "BootId"),
+];
+
+struct JournalFile {
+    data: Vec<u8>,
+    compact: bool,
+}
+
+impl JournalFile {
+    fn u64_at(&self, offset: usize) -> Option<u64> {
+        Some(u64::from_le_bytes(self.data.get(offset..offset.checked_add(8)?)?.try_into().ok()?))
+    }
+
+    /// Returns (type, flags, object bytes) for the object at `offset`.
+    fn object(&self, offset: u64) -> Option<(u8, u8, &[u8])> {
+        let offset = usize::try_from(offset).ok()?;
+        let object_type = *self.data.get(offset)?;
+        let flags = *self.data.get(offset.checked_add(1)?)?;
+        let size = usize::try_from(self.u64_at(offset.checked_add(8)?)?).ok()?;
+        if size < 16 {
+            return None;
+        }
+        // Garbage sizes in .journal~ files would wrap around
+        Some((object_type, flags, self.data.get(offset..offset.checked_add(size)?)?))
+    }
+
+    /// Follows the chain of entry array objects and returns every entry offset in order.
+    fn entry_offsets(&self) -> Vec<u64> {
+        let mut offsets = Vec::new();
+        let mut array_offset = self.u64_at(HEADER_ENTRY_ARRAY_OFFSET).unwrap_or(0);
+        let item_size = if self.compact { 4 } else { 8 };
+
+        // Guard against loops in corrupted files
+        let mut visited = 0;
+        while array_offset != 0 && visited < 1_000_000 {
+            visited += 1;
+            let Some((OBJECT_ENTRY_ARRAY, _, object)) = self.object(array_offset) else {
+                break;
+            };
+            let Some(next) = object.get(16..24).and_then(|b| b.try_into().ok()).map(u64::from_le_bytes) else {
+                break;
+            };
+
+            for item in object[24..].chunks_exact(item_size) {
+                let entry = if self.compact {
+                    u32::from_le_bytes(item.try_into().unwrap()) as u64
+                } else {
+                    u64::from_le_bytes(item.try_into().unwrap())
+                };
+                // Unused slots at the end of the last array are zero
+                if entry != 0 {
+                    offsets.push(entry);
+                }
+            }
+            array_offset = next;
+        }
+        offsets
+    }
+
+    fn entry(&self, offset: u64) -> Option<Value> {
+        let (OBJECT_ENTRY, _, object) = self.object(offset)? else {
+            return None;
+        };
+        let realtime = u64::from_le_bytes(object.get(24..32)?.try_into().ok()?);
+        let seqnum = u64::from_le_bytes(object.get(16..24)?.try_into().ok()?);
+
+        let item_size = if self.compact { 4 } else { 16 };
+        let mut fields = Map::new();
+
+        for item in object.get(64..)?.chunks_exact(item_size) {
+            let data_offset = if self.compact {
+                u32::from_le_bytes(item.try_into().ok()?) as u64
+            } else {
+                u64::from_le_bytes(item[0..8].try_into().ok()?)
+            };
+            if let Some((key, value)) = self.data_field(data_offset) {
+                fields.insert(key, Value::String(value));
+            }
+        }
+
+        let timestamp = DateTime::from_timestamp_micros(realtime as i64)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+
+        let mut record = json!({
+            "Timestamp": timestamp,
+            "Seqnum": seqnum,
+        });
+        for (field, column) in KNOWN_FIELDS {
+            record[*column] = fields.remove(*field).unwrap_or(Value::Null);
+        }
+        record["Fields"] = Value::Object(fields);
+        Some(record)
+    }
+
+    /// Reads a DATA object and splits its "FIELD=value" payload.
+    fn data_field(&self, offset: u64) -> Option<(String, String)> {
+        let (OBJECT_DATA, flags, object) = self.object(offset)? else {
+            return None;
+        };
+        let payload_start = if self.compact { 72 } else { 64 };
+        let payload = decompress(flags, object.get(payload_start..)?)?;
+
+        let split = payload.iter().position(|&b| b == b'=')?;
+        Some((
+            String::from_utf8_lossy(&payload[..split]).to_string(),
+            String::from_utf8_lossy(&payload[split + 1..]).to_string(),
+        ))
+    }
+}
+
+fn decompress(flags: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    if flags & OBJECT_COMPRESSED_ZSTD != 0 {
+        zstd::stream::decode_all(payload).ok()
+    } else if flags & OBJECT_COMPRESSED_XZ != 0 {
+        let mut out = Vec::new();
+        xz2::read::XzDecoder::new(payload).read_to_end(&mut out).ok()?;
+        Some(out)
+    } else if flags & OBJECT_COMPRESSED_LZ4 != 0 {
+        // systemd prefixes the raw LZ4 block with the uncompressed size (u64 LE)
+        let size = u64::from_le_bytes(payload.get(0..8)?.try_into().ok()?) as usize;
+        lz4_flex::block::decompress(payload.get(8..)?, size).ok()
+    } else {
+        Some(payload.to_vec())
+    }
+}
+
+/// Parses a single .journal file. Works on files from any machine, nothing is looked up locally.
+pub fn parse_file(path: &Path) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    parse_bytes(fs::read(path)?, &path.to_string_lossy())
+}
+
+fn parse_bytes(data: Vec<u8>, source: &str) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    if data.len() < 272 || &data[0..8] != SIGNATURE {
+        return Err("not a systemd journal file".into());
+    }
+
+    let incompatible = u32::from_le_bytes(data[HEADER_INCOMPATIBLE_FLAGS..HEADER_INCOMPATIBLE_FLAGS + 4].try_into()?);
+    let journal = JournalFile {
+        compact: incompatible & INCOMPAT_COMPACT != 0,
+        data,
+    };
+
+    let records = journal.entry_offsets()
+        .into_iter()
+        .filter_map(|offset| journal.entry(offset))
+        .map(|mut record| {
+            record["SourceFile"] = Value::String(source.to_string());
+            record
+        })
+        .collect();
+    Ok(records)
+}
+
+/// Parses every *.journal (and *.journal~ left behind by unclean shutdowns) below `dir`.
+pub fn collect_dir(dir: &Path) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        let file_name = entry.file_name().to_string_lossy();
+        if !entry.path().is_file() || !(file_name.ends_with(".journal") || file_name.ends_with(".journal~")) {
+            continue;
+        }
+
+        match parse_file(entry.path()) {
+            Ok(entries) => records.extend(entries),
+            Err(e) => ui::error(&format!("Could not parse journal {}: {}", entry.path().display(), e)),
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_SIZE: usize = 272;
+
+    // Header with the entry array chain starting right behind it, followed by `objects`
+    fn journal(objects: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[0..8].copy_from_slice(SIGNATURE);
+        data[HEADER_ENTRY_ARRAY_OFFSET..HEADER_ENTRY_ARRAY_OFFSET + 8].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        data.extend_from_slice(objects);
+        data
+    }
+
+    fn object_header(object_type: u8, size: u64) -> Vec<u8> {
+        let mut header = vec![object_type, 0, 0, 0, 0, 0, 0, 0];
+        header.extend_from_slice(&size.to_le_bytes());
+        header
+    }
+
+    // Object with its header, padded to the 8 byte alignment journald uses
+    fn object(object_type: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+        let mut object = object_header(object_type, 16 + body.len() as u64);
+        object[1] = flags;
+        object.extend_from_slice(body);
+        object.resize(object.len().next_multiple_of(8), 0);
+        object
+    }
+
+    /// A journal with one entry made of `fields` (flags, "FIELD=value" payload, already compressed).
+    fn single_entry(compact: bool, fields: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut data = journal(&[]);
+        if compact {
+            data[HEADER_INCOMPATIBLE_FLAGS..HEADER_INCOMPATIBLE_FLAGS + 4].copy_from_slice(&INCOMPAT_COMPACT.to_le_bytes());
+        }
+        let push = |data: &mut Vec<u8>, object: Vec<u8>| {
+            let offset = data.len() as u64;
+            data.extend(object);
+            offset
+        };
+
+        // hash, next hash, next field, entry, entry array, n_entries (+ compact tail fields)
+        let data_fields = if compact { 56 } else { 48 };
+        let offsets: Vec<u64> = fields.iter()
+            .map(|(flags, payload)| push(&mut data, object(OBJECT_DATA, *flags, &[vec![0; data_fields], payload.clone()].concat())))
+            .collect();
+
+        // seqnum, realtime (2024-05-01T10:00:00.5Z), monotonic, boot id, xor hash, items
+        let mut entry = 7u64.to_le_bytes().to_vec();
+        entry.extend_from_slice(&1_714_557_600_500_000u64.to_le_bytes());
+        entry.extend_from_slice(&[0; 32]);
+        for offset in &offsets {
+            if compact {
+                entry.extend_from_slice(&(*offset as u32).to_le_bytes());
+            } else {
+                entry.extend_from_slice(&offset.to_le_bytes());
+                entry.extend_from_slice(&[0; 8]);
+            }
+        }
+        let entry_offset = push(&mut data, object(OBJECT_ENTRY, 0, &entry));
+
+        let mut array = vec![0; 8];
+        if compact {
+            array.extend_from_slice(&(entry_offset as u32).to_le_bytes());
+        } else {
+            array.extend_from_slice(&entry_offset.to_le_bytes());
+        }
+        let array_offset = push(&mut data, object(OBJECT_ENTRY_ARRAY, 0, &array));
+        data[HEADER_ENTRY_ARRAY_OFFSET..HEADER_ENTRY_ARRAY_OFFSET + 8].copy_from_slice(&array_offset.to_le_bytes());
+        data
+    }
+
+    fn plain(payload: &str) -> (u8, Vec<u8>) {
+        (0, payload.as_bytes().to_vec())
+    }
+
+    fn check_entry(records: &[Value]) {
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["Timestamp"], "2024-05-01T10:00:00.500+00:00");
+        assert_eq!(records[0]["Seqnum"], 7);
+        assert_eq!(records[0]["Message"], "Accepted publickey for root");
+        assert_eq!(records[0]["Pid"], "1234");
+        assert_eq!(records[0]["Process"], "sshd");
+    }
+
+    #[test]
+    fn regular_entry() {
+        let data = single_entry(false, &[plain("MESSAGE=Accepted publickey for root"), plain("_PID=1234"), plain("_COMM=sshd"), plain("CODE_LINE=42")]);
+        let records = parse_bytes(data, "system.journal").unwrap();
+        check_entry(&records);
+        assert_eq!(records[0]["Fields"]["CODE_LINE"], "42");
+        assert_eq!(records[0]["SourceFile"], "system.journal");
+    }
+
+    #[test]
+    fn compact_entry() {
+        let data = single_entry(true, &[plain("MESSAGE=Accepted publickey for root"), plain("_PID=1234"), plain("_COMM=sshd")]);
+        check_entry(&parse_bytes(data, "system.journal").unwrap());
+    }
+
+    #[test]
+    fn compressed_data_objects() {
+        use std::io::Write;
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(b"MESSAGE=Accepted publickey for root").unwrap();
+        let xz = xz.finish().unwrap();
+        // systemd stores the uncompressed size in front of the LZ4 block
+        let lz4 = [&9u64.to_le_bytes()[..], &lz4_flex::block::compress(b"_PID=1234")].concat();
+        let zstd = zstd::stream::encode_all(&b"_COMM=sshd"[..], 0).unwrap();
+
+        for compact in [false, true] {
+            let data = single_entry(compact, &[
+                (OBJECT_COMPRESSED_XZ, xz.clone()),
+                (OBJECT_COMPRESSED_LZ4, lz4.clone()),
+                (OBJECT_COMPRESSED_ZSTD, zstd.clone()),
+            ]);
+            check_entry(&parse_bytes(data, "system.journal").unwrap());
+        }
+    }
+
+    #[test]
+    fn entry_array_shorter_than_its_next_pointer() {
+        // Size 20 passes the 16 byte minimum but ends inside the "next" field
+        let mut object = object_header(OBJECT_ENTRY_ARRAY, 20);
+        object.extend_from_slice(&[0; 4]);
+        let records = parse_bytes(journal(&object), "test.journal~").unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn object_size_overflowing_the_offset() {
+        let records = parse_bytes(journal(&object_header(OBJECT_ENTRY_ARRAY, u64::MAX)), "test.journal~").unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn truncated_file() {
+        // Entry array announces 64 bytes, the file ends after 24
+        let mut object = object_header(OBJECT_ENTRY_ARRAY, 64);
+        object.extend_from_slice(&[0; 8]);
+        assert!(parse_bytes(journal(&object), "test.journal~").unwrap().is_empty());
+        assert!(parse_bytes(journal(&[])[..200].to_vec(), "test.journal~").is_err());
+    }
+}
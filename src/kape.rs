@@ -0,0 +1,154 @@
+use serde_json::{json, Map, Value};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::dialect;
+use crate::profiles;
+use crate::ui;
+
+// KAPE writes <tdest>\C\Windows\..., optionally below a %m (machine name) folder
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// Runs the Windows pipeline on a KAPE target collection and returns the collection metadata
+/// for the case summary: where it came from and the hashes from KAPE's *_CopyLog.csv.
+pub fn import(kape_dir: &Path, out_dir: &str, light: bool) -> Result<Value, Box<dyn std::error::Error>> {
+    let kape_dir = fs::canonicalize(kape_dir)?;
+    let system_root = find_system_root(&kape_dir).ok_or("no drive folder with Windows\\System32\\config found")?;
+    ui::info(&format!("KAPE system root: {}", system_root.display()));
+
+    if light {
+        profiles::run_light(out_dir, Some(&system_root));
+    } else {
+        profiles::run_full(out_dir, Some(&system_root));
+    }
+
+    let copy_log = read_copy_logs(&kape_dir);
+    let mismatches = copy_log.iter().filter(|e| e["HashStatus"] == "Mismatch").count();
+    if mismatches > 0 {
+        ui::warn(&format!("{} files do not match the SHA1 recorded in the KAPE copy log", mismatches));
+    }
+    ui::success(&format!("KAPE copy log: {} files", copy_log.len()));
+
+    Ok(json!({
+        "type": "KAPE",
+        "path": kape_dir.to_string_lossy(),
+        "system_root": system_root.to_string_lossy(),
+        "copied_files": copy_log.len(),
+        "hash_mismatches": mismatches,
+        "copy_log": copy_log,
+    }))
+}
+
+/// Finds the drive letter folder (C, D, ...) that holds the Windows installation.
+fn find_system_root(kape_dir: &Path) -> Option<PathBuf> {
+    // Pointing at the drive folder itself works too
+    std::iter::once(kape_dir.to_path_buf())
+        .chain(
+            WalkDir::new(kape_dir)
+                .min_depth(1)
+                .max_depth(MAX_SEARCH_DEPTH)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_dir() && e.file_name().len() == 1)
+                .map(|e| e.path().to_path_buf()),
+        )
+        .find(|dir| dir.join("Windows").join("System32").join("config").is_dir())
+}
+
+/// Reads every *_CopyLog.csv and checks the copied files against the recorded source SHA1.
+fn read_copy_logs(kape_dir: &Path) -> Vec<Value> {
+    let mut entries = Vec::new();
+
+    for log in WalkDir::new(kape_dir).max_depth(MAX_SEARCH_DEPTH).into_iter().filter_map(|e| e.ok()) {
+        if !log.file_name().to_string_lossy().ends_with("_CopyLog.csv") {
+            continue;
+        }
+        let Ok((mut rdr, _)) = dialect::open(log.path()) else {
+            continue;
+        };
+        let Ok(headers) = rdr.headers().cloned() else {
+            continue;
+        };
+
+        for record in rdr.records().filter_map(|r| r.ok()) {
+            let mut entry: Map<String, Value> = headers.iter()
+                .zip(record.iter())
+                .map(|(h, v)| (h.to_string(), Value::String(v.to_string())))
+                .collect();
+
+            let status = match (entry.get("DestinationFile").and_then(|v| v.as_str()), entry.get("SourceFileSha1").and_then(|v| v.as_str())) {
+                (Some(destination), Some(expected)) if !expected.is_empty() => {
+                    match locate_copy(kape_dir, destination).and_then(|p| sha1_file(&p)) {
+                        Some(actual) if actual.eq_ignore_ascii_case(expected) => "Verified",
+                        Some(_) => "Mismatch",
+                        None => "Missing",
+                    }
+                },
+                _ => "NoHash",
+            };
+            entry.insert("HashStatus".to_string(), Value::String(status.to_string()));
+            entries.push(Value::Object(entry));
+        }
+    }
+    entries
+}
+
+// DestinationFile is the absolute path on the collecting machine (e.g. E:\tdest\C\...),
+// the longest trailing part that exists below the import folder is the copy we have
+fn locate_copy(kape_dir: &Path, destination: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = destination.split(['\\', '/']).filter(|p| !p.is_empty()).collect();
+    (0..parts.len())
+        .map(|skip| kape_dir.join(parts[skip..].join(std::path::MAIN_SEPARATOR_STR)))
+        .find(|candidate| candidate.is_file())
+}
+
+// KAPE records SHA1, so that's what we compare against
+fn sha1_file(path: &Path) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut hasher = Sha1::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_root_and_copy_log_hashes() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-kape-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let drive = dir.join("SERVER01").join("C");
+        fs::create_dir_all(drive.join("Windows").join("System32").join("config")).unwrap();
+        fs::create_dir_all(dir.join("SERVER01").join("D").join("Data")).unwrap();
+        fs::write(drive.join("Windows").join("good.txt"), "hello").unwrap();
+        fs::write(drive.join("Windows").join("tampered.txt"), "hello, changed").unwrap();
+        // sha1("hello")
+        let sha1 = "AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D";
+        fs::write(dir.join("2024-03-01T100000_CopyLog.csv"), [
+            "CopyTime,SourceFile,DestinationFile,FileSize,SourceFileSha1".to_string(),
+            format!("2024-03-01 10:00:00,C:\\Windows\\good.txt,E:\\tdest\\SERVER01\\C\\Windows\\good.txt,5,{}", sha1),
+            format!("2024-03-01 10:00:01,C:\\Windows\\tampered.txt,E:\\tdest\\SERVER01\\C\\Windows\\tampered.txt,5,{}", sha1),
+            format!("2024-03-01 10:00:02,C:\\Windows\\gone.txt,E:\\tdest\\SERVER01\\C\\Windows\\gone.txt,5,{}", sha1),
+            "2024-03-01 10:00:03,C:\\Windows\\nohash.txt,E:\\tdest\\SERVER01\\C\\Windows\\nohash.txt,5,".to_string(),
+        ].join("\r\n")).unwrap();
+
+        assert_eq!(find_system_root(&dir), Some(drive.clone()));
+        assert_eq!(find_system_root(&drive), Some(drive.clone()));
+        assert_eq!(find_system_root(&dir.join("SERVER01").join("D")), None);
+
+        let log = read_copy_logs(&dir);
+        let statuses: Vec<(&str, &str)> = log.iter()
+            .map(|e| (e["SourceFile"].as_str().unwrap(), e["HashStatus"].as_str().unwrap()))
+            .collect();
+        assert_eq!(statuses, vec![
+            ("C:\\Windows\\good.txt", "Verified"),
+            ("C:\\Windows\\tampered.txt", "Mismatch"),
+            ("C:\\Windows\\gone.txt", "Missing"),
+            ("C:\\Windows\\nohash.txt", "NoHash"),
+        ]);
+        assert_eq!(log[0]["FileSize"], "5");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,751 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use flate2::read::GzDecoder;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::ui;
+use crate::journal;
+use crate::timezone;
+use crate::utmp;
+
+// struct lastlog: ll_time (i32), ll_line[32], ll_host[256], indexed by UID
+const LASTLOG_RECORD_SIZE: usize = 292;
+
+const AUTH_LOGS: &[&str] = &["var/log/auth.log", "var/log/secure"];
+
+const HISTORY_FILES: &[(&str, &str)] = &[
+    (".bash_history", "bash"),
+    (".zsh_history", "zsh"),
+    (".sh_history", "sh"),
+    (".ash_history", "ash"),
+    (".local/share/fish/fish_history", "fish"),
+];
+
+const CRON_LOCATIONS: &[&str] = &[
+    "etc/crontab",
+    "etc/cron.d",
+    "var/spool/cron/crontabs",
+    "var/spool/cron",
+];
+
+const SYSTEMD_UNIT_DIRS: &[&str] = &[
+    "etc/systemd/system",
+    "usr/lib/systemd/system",
+    "lib/systemd/system",
+    "run/systemd/system",
+];
+
+const PACKAGE_LOGS: &[&str] = &[
+    "var/log/dpkg.log",
+    "var/log/apt/history.log",
+    "var/log/yum.log",
+    "var/log/dnf.rpm.log",
+];
+
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+/// Native Linux triage. `root` is "/" on a live host, or a mounted/extracted image.
+/// `journal_dir` overrides where the systemd journal files are read from.
+/// `zone` is the host's zone, syslog and package logs are written in local time (None = UTC).
+pub fn collect(root: &Path, out_dir: &str, journal_dir: Option<&Path>, zone: Option<Tz>) {
+    let accounts = read_accounts(&root.join("etc/passwd"));
+    ui::info(&format!("Found {} accounts in /etc/passwd", accounts.len()));
+
+    let datasets = [
+        ("Linux_AuthLog", collect_auth_logs(root, zone)),
+        ("Linux_Journal", collect_journals(root, journal_dir)),
+        ("Linux_Wtmp", read_utmp_files(root, "var/log/wtmp")),
+        ("Linux_Btmp", read_utmp_files(root, "var/log/btmp")),
+        ("Linux_Lastlog", read_lastlog(&root.join("var/log/lastlog"), &accounts)),
+        ("Linux_Accounts", diff_account_files(root)),
+        ("Linux_ShellHistory", collect_shell_history(root, &accounts)),
+        ("Linux_Cron", collect_crontabs(root)),
+        ("Linux_SystemdUnits", collect_systemd_units(root, &accounts)),
+        ("Linux_AuthorizedKeys", collect_authorized_keys(root, &accounts)),
+        ("Linux_PackageLog", collect_package_logs(root, zone)),
+    ];
+
+    for (name, records) in datasets {
+        ui::info(&format!("Executing: {}", name));
+        let count = records.len();
+        let target = Path::new(out_dir).join(format!("{}.json", name));
+        let _ = fs::write(target, serde_json::to_string(&records).unwrap());
+        ui::success(&format!("{} finished successfully. {} records.", name, count));
+    }
+}
+
+pub fn read_accounts(passwd: &Path) -> Vec<Account> {
+    fs::read_to_string(passwd)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() >= 7).then(|| Account {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().unwrap_or(u32::MAX),
+                home: fields[5].to_string(),
+                shell: fields[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+// Absolute paths from passwd have to be resolved below the source root
+fn under_root(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+/// Resolves every symlink on the way from `root` to `path`, absolute targets are taken relative to
+/// the source root so an image's /lib/systemd/... or /var/log -> /data/log link doesn't end up
+/// reading the analysis host's files. ".." never climbs above the root.
+pub fn resolve(root: &Path, path: &Path) -> PathBuf {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return path.to_path_buf();
+    };
+    let mut pending: VecDeque<OsString> = relative.iter().map(|c| c.to_os_string()).collect();
+    let mut current = root.to_path_buf();
+    let mut links = 0;
+
+    while let Some(component) = pending.pop_front() {
+        if component == ".." {
+            if current.as_path() != root {
+                current.pop();
+            }
+            continue;
+        }
+        if component == "." || component == "/" {
+            continue;
+        }
+        let next = current.join(&component);
+        // Same limit as the kernel's MAXSYMLINKS
+        if links < 40
+            && fs::symlink_metadata(&next).is_ok_and(|m| m.file_type().is_symlink())
+            && let Ok(target) = fs::read_link(&next)
+        {
+            links += 1;
+            if target.is_absolute() {
+                current = root.to_path_buf();
+            }
+            for part in target.iter().rev() {
+                pending.push_front(part.to_os_string());
+            }
+            continue;
+        }
+        current = next;
+    }
+    current
+}
+
+fn modified_time(root: &Path, path: &Path) -> String {
+    fs::metadata(resolve(root, path))
+        .and_then(|m| m.modified())
+        .map(|t| DateTime::<chrono::Utc>::from(t).to_rfc3339())
+        .unwrap_or_default()
+}
+
+// mtime of a log in the host's local time, the reference for timestamps without a year
+fn reference_time(path: &Path, zone: Option<Tz>) -> NaiveDateTime {
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    match zone {
+        Some(zone) => modified.with_timezone(&zone).naive_local(),
+        None => modified.naive_utc(),
+    }
+}
+
+// A time in the DST gap doesn't exist locally, the line is kept with its raw time like the refiner does
+fn local_time(naive: NaiveDateTime, raw: &str, zone: Option<Tz>) -> String {
+    timezone::local_to_utc(naive, zone)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| raw.to_string())
+}
+
+// "Dec 31 23:59:59" in a file last written in January belongs to the year before
+fn yearless_time(raw: &str, format: &str, reference: NaiveDateTime) -> Option<NaiveDateTime> {
+    let parse = |year: i32| NaiveDateTime::parse_from_str(&format!("{} {}", year, raw), &format!("%Y {}", format)).ok();
+    match parse(reference.year()) {
+        Some(time) if time <= reference => Some(time),
+        // Feb 29 only parses in one of the two years
+        current => parse(reference.year() - 1).or(current),
+    }
+}
+
+/// Reads a log file, transparently decompressing rotated .gz files.
+fn read_log(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    if path.extension().is_some_and(|e| e == "gz") {
+        let mut text = String::new();
+        GzDecoder::new(&data[..]).read_to_string(&mut text).ok()?;
+        Some(text)
+    } else {
+        Some(String::from_utf8_lossy(&data).to_string())
+    }
+}
+
+/// auth.log, auth.log.1, auth.log.2.gz, ... sorted by name
+// The log and its rotations (auth.log.1, auth.log.2.gz, secure-20240101), resolved below the root
+fn rotated_files(root: &Path, log: &str) -> Vec<PathBuf> {
+    let base = Path::new(log);
+    let (Some(dir), Some(name)) = (base.parent(), base.file_name()) else {
+        return Vec::new();
+    };
+    let name = name.to_string_lossy().to_string();
+
+    let mut files: Vec<PathBuf> = fs::read_dir(resolve(root, &root.join(dir)))
+        .map(|entries| {
+            entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    let file_name = p.file_name().unwrap_or_default().to_string_lossy();
+                    file_name == name || file_name.starts_with(&format!("{}.", name)) || file_name.starts_with(&format!("{}-", name))
+                })
+                .map(|p| resolve(root, &p))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+fn collect_auth_logs(root: &Path, zone: Option<Tz>) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for log in AUTH_LOGS {
+        for path in rotated_files(root, log) {
+            let Some(text) = read_log(&path) else {
+                continue;
+            };
+            // Classic syslog lines have no year, the file's mtime is the best reference we have
+            let reference = reference_time(&path, zone);
+
+            for line in text.lines() {
+                if let Some(mut record) = parse_syslog_line(line, reference, zone) {
+                    record["SourceFile"] = Value::String(path.to_string_lossy().to_string());
+                    records.push(record);
+                }
+            }
+        }
+    }
+    records
+}
+
+/// Parses "Jan  2 03:04:05 host sshd[123]: message" and the RFC 3339 variant rsyslog writes.
+/// `reference` is the local time the file was last written, lines without a year are placed before it.
+pub fn parse_syslog_line(line: &str, reference: NaiveDateTime, zone: Option<Tz>) -> Option<Value> {
+    let (timestamp, raw, rest) = if line.len() > 15 && line.as_bytes()[0].is_ascii_digit() {
+        let (ts, rest) = line.split_once(' ')?;
+        let dt = DateTime::parse_from_rfc3339(ts).ok()?;
+        (dt.to_utc().to_rfc3339(), ts, rest)
+    } else {
+        let raw = line.get(..15)?;
+        let naive = yearless_time(raw, "%b %e %H:%M:%S", reference)?;
+        (local_time(naive, raw, zone), raw, line.get(16..)?)
+    };
+
+    let (host, rest) = rest.split_once(' ')?;
+    let (tag, message) = rest.split_once(": ").unwrap_or((rest, ""));
+    let (process, pid) = match tag.split_once('[') {
+        Some((name, pid)) => (name, pid.trim_end_matches(']')),
+        None => (tag, ""),
+    };
+
+    Some(json!({
+        "Timestamp": timestamp,
+        "Timestamp_Raw": raw,
+        "Host": host,
+        "Process": process,
+        "Pid": pid,
+        "Message": message,
+    }))
+}
+
+// Persistent journals live in /var/log/journal, volatile ones in /run/log/journal
+fn collect_journals(root: &Path, journal_dir: Option<&Path>) -> Vec<Value> {
+    match journal_dir {
+        Some(dir) => journal::collect_dir(dir),
+        None => ["var/log/journal", "run/log/journal"]
+            .iter()
+            .flat_map(|dir| journal::collect_dir(&root.join(dir)))
+            .collect(),
+    }
+}
+
+fn read_utmp_files(root: &Path, log: &str) -> Vec<Value> {
+    let mut records = Vec::new();
+    for path in rotated_files(root, log) {
+        let mut data = fs::read(&path).unwrap_or_default();
+        if path.extension().is_some_and(|e| e == "gz") {
+            let mut buf = Vec::new();
+            let _ = GzDecoder::new(&data[..]).read_to_end(&mut buf);
+            data = buf;
+        }
+
+        for mut record in utmp::parse(&data) {
+            record["SourceFile"] = Value::String(path.to_string_lossy().to_string());
+            records.push(record);
+        }
+    }
+    records
+}
+
+fn read_lastlog(path: &Path, accounts: &[Account]) -> Vec<Value> {
+    let Ok(data) = fs::read(path) else {
+        return Vec::new();
+    };
+
+    accounts.iter()
+        .filter_map(|account| {
+            let offset = account.uid as usize * LASTLOG_RECORD_SIZE;
+            let record = data.get(offset..offset + LASTLOG_RECORD_SIZE)?;
+            let time = i32::from_le_bytes(record[0..4].try_into().ok()?);
+            if time == 0 {
+                return None;
+            }
+            let c_str = |bytes: &[u8]| {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                String::from_utf8_lossy(&bytes[..end]).to_string()
+            };
+
+            Some(json!({
+                "User": account.name,
+                "Uid": account.uid,
+                "Line": c_str(&record[4..36]),
+                "Host": c_str(&record[36..292]),
+                "LoginTime": DateTime::from_timestamp(time as i64, 0).map(|dt| dt.to_rfc3339()).unwrap_or_default(),
+            }))
+        })
+        .collect()
+}
+
+/// Compares /etc/passwd and /etc/group with the backups shadow-utils leaves behind (passwd-, group-).
+fn diff_account_files(root: &Path) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for file in ["etc/passwd", "etc/group"] {
+        let current_path = root.join(file);
+        let backup_path = root.join(format!("{}-", file));
+        let parse = |path: &Path| -> HashMap<String, String> {
+            fs::read_to_string(path)
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|l| l.split_once(':').map(|(name, _)| (name.to_string(), l.to_string())))
+                .collect()
+        };
+
+        let current = parse(&current_path);
+        let backup = parse(&backup_path);
+        let has_backup = backup_path.exists();
+        let file_modified = modified_time(root, &current_path);
+
+        for (name, entry) in &current {
+            let status = match backup.get(name) {
+                _ if !has_backup => "Unknown",
+                None => "Added",
+                Some(old) if old != entry => "Modified",
+                Some(_) => "Unchanged",
+            };
+            records.push(json!({
+                "File": format!("/{}", file),
+                "Name": name,
+                "Entry": entry,
+                "Status": status,
+                "FileModified": file_modified,
+            }));
+        }
+
+        for (name, entry) in backup.iter().filter(|(name, _)| !current.contains_key(*name)) {
+            records.push(json!({
+                "File": format!("/{}", file),
+                "Name": name,
+                "Entry": entry,
+                "Status": "Removed",
+                "FileModified": file_modified,
+            }));
+        }
+    }
+    records
+}
+
+fn collect_shell_history(root: &Path, accounts: &[Account]) -> Vec<Value> {
+    let mut records: Vec<Value> = Vec::new();
+
+    for account in accounts {
+        for (file, shell) in HISTORY_FILES {
+            let path = under_root(root, &account.home).join(file);
+            let Ok(data) = fs::read(resolve(root, &path)) else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(&data);
+            let mut pending_time: Option<i64> = None;
+
+            for (line_number, line) in text.lines().enumerate() {
+                // bash with HISTTIMEFORMAT writes "#<epoch>" before each command
+                if let Some(epoch) = line.strip_prefix('#').and_then(|e| e.parse::<i64>().ok()) {
+                    pending_time = Some(epoch);
+                    continue;
+                }
+
+                // fish is YAML-like: "- cmd: ..." followed by "  when: <epoch>" and optional "  paths:"
+                let (command, epoch) = if *shell == "fish" {
+                    if let Some(cmd) = line.strip_prefix("- cmd: ") {
+                        (cmd.to_string(), None)
+                    } else {
+                        if let Some(when) = line.trim().strip_prefix("when: ")
+                            && let (Some(last), Ok(epoch)) = (records.last_mut(), when.parse::<i64>())
+                        {
+                            last["Timestamp"] = epoch_to_rfc3339(epoch);
+                        }
+                        continue;
+                    }
+                // zsh extended history ": <epoch>:<duration>;command"
+                } else if let Some(rest) = line.strip_prefix(": ") {
+                    match rest.split_once(';') {
+                        Some((meta, cmd)) => (cmd.to_string(), meta.split(':').next().and_then(|e| e.parse().ok())),
+                        None => (line.to_string(), None),
+                    }
+                } else {
+                    (line.to_string(), pending_time.take())
+                };
+
+                if command.trim().is_empty() {
+                    continue;
+                }
+
+                records.push(json!({
+                    "User": account.name,
+                    "Shell": shell,
+                    "Command": command,
+                    "LineNumber": line_number + 1,
+                    "Timestamp": epoch.map(epoch_to_rfc3339).unwrap_or(Value::Null),
+                    "HistoryFile": path.to_string_lossy(),
+                    "FileModified": modified_time(root, &path),
+                }));
+            }
+        }
+    }
+    records
+}
+
+fn epoch_to_rfc3339(epoch: i64) -> Value {
+    DateTime::from_timestamp(epoch, 0)
+        .map(|dt| Value::String(dt.to_rfc3339()))
+        .unwrap_or(Value::Null)
+}
+
+fn collect_crontabs(root: &Path) -> Vec<Value> {
+    let mut files = Vec::new();
+    for location in CRON_LOCATIONS {
+        let path = root.join(location);
+        if resolve(root, &path).is_file() {
+            files.push(path);
+        } else if let Ok(entries) = fs::read_dir(resolve(root, &path)) {
+            files.extend(entries.flatten().map(|e| e.path()).filter(|p| resolve(root, p).is_file()));
+        }
+    }
+
+    let mut records = Vec::new();
+    for path in files {
+        let Ok(text) = fs::read_to_string(resolve(root, &path)) else {
+            continue;
+        };
+        // System crontabs (/etc/crontab, /etc/cron.d) have a user column, user crontabs are named after the user
+        let is_system = path.starts_with(root.join("etc"));
+        let file_user = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Variable assignments like SHELL=/bin/sh or MAILTO=root
+            if line.split_whitespace().next().is_some_and(|first| first.contains('=')) {
+                continue;
+            }
+
+            let schedule_fields = if line.starts_with('@') { 1 } else { 5 };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() <= schedule_fields {
+                continue;
+            }
+            let schedule = fields[..schedule_fields].join(" ");
+            let (user, command) = if is_system && fields.len() > schedule_fields + 1 {
+                (fields[schedule_fields].to_string(), fields[schedule_fields + 1..].join(" "))
+            } else {
+                (file_user.clone(), fields[schedule_fields..].join(" "))
+            };
+
+            records.push(json!({
+                "SourceFile": path.to_string_lossy(),
+                "User": user,
+                "Schedule": schedule,
+                "Command": command,
+                "FileModified": modified_time(root, &path),
+            }));
+        }
+    }
+    records
+}
+
+/// Unit files including timers. User units from ~/.config/systemd/user are included.
+fn collect_systemd_units(root: &Path, accounts: &[Account]) -> Vec<Value> {
+    let mut dirs: Vec<PathBuf> = SYSTEMD_UNIT_DIRS.iter().map(|d| root.join(d)).collect();
+    dirs.extend(accounts.iter().map(|a| under_root(root, &a.home).join(".config/systemd/user")));
+
+    let mut records = Vec::new();
+    for dir in dirs {
+        for entry in walkdir::WalkDir::new(&dir).max_depth(2).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let Some(unit_type) = path.extension().map(|e| e.to_string_lossy().to_string()) else {
+                continue;
+            };
+            if !["service", "timer", "socket", "path"].contains(&unit_type.as_str()) {
+                continue;
+            }
+
+            // Symlinks in *.wants/ show what is enabled, the target is parsed on its own
+            let enabled_link = entry.path_is_symlink();
+            let content = fs::read_to_string(resolve(root, path)).unwrap_or_default();
+            let directive = |key: &str| {
+                content.lines()
+                    .filter_map(|l| l.trim().strip_prefix(&format!("{}=", key)))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            };
+
+            records.push(json!({
+                "SourceFile": path.to_string_lossy(),
+                "Unit": path.file_name().unwrap_or_default().to_string_lossy(),
+                "UnitType": unit_type,
+                "EnabledLink": enabled_link,
+                "LinkTarget": fs::read_link(path).map(|t| t.to_string_lossy().to_string()).unwrap_or_default(),
+                "ExecStart": directive("ExecStart"),
+                "User": directive("User"),
+                "OnCalendar": directive("OnCalendar"),
+                "WantedBy": directive("WantedBy"),
+                "FileModified": modified_time(root, path),
+            }));
+        }
+    }
+    records
+}
+
+fn collect_authorized_keys(root: &Path, accounts: &[Account]) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for account in accounts {
+        for file in [".ssh/authorized_keys", ".ssh/authorized_keys2"] {
+            let path = under_root(root, &account.home).join(file);
+            let Ok(text) = fs::read_to_string(resolve(root, &path)) else {
+                continue;
+            };
+
+            for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                // Optional leading options (command="...",from="..."), then type, key, comment
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let type_idx = fields.iter().position(|f| f.starts_with("ssh-") || f.starts_with("ecdsa-") || f.starts_with("sk-"));
+                let Some(type_idx) = type_idx else {
+                    continue;
+                };
+
+                records.push(json!({
+                    "User": account.name,
+                    "SourceFile": path.to_string_lossy(),
+                    "Options": fields[..type_idx].join(" "),
+                    "KeyType": fields[type_idx],
+                    "Key": fields.get(type_idx + 1).copied().unwrap_or(""),
+                    "Comment": fields.get(type_idx + 2..).map(|c| c.join(" ")).unwrap_or_default(),
+                    "Shell": account.shell,
+                    "FileModified": modified_time(root, &path),
+                }));
+            }
+        }
+    }
+    records
+}
+
+fn collect_package_logs(root: &Path, zone: Option<Tz>) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for log in PACKAGE_LOGS {
+        for path in rotated_files(root, log) {
+            let Some(text) = read_log(&path) else {
+                continue;
+            };
+            let source = path.to_string_lossy().to_string();
+            let reference = reference_time(&path, zone);
+
+            if log.ends_with("history.log") {
+                records.extend(parse_apt_history(&text, &source, zone));
+                continue;
+            }
+
+            for line in text.lines() {
+                if let Some(record) = parse_package_line(line, &source, reference, zone) {
+                    records.push(record);
+                }
+            }
+        }
+    }
+    records
+}
+
+// dpkg: "2024-01-02 03:04:05 install pkg:amd64 <none> 1.2"
+// dnf:  "2024-01-02T03:04:05+0000 SUBDEBUG Installed: pkg-1.2.x86_64"
+// yum:  "Jan 02 03:04:05 Installed: pkg-1.2.x86_64"
+fn parse_package_line(line: &str, source: &str, reference: NaiveDateTime, zone: Option<Tz>) -> Option<Value> {
+    let (timestamp, raw, action) = if let Ok(dt) = NaiveDateTime::parse_from_str(line.get(..19)?, "%Y-%m-%d %H:%M:%S") {
+        (local_time(dt, line.get(..19)?, zone), line.get(..19)?, line.get(20..)?)
+    } else if let Some((ts, rest)) = line.split_once(' ').filter(|(ts, _)| ts.contains('T')) {
+        let dt = DateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%z").ok()?;
+        (dt.to_utc().to_rfc3339(), ts, rest)
+    } else {
+        let naive = yearless_time(line.get(..15)?, "%b %d %H:%M:%S", reference)?;
+        (local_time(naive, line.get(..15)?, zone), line.get(..15)?, line.get(16..)?)
+    };
+
+    Some(json!({
+        "Timestamp": timestamp,
+        "Timestamp_Raw": raw,
+        "Action": action,
+        "SourceFile": source,
+    }))
+}
+
+// apt history.log groups each transaction in a "Start-Date: ... End-Date:" block
+fn parse_apt_history(text: &str, source: &str, zone: Option<Tz>) -> Vec<Value> {
+    let mut records = Vec::new();
+    let mut current = serde_json::Map::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                current.insert("SourceFile".into(), Value::String(source.to_string()));
+                records.push(Value::Object(std::mem::take(&mut current)));
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(": ") {
+            let key_name = if key == "Start-Date" { "Timestamp".to_string() } else { key.replace('-', "") };
+            let value = if key == "Start-Date" || key == "End-Date" {
+                current.insert(format!("{}_Raw", key_name), Value::String(value.to_string()));
+                NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d  %H:%M:%S")
+                    .ok()
+                    .and_then(|dt| timezone::local_to_utc(dt, zone))
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_else(|| value.to_string())
+            } else {
+                value.to_string()
+            };
+            current.insert(key_name, Value::String(value));
+        }
+    }
+    if !current.is_empty() {
+        current.insert("SourceFile".into(), Value::String(source.to_string()));
+        records.push(Value::Object(current));
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn syslog_year_rollover() {
+        let line = "Dec 31 23:59:58 web01 sshd[812]: Accepted publickey for bob";
+        // Written in January, so the December line is from the year before
+        let record = parse_syslog_line(line, at(2024, 1, 2), None).unwrap();
+        assert_eq!(record["Timestamp"], "2023-12-31T23:59:58+00:00");
+        assert_eq!(record["Process"], "sshd");
+        assert_eq!(record["Pid"], "812");
+
+        let record = parse_syslog_line("Jan  2 08:00:00 web01 cron[1]: x", at(2024, 1, 2), None).unwrap();
+        assert_eq!(record["Timestamp"], "2024-01-02T08:00:00+00:00");
+    }
+
+    #[test]
+    fn syslog_line_in_dst_gap() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let reference = at(2024, 4, 1);
+        let record = parse_syslog_line("Mar 31 02:30:00 web01 sshd[9]: Failed password for root", reference, Some(berlin)).unwrap();
+        assert_eq!(record["Timestamp"], "Mar 31 02:30:00");
+        assert_eq!(record["Message"], "Failed password for root");
+        let record = parse_syslog_line("Mar 31 03:30:00 web01 sshd[9]: x", reference, Some(berlin)).unwrap();
+        assert_eq!(record["Timestamp"], "2024-03-31T01:30:00+00:00");
+    }
+
+    #[test]
+    fn leap_day_without_year() {
+        assert_eq!(yearless_time("Feb 29 10:00:00", "%b %d %H:%M:%S", at(2025, 1, 5)), NaiveDate::from_ymd_opt(2024, 2, 29).unwrap().and_hms_opt(10, 0, 0));
+        assert_eq!(yearless_time("Feb 29 10:00:00", "%b %d %H:%M:%S", at(2024, 3, 1)), NaiveDate::from_ymd_opt(2024, 2, 29).unwrap().and_hms_opt(10, 0, 0));
+    }
+
+    #[test]
+    fn yum_year_from_reference() {
+        let record = parse_package_line("Nov 30 10:00:00 Installed: nmap-7.92.x86_64", "yum.log", at(2021, 12, 1), None).unwrap();
+        assert_eq!(record["Timestamp"], "2021-11-30T10:00:00+00:00");
+        assert_eq!(record["Action"], "Installed: nmap-7.92.x86_64");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_symlinks_stay_below_root() {
+        let root = std::env::temp_dir().join(format!("tracenexus-linux-{}", std::process::id()));
+        let units = root.join("etc/systemd/system/multi-user.target.wants");
+        fs::create_dir_all(&units).unwrap();
+        fs::create_dir_all(root.join("lib/systemd/system")).unwrap();
+        fs::write(root.join("lib/systemd/system/backdoor.service"), "[Service]\nExecStart=/tmp/.x/run\n").unwrap();
+        let _ = fs::remove_file(units.join("backdoor.service"));
+        std::os::unix::fs::symlink("/lib/systemd/system/backdoor.service", units.join("backdoor.service")).unwrap();
+
+        assert_eq!(resolve(&root, &units.join("backdoor.service")), root.join("lib/systemd/system/backdoor.service"));
+        let records = collect_systemd_units(&root, &[]);
+        let link = records.iter().find(|r| r["EnabledLink"] == true).unwrap();
+        assert_eq!(link["LinkTarget"], "/lib/systemd/system/backdoor.service");
+        assert_eq!(link["ExecStart"], "/tmp/.x/run");
+        assert_ne!(link["FileModified"], "");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_dir_symlinked_outside_var() {
+        use std::os::unix::fs::symlink;
+        let root = std::env::temp_dir().join(format!("tracenexus-linux-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("data/log")).unwrap();
+        fs::create_dir_all(root.join("archive")).unwrap();
+        fs::create_dir_all(root.join("var")).unwrap();
+        // /var/log -> /data/log, auth.log.1 -> /archive/auth.log.1, both absolute
+        symlink("/data/log", root.join("var/log")).unwrap();
+        fs::write(root.join("data/log/auth.log"), "Mar  2 10:00:00 web sshd[10]: Accepted publickey for root\n").unwrap();
+        fs::write(root.join("archive/auth.log.1"), "Mar  1 10:00:00 web sshd[9]: Failed password for root\n").unwrap();
+        symlink("/archive/auth.log.1", root.join("data/log/auth.log.1")).unwrap();
+
+        assert_eq!(resolve(&root, &root.join("var/log/../log/auth.log")), root.join("data/log/auth.log"));
+        assert_eq!(resolve(&root, &root.join("var/../../../etc/passwd")), root.join("etc/passwd"));
+        let files = rotated_files(&root, "var/log/auth.log");
+        assert_eq!(files, vec![root.join("archive/auth.log.1"), root.join("data/log/auth.log")]);
+
+        let records = collect_auth_logs(&root, None);
+        let mut pids: Vec<&str> = records.iter().map(|r| r["Pid"].as_str().unwrap()).collect();
+        pids.sort();
+        assert_eq!(pids, vec!["10", "9"]);
+        let _ = fs::remove_dir_all(root);
+    }
+}
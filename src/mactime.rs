@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::bodyfile::{self, BodyEntry};
+use crate::normalize;
+use crate::refiner;
+use crate::ui;
+
+// Refined sub folder for the bodyfile and the rendered mactime CSV
+const TIMELINE_DIR: &str = "Timeline";
+const BODYFILE: &str = "mft_bodyfile.txt";
+const MACTIME_CSV: &str = "mft_mactime.csv";
+
+// USN reasons that map onto the bodyfile times, every change also counts as "c"
+const USN_CREATE: &str = "FileCreate";
+const USN_MODIFY: &[&str] = &["DataOverwrite", "DataExtend", "DataTruncation"];
+
+/// Builds a bodyfile from the refined MFTECmd $MFT and $J output and renders it mactime style.
+/// Unlike the master timeline every $SI and $FN time is kept.
+pub fn export(refined_path: &Path) {
+    let mut entries = Vec::new();
+
+    for entry in WalkDir::new(refined_path).into_iter().filter_map(|e| e.ok()) {
+        let file_name = entry.file_name().to_string_lossy();
+        if !file_name.contains("MFTECmd") || !file_name.ends_with(".json") {
+            continue;
+        }
+        for record in refiner::read_json_records(entry.path()).iter().filter_map(|r| r.as_object()).map(normalize::original) {
+            if record.contains_key("UpdateReasons") {
+                entries.extend(from_usn_record(record));
+            } else if record.contains_key("Created0x10") {
+                entries.extend(from_mft_record(record));
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let timeline_dir = refined_path.join(TIMELINE_DIR);
+    fs::create_dir_all(&timeline_dir).ok();
+    let written = write_bodyfile(&timeline_dir.join(BODYFILE), &entries)
+        .map_err(|e| e.into())
+        .and_then(|_| write_mactime(&timeline_dir.join(MACTIME_CSV), &entries));
+
+    match written {
+        Ok(rows) => ui::success(&format!("Bodyfile: {} entries, mactime: {} events", entries.len(), rows)),
+        Err(e) => ui::error(&format!("Could not write MFT timeline: {}", e)),
+    }
+}
+
+fn str_field<'a>(record: &'a Map<String, Value>, key: &str) -> &'a str {
+    record.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+// Typed refinement gives numbers and booleans, --strings-only the CSV text ("True", "4096")
+fn bool_field(record: &Map<String, Value>, key: &str) -> bool {
+    match record.get(key) {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => s.trim().eq_ignore_ascii_case("true") || s.trim() == "1",
+        Some(Value::Number(n)) => n.as_u64().is_some_and(|n| n != 0),
+        _ => false,
+    }
+}
+
+fn u64_field(record: &Map<String, Value>, key: &str) -> u64 {
+    match record.get(key) {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0),
+        Some(Value::String(s)) => s.trim().parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn time_field(record: &Map<String, Value>, key: &str) -> Option<DateTime<Utc>> {
+    refiner::parse_instant(str_field(record, key))
+}
+
+// MFTECmd parent paths start at ".\" for the volume root
+fn full_path(record: &Map<String, Value>, name_key: &str) -> String {
+    let parent = str_field(record, "ParentPath").trim_start_matches('.').replace('\\', "/");
+    format!("{}/{}", parent.trim_end_matches('/'), str_field(record, name_key))
+}
+
+fn inode(record: &Map<String, Value>) -> String {
+    let number = |key: &str| record.get(key).map(|v| v.to_string().trim_matches('"').to_string()).unwrap_or_default();
+    format!("{}-{}", number("EntryNumber"), number("SequenceNumber"))
+}
+
+/// One entry for $STANDARD_INFORMATION and, like TSK, one "($FILE_NAME)" entry when the record has $FN times.
+fn from_mft_record(record: &Map<String, Value>) -> Vec<BodyEntry> {
+    let path = full_path(record, "FileName");
+    let mode = if bool_field(record, "IsDirectory") { "d/drwxrwxrwx" } else { "r/rrwxrwxrwx" };
+    let size = u64_field(record, "FileSize");
+
+    let mut entries = Vec::new();
+    for (suffix, attr) in [("", "0x10"), (" ($FILE_NAME)", "0x30")] {
+        let entry = BodyEntry {
+            md5: "0".to_string(),
+            name: format!("{}{}", path, suffix),
+            inode: inode(record),
+            mode: mode.to_string(),
+            uid: "0".to_string(),
+            gid: "0".to_string(),
+            size,
+            atime: time_field(record, &format!("LastAccess{}", attr)),
+            mtime: time_field(record, &format!("LastModified{}", attr)),
+            ctime: time_field(record, &format!("LastRecordChange{}", attr)),
+            crtime: time_field(record, &format!("Created{}", attr)),
+        };
+        if entry.atime.is_some() || entry.mtime.is_some() || entry.ctime.is_some() || entry.crtime.is_some() {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// A USN record has a single time, the reasons decide which MACB slots it fills.
+fn from_usn_record(record: &Map<String, Value>) -> Option<BodyEntry> {
+    let time = time_field(record, "UpdateTimestamp")?;
+    // MFTECmd joins the reasons with '|', the bodyfile separator
+    let reasons = str_field(record, "UpdateReasons").replace('|', ",");
+    let modified = USN_MODIFY.iter().any(|r| reasons.contains(r));
+
+    Some(BodyEntry {
+        md5: "0".to_string(),
+        name: format!("{} ($UsnJrnl: {})", full_path(record, "Name"), reasons),
+        inode: inode(record),
+        mode: "r/rrwxrwxrwx".to_string(),
+        uid: "0".to_string(),
+        gid: "0".to_string(),
+        size: 0,
+        atime: None,
+        mtime: modified.then_some(time),
+        ctime: Some(time),
+        crtime: reasons.contains(USN_CREATE).then_some(time),
+    })
+}
+
+fn write_bodyfile(path: &Path, entries: &[BodyEntry]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for entry in entries {
+        writeln!(out, "{}", entry.to_line())?;
+    }
+    out.flush()
+}
+
+/// Same columns as `mactime -d -y`: one row per distinct time of an entry with its MACB flags.
+fn write_mactime(path: &Path, entries: &[BodyEntry]) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut rows: Vec<(DateTime<Utc>, String, &BodyEntry)> = entries.iter()
+        .flat_map(|entry| bodyfile::macb_events(entry).into_iter().map(move |(time, flags)| (time, flags, entry)))
+        .collect();
+    rows.sort_by_key(|(time, _, _)| *time);
+
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["Date", "Size", "Type", "Mode", "UID", "GID", "Meta", "File Name"])?;
+    for (time, flags, entry) in &rows {
+        writer.write_record([
+            time.to_rfc3339().as_str(),
+            &entry.size.to_string(),
+            flags,
+            &entry.mode,
+            &entry.uid,
+            &entry.gid,
+            &entry.inode,
+            &entry.name,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn mft_record_typed_and_as_strings() {
+        let typed = record(json!({
+            "EntryNumber": 42, "SequenceNumber": 3, "ParentPath": ".\\Users\\bob", "FileName": "Downloads",
+            "IsDirectory": true, "FileSize": 4096,
+            "Created0x10": "2024-03-01T08:00:00+00:00", "LastModified0x10": "2024-03-02T08:00:00+00:00",
+            "Created0x30": "2024-03-01T08:00:00+00:00",
+        }));
+        let entries = from_mft_record(&typed);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "/Users/bob/Downloads");
+        assert_eq!(entries[0].inode, "42-3");
+        assert_eq!(entries[0].mode, "d/drwxrwxrwx");
+        assert_eq!(entries[0].size, 4096);
+        assert_eq!(entries[0].mtime.unwrap().to_rfc3339(), "2024-03-02T08:00:00+00:00");
+        assert!(entries[0].atime.is_none());
+        assert_eq!(entries[1].name, "/Users/bob/Downloads ($FILE_NAME)");
+
+        // --strings-only keeps every cell a string
+        let strings = record(json!({
+            "EntryNumber": "42", "SequenceNumber": "3", "ParentPath": ".", "FileName": "pagefile.sys",
+            "IsDirectory": "False", "FileSize": "1073741824", "Created0x10": "2024-03-01T08:00:00+00:00",
+        }));
+        let entries = from_mft_record(&strings);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "/pagefile.sys");
+        assert_eq!(entries[0].inode, "42-3");
+        assert_eq!(entries[0].mode, "r/rrwxrwxrwx");
+        assert_eq!(entries[0].size, 1_073_741_824);
+        assert!(bool_field(&record(json!({"IsDirectory": "True"})), "IsDirectory"));
+    }
+
+    #[test]
+    fn usn_reasons_to_macb() {
+        let usn = |reasons: &str| from_usn_record(&record(json!({
+            "UpdateTimestamp": "2024-03-01T08:00:00+00:00", "ParentPath": ".\\Temp", "Name": "x.exe",
+            "EntryNumber": 7, "SequenceNumber": 1, "UpdateReasons": reasons,
+        }))).unwrap();
+        let flags = |reasons: &str| bodyfile::macb_events(&usn(reasons))[0].1.clone();
+
+        assert_eq!(flags("FileCreate"), "..cb");
+        assert_eq!(flags("DataExtend|FileCreate|Close"), "m.cb");
+        assert_eq!(flags("DataOverwrite"), "m.c.");
+        assert_eq!(flags("RenameNewName|Close"), "..c.");
+        // '|' would break the bodyfile line
+        assert_eq!(usn("DataExtend|Close").name, "/Temp/x.exe ($UsnJrnl: DataExtend,Close)");
+        assert!(from_usn_record(&record(json!({"UpdateReasons": "FileCreate"}))).is_none());
+    }
+}
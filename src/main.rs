@@ -1,6 +1,7 @@
 use clap::Parser;
-use cli::Cli;
+use cli::{CategoriesAction, Cli, Command};
 use std::io::{self, Write};
+use std::path::Path;
 use colored::*;
 mod cli;
 mod admin;
@@ -11,6 +12,30 @@ mod manifest;
 mod refiner;
 mod uploader;
 mod ui;
+mod users;
+mod recyclebin;
+mod browser;
+mod utmp;
+mod volatile;
+mod linux;
+mod journal;
+mod containers;
+mod bolt;
+mod image;
+mod kape;
+mod velociraptor;
+mod bodyfile;
+mod mactime;
+mod timeline_import;
+mod timezone;
+mod schema;
+mod dialect;
+mod report;
+mod rules;
+mod normalize;
+mod casedb;
+mod parquet_export;
+mod timesketch;
 
 
 
@@ -21,18 +46,71 @@ fn main() {
     ui::info("Initializing TraceNexus engine...");
     let args = Cli::parse();
 
+    // Broken rules would put every file into "Other", better to stop right away
+    let rules = match rules::load(args.rules.as_deref().map(Path::new)) {
+        Ok(rules) => rules,
+        Err(e) => {
+            ui::error(&format!("Could not load category rules: {}", e));
+            std::process::exit(1);
+        }
+    };
+
+    // Same for a broken mapping, checked before hours of collection
+    let normalizer = match &args.normalize {
+        Some(schema) => match normalize::load(schema) {
+            Ok(normalizer) => Some(normalizer),
+            Err(e) => {
+                ui::error(&format!("Could not load schema mapping: {}", e));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Some(Command::Categories { action: CategoriesAction::Test { file } }) = &args.command {
+        if let Err(e) = rules::explain(&rules, Path::new(file), &mut std::io::stdout()) {
+            ui::error(&format!("Could not explain {}: {}", file, e));
+        }
+        return;
+    }
+
+    // Timeline imports only extend a case, nothing is collected or parsed with the EZ tools
+    if let Some(Command::ImportTimeline { file, format, label, mapping, time_format }) = &args.command {
+        let output_dir = std::env::current_dir().unwrap().join("output");
+        let options = timeline_import::ImportOptions {
+            format,
+            label: label.as_deref(),
+            mapping,
+            time_format: time_format.as_deref(),
+            source_tz: args.source_tz,
+            rules: &rules,
+            output_format: args.output_format,
+        };
+        if let Err(e) = timeline_import::import(Path::new(file), output_dir.to_str().unwrap(), &options) {
+            ui::error(&format!("Timeline import failed: {}", e));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Images and imported collections are just files, nothing of this host is touched
+    let offline = args.image.is_some() || args.command.is_some();
+
     // 1. Admin Check
-    if !admin::check_admin() {
+    if !offline && !admin::check_admin() {
         ui::error("Run as Administrator!");
         std::process::exit(1);
     }
-    // 2. Unblock Tools because Windows is annoying that way
-    tools::unblock_tools();
+    // Linux profile is fully native, the EZ tools are only needed on Windows
+    if !args.linux {
+        // 2. Unblock Tools because Windows is annoying that way
+        tools::unblock_tools();
 
-    // 3. Check Tools
-    if let Err(missing) = tools::verify_tools() {
-        ui::error(&format!("Missing tools: {:?}", missing));
-        std::process::exit(1);
+        // 3. Check Tools
+        if let Err(missing) = tools::verify_tools() {
+            ui::error(&format!("Missing tools: {:?}", missing));
+            std::process::exit(1);
+        }
     }
 
     // One output directory for all profiles
@@ -40,25 +118,95 @@ fn main() {
     std::fs::create_dir_all(&output_dir).ok();
     let output_str = output_dir.to_str().unwrap();
 
-    let profile_name = if args.light { "LIGHT" } else { "FULL" };
+    // 0. Volatile state first (order of volatility), before any disk tool touches the system.
+    // Pointless for an offline source root or image, that would only capture the analysis machine.
+    if args.source_root.is_none() && !offline {
+        volatile::collect(output_str);
+    }
+
+    // Artifacts from a disk image are extracted below raw/ so they end up in the raw package untouched
+    let system_root = args.image.as_deref().map(|image_path| {
+        ui::info(&format!("Extracting artifacts from image: {}", image_path));
+        match image::extract_artifacts(Path::new(image_path), &output_dir.join("raw").join("image")) {
+            Ok(root) => root,
+            Err(e) => {
+                ui::error(&format!("Could not read image: {}", e));
+                std::process::exit(1);
+            }
+        }
+    });
+
+    let profile_name = if args.light { "LIGHT" } else if args.linux { "LINUX" } else { "FULL" };
     ui::info(&format!("Starting {} collection profile...", profile_name));
 
+    // Imported collections carry their own metadata (copy logs, hashes) into the case summary
+    let mut collection_source = None;
+
     // 1. Data Collection based on profile
-    if args.light {
-        profiles::run_light(output_str);
-    } else if args.full {
-        profiles::run_full(output_str);
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::ImportKape { dir } => kape::import(Path::new(dir), output_str, args.light),
+            Command::ImportVelociraptor { zip } => velociraptor::import(Path::new(zip), output_str, args.light),
+            Command::ImportTimeline { .. } | Command::Categories { .. } => unreachable!("handled before collection"),
+        };
+        match result {
+            Ok(source) => collection_source = Some(source),
+            Err(e) => {
+                ui::error(&format!("Import failed: {}", e));
+                std::process::exit(1);
+            }
+        }
+    } else if args.light {
+        profiles::run_light(output_str, system_root.as_deref());
+    } else if args.full || system_root.is_some() {
+        profiles::run_full(output_str, system_root.as_deref());
+    } else if args.linux {
+        profiles::run_linux(output_str, args.source_root.as_deref(), args.journal_dir.as_deref(), args.source_tz);
     }
 
+    // Local times of the source are converted with its own zone, not the one of this machine
+    let linux_root = args.linux.then(|| Path::new(args.source_root.as_deref().unwrap_or("/")));
+    let (zone, zone_origin) = timezone::detect(args.source_tz, &output_dir, linux_root);
+
     ui::info("Generating collection manifest...");
-    refiner::run_refinement(output_str);
+    let refinement_errors = refiner::run_refinement(output_str, &refiner::RefineOptions {
+        zone,
+        zone_origin,
+        strings_only: args.strings_only,
+        rules: &rules,
+        normalizer: normalizer.as_ref(),
+        output_format: args.output_format.unwrap_or_default(),
+    });
     
     // ID creation moved to manifest module
-    let incident_id = manifest::create_case_summary(output_str);
+    let incident_id = manifest::create_case_summary(output_str, collection_source.as_ref(), args.output_format.unwrap_or_default());
+
+    // Built last so case_info and the timeline are complete
+    if args.sqlite {
+        casedb::build(&output_dir.join("refined"));
+    }
+    let timesketch_file = if args.timesketch || args.timesketch_upload {
+        timesketch::export(&output_dir.join("refined"))
+    } else {
+        None
+    };
     
     // ID used for ZIP naming
     compressor::create_packages(output_str, &incident_id);
 
+    // Packages are still written so nothing collected is lost, but a broken refinement isn't uploaded
+    if let Err(message) = report::check_limit(refinement_errors, args.max_errors) {
+        ui::error(&message);
+        std::process::exit(2);
+    }
+
+    if args.timesketch_upload
+        && let Some(path) = &timesketch_file
+        && let Err(e) = timesketch::upload(path, &incident_id)
+    {
+        ui::error(&format!("Timesketch upload failed: {}", e));
+    }
+
 
 
     ui::warn("Do you want to upload the refined data to the server? (y/N):\n");
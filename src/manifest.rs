@@ -1,27 +1,36 @@
 use chrono::Local;
+use clap::ValueEnum;
 use std::fs;
 use std::path::Path;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::env;
 
+use crate::cli::OutputFormat;
 use crate::ui;
 
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub fn create_case_summary(output_dir: &str) -> String {
+/// `source` describes imported collections (KAPE, ...), None for a collection made by TraceNexus itself.
+pub fn create_case_summary(output_dir: &str, source: Option<&Value>, output_format: OutputFormat) -> String {
     let output_path = Path::new(output_dir);
     let refined_path = output_path.join("refined");
 
     // 1. Get system information from environment variables
-    let hostname = env::var("COMPUTERNAME").unwrap_or_else(|_| "Unknown-Host".to_string());
-    let username = env::var("USERNAME").unwrap_or_else(|_| "Unknown-User".to_string());
+    // COMPUTERNAME/USERNAME on Windows, /etc/hostname and USER on Linux
+    let hostname = env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok().map(|h| h.trim().to_string()))
+        .unwrap_or_else(|| "Unknown-Host".to_string());
+    let username = env::var("USERNAME")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| "Unknown-User".to_string());
     
     // 2. Get current timestamp
     let now = Local::now();
     let incident_id = format!("INC-{}", now.format("%Y%m%d-%H%M%S"));
 
     // 3. Create Case Summary JSON
-    let summary = json!({
+    let mut summary = json!({
         "case_id": incident_id,
         "generated_at": now.to_rfc3339(),
         "system": {
@@ -32,8 +41,13 @@ pub fn create_case_summary(output_dir: &str) -> String {
         "collector": {
             "name": "TraceNexus",
             "version": APP_VERSION
-        }
+        },
+        // Layout of the refined files and master_timeline.json: "json" (array) or "ndjson" (one record per line)
+        "output_format": format_name(output_format),
     });
+    if let Some(source) = source {
+        summary["source"] = source.clone();
+    }
 
     // 4. Save JSON to refined directory
     let summary_path = refined_path.join("case_summary.json");
@@ -41,4 +55,14 @@ pub fn create_case_summary(output_dir: &str) -> String {
 
     ui::success(&format!("[+] Case Summary created for {} (User: {})", hostname, username));
     incident_id
-}
\ No newline at end of file
+}
+
+fn format_name(output_format: OutputFormat) -> String {
+    output_format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
+
+/// Format recorded in an existing case summary, None for cases from before the option.
+pub fn case_output_format(refined_path: &Path) -> Option<OutputFormat> {
+    let summary: Value = serde_json::from_str(&fs::read_to_string(refined_path.join("case_summary.json")).ok()?).ok()?;
+    OutputFormat::from_str(summary["output_format"].as_str()?, true).ok()
+}
@@ -0,0 +1,231 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::cli::EventSchema;
+use crate::refiner;
+
+// Shipped mappings, rules/<schema>.json in the working directory replaces them and
+// every rules/<schema>.d/*.json adds sources (checked before the main file)
+const ECS_MAPPING: &str = include_str!("../rules/ecs.json");
+const OCSF_MAPPING: &str = include_str!("../rules/ocsf.json");
+const RULES_DIR: &str = "rules";
+
+/// Normalized records keep the tool's own fields under this key
+pub const ORIGINAL_KEY: &str = "original";
+
+// "event.code:string" converts the value before it is set, the schema's type and the tool's differ
+const CONVERSIONS: &[&str] = &["string", "integer", "array", "epoch_ms"];
+
+#[derive(Deserialize)]
+struct MappingFile {
+    schema: Option<String>,
+    #[serde(default)]
+    constants: BTreeMap<String, Value>,
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+    #[serde(default)]
+    sources: Vec<SourceMapping>,
+}
+
+/// Tool field -> schema field ("EventId" -> "event.code") for file names containing `source`.
+#[derive(Deserialize)]
+pub struct SourceMapping {
+    pub source: String,
+    #[serde(default)]
+    pub constants: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+pub struct Normalizer {
+    pub schema: String,
+    // Set on every normalized record, e.g. ecs.version
+    constants: BTreeMap<String, Value>,
+    // Fields every source can have (UserName/UserSid from the profile tagging)
+    fields: BTreeMap<String, String>,
+    sources: Vec<SourceMapping>,
+}
+
+/// The mapping for one refined file.
+pub struct SourceNormalizer<'a> {
+    normalizer: &'a Normalizer,
+    mapping: &'a SourceMapping,
+}
+
+fn parse(origin: &str, content: &str) -> Result<MappingFile, String> {
+    let mapping: MappingFile = serde_json::from_str(content).map_err(|e| format!("{}: {}", origin, e))?;
+    let targets = mapping.fields.values().chain(mapping.sources.iter().flat_map(|s| s.fields.values()));
+    for target in targets {
+        if let Some((_, conversion)) = target.split_once(':')
+            && !CONVERSIONS.contains(&conversion)
+        {
+            return Err(format!("{}: unknown conversion '{}' in '{}'", origin, conversion, target));
+        }
+    }
+    Ok(mapping)
+}
+
+pub fn load(schema: &EventSchema) -> Result<Normalizer, String> {
+    let (name, built_in) = match schema {
+        EventSchema::Ecs => ("ecs", ECS_MAPPING),
+        EventSchema::Ocsf => ("ocsf", OCSF_MAPPING),
+    };
+    let main_path = Path::new(RULES_DIR).join(format!("{}.json", name));
+    let main = match fs::read_to_string(&main_path) {
+        Ok(content) => parse(&main_path.display().to_string(), &content)?,
+        Err(_) => parse("built-in", built_in)?,
+    };
+
+    // Extensions first so they can override a shipped source
+    let mut sources = Vec::new();
+    let mut extensions: Vec<_> = fs::read_dir(Path::new(RULES_DIR).join(format!("{}.d", name)))
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    extensions.sort();
+    for path in extensions.iter().filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json")) {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        sources.extend(parse(&path.display().to_string(), &content)?.sources);
+    }
+    sources.extend(main.sources);
+
+    Ok(Normalizer {
+        schema: main.schema.unwrap_or_else(|| name.to_uppercase()),
+        constants: main.constants,
+        fields: main.fields,
+        sources,
+    })
+}
+
+/// The tool's own fields of a record, normalized or not.
+pub fn original(record: &Map<String, Value>) -> &Map<String, Value> {
+    record.get(ORIGINAL_KEY).and_then(|v| v.as_object()).unwrap_or(record)
+}
+
+// "process.parent.pid" -> {"process": {"parent": {"pid": ...}}}. The first value for a field wins.
+fn set_path(target: &mut Map<String, Value>, path: &str, value: Value) {
+    let mut current = target;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            current.entry(part.to_string()).or_insert(value);
+            return;
+        }
+        let next = current.entry(part.to_string()).or_insert_with(|| Value::Object(Map::new()));
+        let Some(next) = next.as_object_mut() else {
+            return;
+        };
+        current = next;
+    }
+}
+
+fn convert(value: &Value, conversion: &str) -> Option<Value> {
+    match conversion {
+        "string" => Some(match value {
+            Value::String(_) => value.clone(),
+            other => Value::String(other.to_string()),
+        }),
+        "integer" => match value {
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(value.clone()),
+            Value::String(s) => s.trim().parse::<i64>().ok().map(Value::from),
+            _ => None,
+        },
+        "array" => Some(match value {
+            Value::Array(_) => value.clone(),
+            other => Value::Array(vec![other.clone()]),
+        }),
+        // Unparseable times are left out, the original record still has them
+        "epoch_ms" => match value {
+            Value::Number(_) => Some(value.clone()),
+            Value::String(s) => refiner::parse_instant(s).map(|t| Value::from(t.timestamp_millis())),
+            _ => None,
+        },
+        _ => Some(value.clone()),
+    }
+}
+
+impl Normalizer {
+    /// None for sources without a mapping, those stay as they are.
+    pub fn for_file(&self, file_name: &str) -> Option<SourceNormalizer<'_>> {
+        let file_name = file_name.to_lowercase();
+        self.sources.iter()
+            .find(|m| file_name.contains(&m.source.to_lowercase()))
+            .map(|mapping| SourceNormalizer { normalizer: self, mapping })
+    }
+}
+
+impl SourceNormalizer<'_> {
+    pub fn schema(&self) -> &str {
+        &self.normalizer.schema
+    }
+
+    pub fn record(&self, record: Map<String, Value>) -> Value {
+        let mut normalized = Map::new();
+        let fields = self.mapping.fields.iter().chain(self.normalizer.fields.iter());
+        for (field, target) in fields {
+            let (target, conversion) = target.split_once(':').unwrap_or((target, ""));
+            if let Some(value) = record.get(field).filter(|v| !v.is_null() && v.as_str() != Some(""))
+                && let Some(value) = convert(value, conversion)
+            {
+                set_path(&mut normalized, target, value);
+            }
+        }
+        for (path, value) in self.mapping.constants.iter().chain(self.normalizer.constants.iter()) {
+            set_path(&mut normalized, path, value.clone());
+        }
+        normalized.insert(ORIGINAL_KEY.to_string(), Value::Object(record));
+        Value::Object(normalized)
+    }
+
+    pub fn records(&self, records: Vec<Value>) -> Vec<Value> {
+        records.into_iter()
+            .map(|record| match record {
+                Value::Object(map) => self.record(map),
+                other => other,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn normalize(schema: EventSchema, file_name: &str, record: Value) -> Value {
+        let normalizer = load(&schema).unwrap();
+        let Value::Object(record) = record else { unreachable!() };
+        normalizer.for_file(file_name).unwrap().record(record)
+    }
+
+    #[test]
+    fn ecs_field_types() {
+        let event = normalize(EventSchema::Ecs, "EvtxECmd_Output.json", json!({"EventId": 4624, "TimeCreated": "2024-01-01T00:00:00+00:00"}));
+        assert_eq!(event["event"]["code"], "4624");
+        let event = normalize(EventSchema::Ecs, "RECmd_Batch_Output.json", json!({"ValueData": "C:\\evil.exe"}));
+        assert_eq!(event["registry"]["data"]["strings"], json!(["C:\\evil.exe"]));
+        let event = normalize(EventSchema::Ecs, "Linux_AuthLog.json", json!({"Pid": "812"}));
+        assert_eq!(event["process"]["pid"], 812);
+    }
+
+    #[test]
+    fn ocsf_required_attributes() {
+        let mapping: MappingFile = serde_json::from_str(OCSF_MAPPING).unwrap();
+        for source in &mapping.sources {
+            let event = normalize(EventSchema::Ocsf, &format!("{}.json", source.source), json!({}));
+            for required in ["class_uid", "category_uid", "activity_id", "type_uid", "severity_id"] {
+                assert!(event[required].is_i64(), "{} lacks {}", source.source, required);
+            }
+            let class_uid = event["class_uid"].as_i64().unwrap();
+            assert_eq!(event["type_uid"].as_i64().unwrap(), class_uid * 100 + event["activity_id"].as_i64().unwrap());
+        }
+        let event = normalize(EventSchema::Ocsf, "Linux_Wtmp.json", json!({"LoginTime": "2024-01-01T00:00:01.5+00:00"}));
+        assert_eq!(event["time"], 1_704_067_201_500i64);
+    }
+
+    #[test]
+    fn unknown_conversion() {
+        assert!(parse("test", r#"{"fields": {"EventId": "event.code:hex"}}"#).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use arrow_array::builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, TimestampMicrosecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema as ArrowSchema, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+use crate::refiner;
+use crate::schema::{self, ColumnType};
+use crate::ui;
+
+// refined/parquet/<category>/<source>.parquet and refined/parquet/master_timeline.parquet
+pub const PARQUET_DIR: &str = "parquet";
+
+// MFT outputs run into millions of rows, readers can skip and parallelize per group
+const ROW_GROUP_SIZE: usize = 100_000;
+
+// Timeline columns with a fixed type, the rest is inferred. Unparseable "ts" values are null, "ts_raw" keeps them.
+const TIMELINE_COLUMNS: &[(&str, ColumnType)] = &[
+    ("ts", ColumnType::Timestamp),
+    ("suspicious_time", ColumnType::Boolean),
+];
+
+/// Writes a Parquet copy of the master timeline and every refined source next to the JSON.
+pub fn export(refined_path: &Path) {
+    ui::info("Writing Parquet files...");
+    let parquet_path = refined_path.join(PARQUET_DIR);
+    let _ = fs::remove_dir_all(&parquet_path);
+    let schemas = schema::load_schemas(Path::new(schema::SCHEMA_DIR));
+
+    let mut written = 0;
+    for entry in WalkDir::new(refined_path).into_iter().filter_map(|e| e.ok()).filter(|e| e.path().is_file()) {
+        let path = entry.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        if !file_name.ends_with(".json") || path.starts_with(&parquet_path) {
+            continue;
+        }
+        let pinned: HashMap<String, ColumnType> = if file_name == "master_timeline.json" {
+            TIMELINE_COLUMNS.iter().map(|(c, t)| (c.to_string(), *t)).collect()
+        } else if refiner::is_case_file(&file_name) {
+            continue;
+        } else {
+            schema::find(&schemas, &file_name).map(|s| s.columns.clone()).unwrap_or_default()
+        };
+
+        let relative = path.strip_prefix(refined_path).unwrap_or(path);
+        let target = parquet_path.join(relative).with_extension("parquet");
+        let records = refiner::read_json_records(path);
+        // Parquet can't hold a file without columns, a source without records has no copy
+        if records.iter().all(|r| r.as_object().is_none_or(|o| o.is_empty())) {
+            continue;
+        }
+        let result = fs::create_dir_all(target.parent().unwrap())
+            .map_err(|e| e.into())
+            .and_then(|_| write_parquet(&target, &records, &pinned));
+        match result {
+            Ok(()) => written += 1,
+            Err(e) => ui::warn(&format!("Parquet export of {} failed: {}", file_name, e)),
+        }
+    }
+    ui::success(&format!("Wrote {} Parquet files to refined/{}", written, PARQUET_DIR));
+}
+
+// Narrowest type all non-null values of a column fit into. Strings are timestamps if every one parses.
+fn infer(records: &[Value], column: &str) -> ColumnType {
+    // <field>_Raw and ts_raw are what the source wrote, often local time, so they stay text
+    if column.ends_with("_Raw") || column == "ts_raw" {
+        return ColumnType::String;
+    }
+    let mut candidates = vec![ColumnType::Integer, ColumnType::Float, ColumnType::Boolean, ColumnType::Timestamp];
+    for value in records.iter().filter_map(|r| r.get(column)).filter(|v| !v.is_null()) {
+        candidates.retain(|t| match t {
+            ColumnType::Integer => value.is_i64(),
+            ColumnType::Float => value.is_number(),
+            ColumnType::Boolean => value.is_boolean(),
+            ColumnType::Timestamp => value.as_str().is_some_and(|s| refiner::parse_instant(s).is_some()),
+            ColumnType::String => true,
+        });
+        if candidates.is_empty() {
+            return ColumnType::String;
+        }
+    }
+    // A column without any value is kept as text
+    if candidates.len() == 4 { ColumnType::String } else { candidates[0] }
+}
+
+fn data_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::String => DataType::Utf8,
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+    }
+}
+
+fn write_parquet(target: &Path, records: &[Value], pinned: &HashMap<String, ColumnType>) -> Result<(), Box<dyn std::error::Error>> {
+    // Columns in the order they first show up
+    let mut columns: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for record in records.iter().filter_map(|r| r.as_object()) {
+        for key in record.keys() {
+            if seen.insert(key.as_str()) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    let types: Vec<ColumnType> = columns.iter()
+        .map(|c| pinned.get(c).copied().unwrap_or_else(|| infer(records, c)))
+        .collect();
+    let schema = Arc::new(ArrowSchema::new(
+        columns.iter().zip(&types).map(|(c, t)| Field::new(c, data_type(*t), true)).collect::<Vec<_>>(),
+    ));
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(ROW_GROUP_SIZE)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(target)?, schema.clone(), Some(properties))?;
+    for chunk in records.chunks(ROW_GROUP_SIZE) {
+        let arrays: Vec<ArrayRef> = columns.iter().zip(&types)
+            .map(|(column, column_type)| build_array(chunk, column, *column_type))
+            .collect();
+        writer.write(&RecordBatch::try_new(schema.clone(), arrays)?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+// Values that don't fit a pinned type become null, nested values are written as JSON text
+fn build_array(records: &[Value], column: &str, column_type: ColumnType) -> ArrayRef {
+    let values = records.iter().map(|r| r.get(column).filter(|v| !v.is_null()));
+    match column_type {
+        ColumnType::Integer => {
+            let mut builder = Int64Builder::new();
+            values.for_each(|v| builder.append_option(v.and_then(|v| v.as_i64().or_else(|| v.as_str()?.parse().ok()))));
+            Arc::new(builder.finish())
+        },
+        ColumnType::Float => {
+            let mut builder = Float64Builder::new();
+            values.for_each(|v| builder.append_option(v.and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()))));
+            Arc::new(builder.finish())
+        },
+        ColumnType::Boolean => {
+            let mut builder = BooleanBuilder::new();
+            values.for_each(|v| builder.append_option(v.and_then(|v| v.as_bool().or_else(|| v.as_str()?.to_lowercase().parse().ok()))));
+            Arc::new(builder.finish())
+        },
+        ColumnType::Timestamp => {
+            let mut builder = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+            values.for_each(|v| builder.append_option(v.and_then(|v| refiner::parse_instant(v.as_str()?)).map(|t| t.timestamp_micros())));
+            Arc::new(builder.finish())
+        },
+        ColumnType::String => {
+            let mut builder = StringBuilder::new();
+            values.for_each(|v| builder.append_option(v.map(|v| match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })));
+            Arc::new(builder.finish())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, TimestampMicrosecondArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn inferred_column_types() {
+        let records = vec![
+            json!({"Pid": 4, "Score": 1, "Enabled": true, "Created": "2024-03-01 10:00:00", "Created_Raw": "2024-03-01 10:00:00", "Empty": null, "Mixed": 1}),
+            json!({"Pid": 8, "Score": 1.5, "Enabled": false, "Created": "2024-03-01T10:00:00Z", "Created_Raw": "2024-03-01T10:00:00Z", "Empty": null, "Mixed": "one"}),
+            json!({"Pid": null}),
+        ];
+        let types: Vec<ColumnType> = ["Pid", "Score", "Enabled", "Created", "Created_Raw", "Empty", "Mixed", "Missing"]
+            .iter()
+            .map(|c| infer(&records, c))
+            .collect();
+        assert_eq!(types, vec![
+            ColumnType::Integer,
+            ColumnType::Float,
+            ColumnType::Boolean,
+            ColumnType::Timestamp,
+            // what the source wrote stays text, even if it parses
+            ColumnType::String,
+            ColumnType::String,
+            ColumnType::String,
+            ColumnType::String,
+        ]);
+    }
+
+    #[test]
+    fn timeline_round_trip_and_empty_sources() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-parquet-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Logs")).unwrap();
+        fs::write(dir.join("master_timeline.json"), json!([
+            {"ts": "2024-03-01T10:00:00.123456Z", "ts_raw": "2024-03-01 10:00:00.123456", "suspicious_time": true, "cat": "Logs"},
+            {"ts": "garbage", "ts_raw": "garbage", "cat": "Logs"},
+        ]).to_string()).unwrap();
+        fs::write(dir.join("Logs").join("Empty.json"), "[]").unwrap();
+        fs::write(dir.join("Logs").join("Blank.json"), "[{}]").unwrap();
+
+        export(&dir);
+        let parquet = dir.join(PARQUET_DIR);
+        assert!(!parquet.join("Logs").join("Empty.parquet").exists());
+        assert!(!parquet.join("Logs").join("Blank.parquet").exists());
+
+        let file = File::open(parquet.join("master_timeline.parquet")).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap().next().unwrap().unwrap();
+        let schema = batch.schema();
+        assert_eq!(schema.field_with_name("ts").unwrap().data_type(), &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())));
+        assert_eq!(schema.field_with_name("ts_raw").unwrap().data_type(), &DataType::Utf8);
+        assert_eq!(schema.field_with_name("suspicious_time").unwrap().data_type(), &DataType::Boolean);
+
+        let ts = batch.column_by_name("ts").unwrap().as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+        assert_eq!(ts.value(0), 1_709_287_200_123_456);
+        // unparseable times are null, ts_raw still has them
+        assert!(ts.is_null(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
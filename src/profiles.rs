@@ -1,9 +1,27 @@
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use chrono_tz::Tz;
 use crate::ui;
+use crate::tools;
+use crate::users;
+use crate::recyclebin;
+use crate::browser;
+use crate::linux;
+use crate::containers;
+use crate::timezone;
 use colored::*;
 
+// Live collection reads straight from the system drive
+const LIVE_ROOT: &str = "C:\\";
+
+/// Builds the path of an artifact below the system root: C:\ on a live host,
+/// the volume extracted from a disk image otherwise.
+fn system_path(system_root: Option<&Path>, relative: &str) -> PathBuf {
+    system_root
+        .unwrap_or(Path::new(LIVE_ROOT))
+        .join(relative.replace('/', std::path::MAIN_SEPARATOR_STR))
+}
 
 /// Runs an external forensic tool and logs stdout/stderr.
 fn run_command(name: &str, executable: &str, args: Vec<String>, out_dir: &str) {
@@ -21,8 +39,10 @@ fn run_command(name: &str, executable: &str, args: Vec<String>, out_dir: &str) {
     let working_dir = full_exe_path.parent().unwrap_or(Path::new("."));
 
     // 3. Execute the command
-    let output = std::process::Command::new(&full_exe_path)
-        .args(&args)
+    let (program, mut launch_args) = tools::launcher(&full_exe_path);
+    launch_args.extend(args);
+    let output = std::process::Command::new(&program)
+        .args(&launch_args)
         .current_dir(working_dir)
         .output();
 
@@ -49,7 +69,8 @@ fn run_command(name: &str, executable: &str, args: Vec<String>, out_dir: &str) {
 }
 
 /// Collects lightweight forensic artifacts for quick triage.
-pub fn run_light(out_dir: &str) {
+/// `system_root` points to a volume extracted from a disk image, None means the live system.
+pub fn run_light(out_dir: &str, system_root: Option<&Path>) {
     println!("{}", "\n--- [ PHASE: LIGHT COLLECTION ] ---\n".bright_cyan().bold());
 
     // Amcache: executed programs and installed software (CSV)
@@ -57,7 +78,7 @@ pub fn run_light(out_dir: &str) {
         "AmcacheParser",
         "tools/AmcacheParser.exe",
         vec![
-            "-f".into(), "C:\\Windows\\AppCompat\\Programs\\Amcache.hve".into(),
+            "-f".into(), system_path(system_root, "Windows/AppCompat/Programs/Amcache.hve").to_string_lossy().to_string(),
             "--csv".into(), out_dir.into(),
             "--mp".into(), 
         ],
@@ -65,12 +86,15 @@ pub fn run_light(out_dir: &str) {
     );
 
     // ShimCache: Binary execution artifacts (CSV)
+    // Live it reads the running SYSTEM hive, offline it needs the hive file
+    let mut shimcache_args = vec!["--csv".to_string(), out_dir.to_string()];
+    if system_root.is_some() {
+        shimcache_args.extend(["-f".to_string(), system_path(system_root, "Windows/System32/config/SYSTEM").to_string_lossy().to_string()]);
+    }
     run_command(
         "ShimCacheParser",
         "tools/AppCompatCacheParser.exe",
-        vec![
-            "--csv".into(), out_dir.into(),
-        ],
+        shimcache_args,
         out_dir
     );
 
@@ -79,30 +103,61 @@ pub fn run_light(out_dir: &str) {
         "EvtxECmd",
         "tools/EvtxECmd/EvtxECmd.exe",
         vec![
-            "-d".into(), "C:\\Windows\\System32\\winevt\\Logs".into(), 
+            "-d".into(), system_path(system_root, "Windows/System32/winevt/Logs").to_string_lossy().to_string(), 
             "--json".into(), out_dir.into()
         ],
         out_dir
     );
 }
 
+/// Collects Linux artifacts natively: logs, logins, shell histories, persistence and package changes.
+pub fn run_linux(out_dir: &str, source_root: Option<&str>, journal_dir: Option<&str>, source_tz: Option<Tz>) {
+    println!("{}", "\n--- [ PHASE: LINUX COLLECTION ] ---\n".bright_cyan().bold());
+    let root = Path::new(source_root.unwrap_or("/"));
+    // Syslog and package logs are written in local time, converted with the root's own zone
+    let zone = source_tz.or_else(|| timezone::linux_zone(root));
+    if zone.is_none() {
+        ui::warn("No time zone configured in the source root, local log times are treated as UTC (see --source-tz)");
+    }
+    linux::collect(root, out_dir, journal_dir.map(Path::new), zone);
+
+    // Docker / containerd hosts: container metadata, upper-dir changes and logs
+    containers::collect(root, out_dir);
+}
+
 /// Collects comprehensive forensic artifacts for deep analysis.
-pub fn run_full(out_dir: &str) {
+pub fn run_full(out_dir: &str, system_root: Option<&Path>) {
     // Light analysis first
-    run_light(out_dir);
+    run_light(out_dir, system_root);
 
     println!("{}", "\n--- [ PHASE: FULL DEEP DIVE ] ---\n".bright_cyan().bold());
     // MFT: Master File Table analysis (JSON)
+    let mft_path = system_path(system_root, "$MFT").to_string_lossy().to_string();
     run_command(
         "MFTECmd",
         "tools/MFTECmd.exe",
         vec![
-            "-f".into(), "C:\\$MFT".into(), 
+            "-f".into(), mft_path.clone(), 
             "--json".into(), out_dir.into()
         ],
         out_dir
     );
 
+    // USN journal: only reachable as a file when it was extracted from an image
+    let usn_path = system_path(system_root, "$Extend/$J");
+    if system_root.is_some() && usn_path.exists() {
+        run_command(
+            "MFTECmd_UsnJrnl",
+            "tools/MFTECmd.exe",
+            vec![
+                "-f".into(), usn_path.to_string_lossy().to_string(),
+                "-m".into(), mft_path,
+                "--json".into(), out_dir.into()
+            ],
+            out_dir
+        );
+    }
+
     // 2. RECmd with Path to Expert Batch File
     let relative_batch_path = "tools/RECmd/BatchExamples/DFIRBatch.reb";
     
@@ -118,10 +173,151 @@ pub fn run_full(out_dir: &str) {
         "RECmd_Expert_Batch",
         "tools/RECmd/RECmd.exe",
         vec![
-            "-d".into(), "C:\\Windows\\System32\\config".into(),
-            "--bn".into(), absolute_batch_path, // now absolute path
+            "-d".into(), system_path(system_root, "Windows/System32/config").to_string_lossy().to_string(),
+            "--bn".into(), absolute_batch_path.clone(), // now absolute path
             "--csv".into(), out_dir.into()
         ],
         out_dir
     );
-}
\ No newline at end of file
+
+    // 3. Per-user artifacts: hives (NTUSER.DAT and UsrClass.dat) and file access history
+    let mut profiles = users::enumerate_profiles(system_root);
+    if system_root.is_some() {
+        users::resolve_offline_sids(out_dir, &mut profiles);
+    }
+    ui::info(&format!("Found {} user profiles with registry hives", profiles.len()));
+
+    collect_user_hives(out_dir, &absolute_batch_path, &profiles);
+    collect_file_access(out_dir, &profiles);
+
+    // 4. Recycle Bin ($I files), SIDs resolved through the ProfileList rows RECmd just produced
+    recyclebin::collect(out_dir, system_root.unwrap_or(Path::new(LIVE_ROOT)), &profiles);
+
+    // 5. SRUM: per-application network and resource usage
+    collect_srum(out_dir, system_root);
+
+    // 6. Browser history (Chrome, Edge, Firefox)
+    browser::collect(out_dir, &profiles);
+
+    // Refiner uses this to tag every per-user record with username and SID
+    users::save_profiles(out_dir, &profiles);
+}
+
+/// Runs RECmd against every user's NTUSER.DAT and UsrClass.dat.
+/// RECmd replays the .LOG1/.LOG2 transaction logs next to each hive on its own.
+fn collect_user_hives(out_dir: &str, batch_path: &str, profiles: &[users::UserProfile]) {
+    for profile in profiles {
+        let id = profile.file_id();
+
+        for (hive_name, hive_path) in [("NTUSER", profile.ntuser_hive()), ("UsrClass", profile.usrclass_hive())] {
+            if !hive_path.exists() {
+                continue;
+            }
+
+            run_command(
+                &format!("RECmd_Batch_{}_{}", hive_name, id),
+                "tools/RECmd/RECmd.exe",
+                vec![
+                    "-f".into(), hive_path.to_string_lossy().to_string(),
+                    "--bn".into(), batch_path.to_string(),
+                    "--csv".into(), out_dir.into(),
+                    "--csvf".into(), format!("RECmd_Batch_{}_{}.csv", hive_name, id),
+                ],
+                out_dir
+            );
+        }
+    }
+}
+
+/// Parses each user's Recent folder: LNK files with LECmd, Automatic/CustomDestinations with JLECmd.
+/// Both keep target paths, MAC times, volume serial, machine ID and MAC address in their CSVs.
+fn collect_file_access(out_dir: &str, profiles: &[users::UserProfile]) {
+    for profile in profiles {
+        let recent_dir = profile.recent_dir();
+        if !recent_dir.exists() {
+            continue;
+        }
+        let id = profile.file_id();
+
+        run_command(
+            &format!("LECmd_{}", id),
+            "tools/LECmd.exe",
+            vec![
+                "-d".into(), recent_dir.to_string_lossy().to_string(),
+                "--csv".into(), out_dir.into(),
+                "--csvf".into(), format!("LECmd_{}.csv", id),
+                "-q".into(),
+            ],
+            out_dir
+        );
+
+        // JLECmd appends _AutomaticDestinations / _CustomDestinations to the file name
+        run_command(
+            &format!("JLECmd_{}", id),
+            "tools/JLECmd.exe",
+            vec![
+                "-d".into(), recent_dir.to_string_lossy().to_string(),
+                "--csv".into(), out_dir.into(),
+                "--csvf".into(), format!("JLECmd_{}.csv", id),
+                "-q".into(),
+            ],
+            out_dir
+        );
+    }
+}
+
+/// Copies SRUDB.dat and the SOFTWARE hive through a shadow copy (both are locked on a live system)
+/// and parses them with SrumECmd. The SOFTWARE hive lets SrumECmd resolve app IDs and SIDs.
+/// Files extracted from an image are not locked and are parsed in place.
+fn collect_srum(out_dir: &str, system_root: Option<&Path>) {
+    let srudb_source = system_path(system_root, "Windows/System32/sru/SRUDB.dat");
+    let software_source = system_path(system_root, "Windows/System32/config/SOFTWARE");
+
+    let (srudb_copy, software_copy) = if system_root.is_some() {
+        (srudb_source, software_source)
+    } else {
+        copy_srum_live(out_dir, &srudb_source, &software_source)
+    };
+
+    if !srudb_copy.exists() {
+        ui::error("SRUDB.dat not available, skipping SrumECmd.");
+        return;
+    }
+
+    // Produces NetworkUsages, AppResourceUseInfo, NetworkConnections and a few more CSVs
+    run_command(
+        "SrumECmd",
+        "tools/SrumECmd.exe",
+        vec![
+            "-f".into(), srudb_copy.to_string_lossy().to_string(),
+            "-r".into(), software_copy.to_string_lossy().to_string(),
+            "--csv".into(), out_dir.into(),
+        ],
+        out_dir
+    );
+}
+
+fn copy_srum_live(out_dir: &str, srudb_source: &Path, software_source: &Path) -> (PathBuf, PathBuf) {
+    let srum_dir = Path::new(out_dir).join("srum");
+    fs::create_dir_all(&srum_dir).ok();
+
+    let srudb_copy = srum_dir.join("SRUDB.dat");
+    let software_copy = srum_dir.join("SOFTWARE");
+
+    for (name, source, target) in [
+        ("SRUM_Copy", srudb_source, &srudb_copy),
+        ("SOFTWARE_Copy", software_source, &software_copy),
+    ] {
+        run_command(
+            name,
+            "C:\\Windows\\System32\\esentutl.exe",
+            vec![
+                "/y".into(), source.to_string_lossy().to_string(),
+                "/vss".into(),
+                "/d".into(), target.to_string_lossy().to_string(),
+            ],
+            out_dir
+        );
+    }
+    (srudb_copy, software_copy)
+}
@@ -0,0 +1,173 @@
+use chrono::DateTime;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::ui;
+use crate::users::{self, UserProfile};
+
+const RECYCLE_BIN: &str = "$Recycle.Bin";
+
+// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_DIFF: i64 = 11_644_473_600;
+
+struct RecycleRecord {
+    original_path: String,
+    file_size: u64,
+    deleted_on: String,
+}
+
+/// Parses all $I files below <system_root>\$Recycle.Bin and writes them as RecycleBin.json into the output directory.
+pub fn collect(out_dir: &str, system_root: &Path, profiles: &[UserProfile]) {
+    ui::info("Executing: RecycleBin ($I parser)");
+
+    let sid_map = resolve_sids(out_dir, profiles);
+    let mut records = Vec::new();
+
+    for entry in WalkDir::new(system_root.join(RECYCLE_BIN)).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy();
+        if !path.is_file() || !file_name.starts_with("$I") {
+            continue;
+        }
+
+        let Ok(data) = fs::read(path) else {
+            continue;
+        };
+
+        match parse_i_file(&data) {
+            Some(record) => {
+                // Layout is C:\$Recycle.Bin\<SID>\$Ixxxxxx.ext
+                let sid = path.parent()
+                    .and_then(|p| p.file_name())
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let user = sid_map.get(&sid).cloned().unwrap_or_default();
+
+                records.push(json!({
+                    "SourceFile": path.to_string_lossy(),
+                    "OriginalPath": record.original_path,
+                    "FileSize": record.file_size,
+                    "DeletedOn": record.deleted_on,
+                    "UserSid": sid,
+                    "UserName": user,
+                    "Event": "FileDeleted",
+                }));
+            },
+            None => ui::warn(&format!("Unknown $I format: {}", path.display())),
+        }
+    }
+
+    let count = records.len();
+    let target = Path::new(out_dir).join("RecycleBin.json");
+    let _ = fs::write(target, serde_json::to_string(&Value::Array(records)).unwrap());
+    ui::success(&format!("RecycleBin finished successfully. {} deleted files found.", count));
+}
+
+/// Parses a single $I record. Version 1 is Vista-8.1 (fixed 520 byte path), version 2 is Win10+.
+fn parse_i_file(data: &[u8]) -> Option<RecycleRecord> {
+    if data.len() < 24 {
+        return None;
+    }
+
+    let version = u64::from_le_bytes(data[0..8].try_into().ok()?);
+    let file_size = u64::from_le_bytes(data[8..16].try_into().ok()?);
+    let filetime = i64::from_le_bytes(data[16..24].try_into().ok()?);
+
+    let name_bytes = match version {
+        1 => data.get(24..24 + 520)?,
+        2 => {
+            let name_len = u32::from_le_bytes(data.get(24..28)?.try_into().ok()?) as usize;
+            data.get(28..28 + name_len * 2)?
+        },
+        _ => return None,
+    };
+
+    let utf16: Vec<u16> = name_bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+
+    Some(RecycleRecord {
+        original_path: String::from_utf16_lossy(&utf16),
+        file_size,
+        deleted_on: filetime_to_rfc3339(filetime),
+    })
+}
+
+fn filetime_to_rfc3339(filetime: i64) -> String {
+    // Euclidean so a negative (corrupt) value doesn't turn into a negative nanosecond part,
+    // anything chrono can't represent ends up empty
+    let secs = filetime.div_euclid(10_000_000) - FILETIME_UNIX_DIFF;
+    let nanos = filetime.rem_euclid(10_000_000) as u32 * 100;
+    DateTime::from_timestamp(secs, nanos)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Builds a SID -> username map. The ProfileList rows in the RECmd batch output come first,
+/// the live profile enumeration fills the gaps.
+fn resolve_sids(out_dir: &str, profiles: &[UserProfile]) -> HashMap<String, String> {
+    let mut map: HashMap<String, String> = profiles.iter()
+        .filter(|p| !p.sid.is_empty())
+        .map(|p| (p.sid.clone(), p.username.clone()))
+        .collect();
+
+    map.extend(users::profile_list_sids(out_dir));
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2023-11-14T22:13:20.1234567Z
+    const DELETED: i64 = (1_700_000_000 + FILETIME_UNIX_DIFF) * 10_000_000 + 1_234_567;
+
+    fn header(version: u64, file_size: u64, filetime: i64) -> Vec<u8> {
+        [version.to_le_bytes(), file_size.to_le_bytes(), filetime.to_le_bytes()].concat()
+    }
+
+    fn utf16(path: &str) -> Vec<u8> {
+        path.encode_utf16().chain([0]).flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn parses_v1_record() {
+        let mut data = header(1, 4096, DELETED);
+        let mut name = utf16("C:\\Users\\bob\\secret.txt");
+        name.resize(520, 0);
+        data.extend(name);
+
+        let record = parse_i_file(&data).unwrap();
+        assert_eq!(record.original_path, "C:\\Users\\bob\\secret.txt");
+        assert_eq!(record.file_size, 4096);
+        assert_eq!(record.deleted_on, "2023-11-14T22:13:20.123456700+00:00");
+        // The fixed path field is cut off
+        assert!(parse_i_file(&data[..300]).is_none());
+    }
+
+    #[test]
+    fn parses_v2_record() {
+        let name = utf16("D:\\tools\\mimikatz.exe");
+        let mut data = header(2, 1_250_000, DELETED);
+        data.extend(((name.len() / 2) as u32).to_le_bytes());
+        data.extend(&name);
+
+        let record = parse_i_file(&data).unwrap();
+        assert_eq!(record.original_path, "D:\\tools\\mimikatz.exe");
+        assert_eq!(record.file_size, 1_250_000);
+        assert_eq!(record.deleted_on, "2023-11-14T22:13:20.123456700+00:00");
+        assert!(parse_i_file(&data[..data.len() - 2]).is_none());
+        assert!(parse_i_file(&header(3, 0, DELETED)).is_none());
+    }
+
+    #[test]
+    fn out_of_range_filetimes() {
+        assert_eq!(filetime_to_rfc3339(-1), "1600-12-31T23:59:59.999999900+00:00");
+        assert!(filetime_to_rfc3339(i64::MIN).starts_with("-27627-"));
+        assert_eq!(filetime_to_rfc3339(i64::MAX), "+30828-09-14T02:48:05.477580700+00:00");
+    }
+}
@@ -1,57 +1,51 @@
-use std::fs::{self, File};
-use std::path::{Path};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
-use serde_json::{Value, Map};
-use chrono::NaiveDateTime;
+use serde_json::{json, Value, Map};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono::Datelike;
-use csv::ReaderBuilder;
+use chrono_tz::Tz;
+use crate::cli::OutputFormat;
+use crate::dialect;
+use crate::mactime;
+use crate::parquet_export;
+use crate::normalize::{self, Normalizer, SourceNormalizer};
+use crate::report::{self, FileReport};
+use crate::rules::Rules;
+use crate::schema::{self, ColumnType, Schema};
+use crate::timezone;
 use crate::ui;
+use crate::users::{self, UserProfile};
 
-const CATEGORIES: &[(&str, &str)] = &[
-    // --- EXECUTION ---
-    ("Amcache", "Execution"),
-    ("AppCompat", "Execution"),
-    ("UserAssist", "Execution"),
-    ("BamDam", "Execution"),
-    ("RADAR", "Execution"),
-    ("RecentApps", "Execution"),
-    ("Prefetch", "Execution"),
-    // --- PERSISTENCE ---
-    ("Run", "Persistence"),
-    ("Services", "Persistence"),
-    ("TaskCache", "Persistence"),
-    ("FirewallRules", "Persistence"),
-    ("AppPaths", "Persistence"),
-    ("ActiveSetup", "Persistence"),
-    // --- NETWORKING ---
-    ("Tcpip", "Networking"),
-    ("KnownNetworks", "Networking"),
-    ("NetworkAdapters", "Networking"),
-    ("NetworkSetup2", "Networking"),
-    ("Wifi", "Networking"),
-    // --- DEVICES ---
-    ("USB", "Devices"),
-    ("SCSI", "Devices"),
-    ("MountedDevices", "Devices"),
-    ("DeviceClasses", "Devices"),
-    // --- USERS & SOFTWARE ---
-    ("UserAccounts", "Users"),
-    ("SAMBuiltin", "Users"),
-    ("ProfileList", "Users"),
-    ("Products", "Software"),
-    ("Uninstall", "Software"), 
-    // --- SYSTEM & REPORTS ---
-    ("RECmd_Batch", "System"), 
-    ("TimeZoneInfo", "System"),
-    ("MFTECmd", "FileSystem"),
-    ("VolumeInfoCache", "FileSystem"),
-    ("EvtxECmd", "Logs"),
-    ("ETW", "Logs"),
-];
-
-// Main function to run the refinement process
-pub fn run_refinement(output_dir: &str) {
+pub struct RefineOptions<'a> {
+    // Source host's zone (None = UTC) and where it came from
+    pub zone: Option<Tz>,
+    pub zone_origin: &'a str,
+    // Every CSV cell stays a string, like before typed conversion
+    pub strings_only: bool,
+    // Category, local time and timeline field rules (rules/categories.json)
+    pub rules: &'a Rules,
+    // ECS/OCSF mapping, None keeps the tool's own field names
+    pub normalizer: Option<&'a Normalizer>,
+    pub output_format: OutputFormat,
+}
+
+/// Records of one refined file that made it into the master timeline
+#[derive(Default)]
+pub struct TimelineStats {
+    pub records: usize,
+    pub in_timeline: usize,
+    pub without_timestamp: usize,
+    pub error: Option<String>,
+}
+
+// Main function to run the refinement process, returns the number of errors in the refinement report
+pub fn run_refinement(output_dir: &str, options: &RefineOptions) -> usize {
+    let RefineOptions { zone, zone_origin, strings_only, rules, normalizer, output_format } = *options;
     ui::info("Refining data and cleaning up workspace...");
+    ui::info(&format!("Source time zone: {} ({})", zone.map(|z| z.name()).unwrap_or("UTC"), zone_origin));
     
     let base_path = Path::new(output_dir);
     let refined_path = base_path.join("refined");
@@ -80,25 +74,83 @@ pub fn run_refinement(output_dir: &str) {
         }
     }
 
+    // Written by the collection phase, used to tag records from per-user hives
+    let profiles = users::load_profiles(&refined_path);
+    let schemas = schema::load_schemas(Path::new(schema::SCHEMA_DIR));
+    let mut time_bases = Map::new();
+    let mut reports = Vec::new();
+
     for path in files_to_process {
         let file_name = path.file_name().unwrap().to_string_lossy().to_string();
-        let category = CATEGORIES.iter()
-            .find(|(key, _)| file_name.to_lowercase().contains(&key.to_lowercase()))
-            .map(|(_, cat)| *cat)
-            .unwrap_or("Other");
+        let category = rules.category(&file_name);
 
         let target_dir = refined_path.join(category);
         fs::create_dir_all(&target_dir).ok();
 
-        process_file(&path, &target_dir);
+        // Only sources the rules mark as local time are shifted, everything else already is UTC.
+        // The Linux collector converts its logs on its own, naive times in marked CSVs are converted here.
+        let is_local = rules.local_time(&file_name).is_some();
+        let source_zone = if is_local { zone } else { None };
+        if file_name.ends_with(".csv") || file_name.ends_with(".json") {
+            time_bases.insert(file_name.clone(), json!(if is_local { "local" } else { "UTC" }));
+        }
+
+        let conversion = Conversion {
+            zone: source_zone,
+            schema: schema::find(&schemas, &file_name),
+            strings_only,
+            normalizer: normalizer.and_then(|n| n.for_file(&file_name)),
+            output_format,
+        };
+        let mut file_report = FileReport {
+            file: file_name.clone(),
+            category: category.to_string(),
+            ..Default::default()
+        };
+        process_file(&path, &target_dir, &profiles, &conversion, &mut file_report);
 
         let destination_raw = raw_path.join(&file_name);
-        let _ = fs::rename(&path, destination_raw);
+        if let Err(e) = fs::rename(&path, destination_raw) {
+            file_report.error(format!("could not move to raw/: {}", e));
+        }
+        reports.push(file_report);
     }
 
+    let time_zone = json!({
+        "zone": zone.map(|z| z.name()).unwrap_or("UTC"),
+        "origin": zone_origin,
+        "sources": time_bases,
+    });
+    let _ = fs::write(refined_path.join(timezone::TIME_ZONE_FILE), serde_json::to_string(&time_zone).unwrap());
+
     // Create timeline after all files are processed
-    create_master_timeline(&refined_path);
+    let mut timeline_stats = create_master_timeline(&refined_path, rules, output_format);
+    for file_report in reports.iter_mut() {
+        let Some(stats) = file_report.refined.as_ref().and_then(|p| timeline_stats.remove(p)) else {
+            continue;
+        };
+        // JSON inputs are only counted here, CSVs already while converting
+        if file_report.format == "json" {
+            file_report.records = stats.records;
+        }
+        file_report.timeline_records = stats.in_timeline;
+        file_report.without_timestamp = stats.without_timestamp;
+        if let Some(e) = stats.error {
+            file_report.error(e);
+        }
+    }
+    reports.sort_by(|a, b| (&a.category, &a.file).cmp(&(&b.category, &b.file)));
+    report::write(&refined_path, &reports);
+    report::print_summary(&reports);
+
+    // MACB view of the MFT/USN data next to it, the master timeline keeps one time per record
+    mactime::export(&refined_path);
+    if output_format == OutputFormat::Parquet {
+        parquet_export::export(&refined_path);
+    }
     cleanup_empty_dirs(base_path);
+
+    reports.iter().map(|r| r.error_count).sum()
 }
 
 fn cleanup_empty_dirs(path: &Path) {
@@ -111,64 +163,158 @@ fn cleanup_empty_dirs(path: &Path) {
 }
 
 
-fn process_file(source: &Path, target_dir: &Path) {
+// How the cells of one CSV are turned into JSON values
+struct Conversion<'a> {
+    zone: Option<Tz>,
+    schema: Option<&'a Schema>,
+    strings_only: bool,
+    normalizer: Option<SourceNormalizer<'a>>,
+    output_format: OutputFormat,
+}
+
+/// Converts or copies one input into target_dir, everything that happened goes into `report`.
+fn process_file(source: &Path, target_dir: &Path, profiles: &[UserProfile], conversion: &Conversion, report: &mut FileReport) {
     let extension = source.extension().and_then(|s| s.to_str()).unwrap_or("");
     let file_stem = source.file_stem().unwrap().to_string_lossy();
     let target_path = target_dir.join(format!("{}.json", file_stem));
+    report.format = extension.to_string();
 
-    match extension {
+    let write = |records: Vec<Value>| -> Result<(), Box<dyn std::error::Error>> {
+        // Mapped onto ECS/OCSF when asked for, the tool's fields move below "original"
+        let records = match &conversion.normalizer {
+            Some(normalizer) => normalizer.records(records),
+            None => records,
+        };
+        Ok(write_records(&target_path, &records, conversion.output_format)?)
+    };
+
+    let written = match extension {
         "csv" => {
-            if let Ok(json_data) = convert_csv_to_json_normalized(source) {
-                // FIX: to_string() and not to_string_pretty() for minified JSON AI token efficient 
-                let _ = fs::write(target_path, serde_json::to_string(&json_data).unwrap());
+            convert_csv_to_json_normalized(source, profiles, conversion, report).and_then(write)
+        },
+        // Rewritten when the records change or the layout has to
+        "json" if conversion.normalizer.is_some() || conversion.output_format == OutputFormat::Ndjson => {
+            let (records, error) = read_json_records_checked(source);
+            if let Some(e) = error {
+                report.error(e);
             }
+            write(records)
         },
         "json" => {
             // If it's already JSON, just copy it over
-            let _ = fs::copy(source, target_path);
+            fs::copy(source, &target_path).map(|_| ()).map_err(|e| e.into())
         },
-        _ => {}
+        _ => {
+            report.format = "not refined".to_string();
+            return;
+        }
+    };
+
+    if conversion.normalizer.is_some() {
+        report.schema = conversion.normalizer.as_ref().map(|n| n.schema().to_string());
+    }
+    match written {
+        Ok(()) => report.refined = Some(target_path),
+        Err(e) => report.error(format!("could not refine: {}", e)),
     }
 }
 
-fn convert_csv_to_json_normalized(path: &Path) -> Result<Value, Box<dyn std::error::Error>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).flexible(true).from_reader(file);
+fn convert_csv_to_json_normalized(path: &Path, profiles: &[UserProfile], conversion: &Conversion, report: &mut FileReport) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    // UTF-16 from the EZ tools, ';' and Windows-1252 from German systems
+    let (mut rdr, dialect) = dialect::open(path)?;
+    // Only CSVs that weren't UTF-8 with commas get their dialect in the report
+    if !dialect.is_default() {
+        if dialect.encoding_guessed {
+            ui::warn(&format!("{}: no BOM and not UTF-8, read as {}", report.file, dialect.encoding));
+        }
+        report.dialect = Some(dialect.to_json());
+    }
     let headers = rdr.headers()?.clone();
-    let mut records = Vec::new();
-    let time_keywords = ["Time", "Date", "Created", "Executed", "Modified", "LastWrite"];
 
-    for result in rdr.records() {
-        let record = result?;
+    // A broken row costs that row, not the whole file. Short rows are fine (missing trailing cells),
+    // a row with more cells than the header can't be matched to its columns.
+    let mut rows = Vec::new();
+    for (i, result) in rdr.records().enumerate() {
+        match result {
+            Ok(row) if row.len() > headers.len() => {
+                report.error(format!("row {}: {} fields, the header has {}", i + 1, row.len(), headers.len()));
+            },
+            Ok(row) => rows.push(row),
+            Err(e) => report.error(format!("row {}: {}", i + 1, e)),
+        }
+    }
+    report.records = rows.len();
+    let time_keywords = ["Time", "Date", "Created", "Executed", "Modified", "Accessed", "LastWrite"];
+
+    // One type per column: pinned by the tool's schema file, a time column, or inferred from all cells
+    let column_types: Vec<ColumnType> = headers.iter().enumerate().map(|(i, header)| {
+        let pinned = conversion.schema.and_then(|s| s.columns.get(header)).copied();
+        let column_type = pinned.unwrap_or_else(|| {
+            if time_keywords.iter().any(|&k| header.contains(k)) {
+                ColumnType::Timestamp
+            } else {
+                schema::infer(rows.iter().map(|r| r.get(i).unwrap_or("").trim()))
+            }
+        });
+        match column_type {
+            ColumnType::Timestamp => ColumnType::Timestamp,
+            _ if conversion.strings_only => ColumnType::String,
+            other => other,
+        }
+    }).collect();
+
+    let mut records = Vec::new();
+    for record in &rows {
         let mut map = Map::new();
 
+        for ((i, header), column_type) in headers.iter().enumerate().zip(&column_types) {
+            // use .trim to clean up whitespace
+            let value = record.get(i).unwrap_or("").trim();
 
-        for (i, header) in headers.iter().enumerate() {
-        // use .trim to clean up whitespace
-        let mut value = record.get(i).unwrap_or("").trim().to_string();
-    
-        if time_keywords.iter().any(|&k| header.contains(k)) && !value.is_empty() {
-            value = normalize_time(&value);
+            let typed = if value.is_empty() {
+                if conversion.strings_only { Value::String(String::new()) } else { Value::Null }
+            } else if *column_type == ColumnType::Timestamp {
+                let normalized = normalize_time(value, conversion.zone);
+                // Original string stays next to the normalized one
+                if normalized != value {
+                    map.insert(format!("{}_Raw", header), Value::String(value.to_string()));
+                }
+                Value::String(normalized)
+            } else {
+                // A pinned type the cell doesn't fit keeps the string instead of losing it
+                schema::convert(value, *column_type).unwrap_or_else(|| Value::String(value.to_string()))
+            };
+            map.insert(header.to_string(), typed);
         }
-        map.insert(header.to_string(), Value::String(value));
-    }
+        users::tag_owner(&mut map, profiles);
         records.push(Value::Object(map));
     }
-    Ok(Value::Array(records))
+    Ok(records)
+}
+
+fn normalize_time(raw_time: &str, zone: Option<Tz>) -> String {
+    parse_time(raw_time)
+        .and_then(|dt| timezone::local_to_utc(dt, zone))
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| raw_time.to_string())
 }
 
-fn normalize_time(raw_time: &str) -> String {
+/// Instant of a refined time value. Values carry their offset or were normalized to UTC already.
+pub fn parse_instant(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.to_utc())
+        .or_else(|| parse_time(value).map(|t| t.and_utc()))
+}
+
+/// Parses the timestamp formats the EZ tools (and most other CSV exports) write.
+pub fn parse_time(raw_time: &str) -> Option<NaiveDateTime> {
     let formats = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.fZ", "%Y-%m-%d %H:%M:%S", "%m/%d/%Y %H:%M:%S"];
-    for fmt in formats {
-        if let Ok(dt) = NaiveDateTime::parse_from_str(raw_time, fmt) {
-            return dt.and_utc().to_rfc3339();
-        }
-    }
-    raw_time.to_string()
+    formats.iter().find_map(|fmt| NaiveDateTime::parse_from_str(raw_time, fmt).ok())
 }
 
-pub fn create_master_timeline(refined_path: &Path) {
+/// Rebuilds master_timeline.json from every refined JSON file and returns per file how many records it held.
+pub fn create_master_timeline(refined_path: &Path, rules: &Rules, output_format: OutputFormat) -> HashMap<PathBuf, TimelineStats> {
     let mut timeline = Vec::new();
+    let mut stats: HashMap<PathBuf, TimelineStats> = HashMap::new();
     let current_year = chrono::Local::now().year(); // Nutzt jetzt Lokalzeit-Jahr
 
     // Wir gehen durch alle Dateien im refined-Ordner
@@ -178,55 +324,212 @@ pub fn create_master_timeline(refined_path: &Path) {
         
         // --- HIER KOMMT DER FILTER HIN ---
         // Wir überspringen alles, was kein JSON ist, UND unsere Spezialdateien
-        if !file_name.ends_with(".json") 
-           || file_name == "case_summary.json" 
-           || file_name == "master_timeline.json" 
-        {
+        if !file_name.ends_with(".json") || is_case_file(&file_name) {
             continue;
         }
         // ---------------------------------
 
-        if let Ok(file) = std::fs::File::open(path) {
-            let data: serde_json::Value = serde_json::from_reader(file).unwrap_or(serde_json::Value::Null);
-            if let Some(array) = data.as_array() {
-                for item in array {
-                    if let Some(obj) = item.as_object() {
-                        if let Some(ts) = find_best_timestamp(obj) {
-                            
-                            // Nutzt die neue Deep-Scan Logik für die 2069-Treiber
-                            let has_future_date = check_for_future_dates(obj, current_year);
-
-                            timeline.push(serde_json::json!({
-                                "ts": ts,
-                                "cat": path.parent().unwrap().file_name().unwrap().to_string_lossy(),
-                                "src": file_name,
-                                "suspicious_time": has_future_date, 
-                                "data": item
-                            }));
-                        }
+        let fields = rules.timestamps(&file_name).map(|t| t.fields.as_slice());
+        // Subcategory and ATT&CK tags per record
+        let file_rules = rules.for_file(&file_name);
+
+        let file_stats = stats.entry(path.to_path_buf()).or_default();
+        let (records, error) = read_json_records_checked(path);
+        file_stats.records = records.len();
+        file_stats.error = error;
+
+        for item in records {
+            let Some(obj) = item.as_object() else {
+                file_stats.without_timestamp += 1;
+                continue;
+            };
+            // Time fields and rules refer to the tool's own field names
+            let obj = normalize::original(obj);
+            // Nutzt die neue Deep-Scan Logik für die 2069-Treiber
+            let has_future_date = check_for_future_dates(obj, current_year);
+
+            let tag = file_rules.tag(obj);
+            let events = timestamp_events(obj, fields);
+            if events.is_empty() {
+                file_stats.without_timestamp += 1;
+            } else {
+                file_stats.in_timeline += 1;
+            }
+            for (instant, ts_raw, ts_field, ts_desc) in events {
+                // Unparseable times keep their string and end up behind the sorted events
+                let ts = instant.map(|t| t.to_rfc3339()).unwrap_or_else(|| ts_raw.clone());
+                let mut event = json!({
+                    "ts": ts,
+                    "ts_raw": ts_raw,
+                    "ts_field": ts_field,
+                    "ts_desc": ts_desc,
+                    "cat": path.parent().unwrap().file_name().unwrap().to_string_lossy(),
+                    "src": file_name,
+                    "suspicious_time": has_future_date, 
+                    "data": item
+                });
+                // Only set when a rule says something, keeps the timeline small
+                if let Some(rule) = tag {
+                    if let Some(subcategory) = &rule.subcategory {
+                        event["subcat"] = json!(subcategory);
+                    }
+                    if !rule.attack.is_empty() {
+                        event["attack"] = json!(rule.attack);
                     }
                 }
+                timeline.push((instant, event));
+            }
+        }
+    }
+
+    // Sortieren (nach Zeitpunkt, nicht String) und minifiziert speichern
+    timeline.sort_by_key(|(instant, _)| (instant.is_none(), *instant));
+    let timeline: Vec<Value> = timeline.into_iter().map(|(_, event)| event).collect();
+    let _ = write_records(&refined_path.join("master_timeline.json"), &timeline, output_format);
+    stats
+}
+
+/// Writes refined records as one minified array or as JSON lines. The file name stays *.json either way,
+/// `read_json_records` takes both.
+pub fn write_records(path: &Path, records: &[Value], output_format: OutputFormat) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    match output_format {
+        // FIX: to_string() and not to_string_pretty() for minified JSON AI token efficient 
+        // Parquet is written from the finished JSON at the end of the refinement
+        OutputFormat::Json | OutputFormat::Parquet => serde_json::to_writer(&mut writer, records)?,
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut writer, record)?;
+                writer.write_all(b"\n")?;
+            }
+        },
+    }
+    writer.flush()
+}
+
+/// Nested objects as dotted keys, {"process": {"name": "sshd"}} -> "process.name". Lists stay lists, nulls are dropped.
+pub fn flatten_json(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_json(&key, value, out);
             }
+        },
+        Value::Null => {},
+        other => out.push((prefix.to_string(), other.clone())),
+    }
+}
+
+/// Our own JSON files in refined/, everything else there holds records of a source.
+pub fn is_case_file(file_name: &str) -> bool {
+    file_name == "case_summary.json"
+        || file_name == "master_timeline.json"
+        || file_name == users::PROFILES_FILE
+        || file_name == timezone::TIME_ZONE_FILE
+        || file_name == report::REPORT_FILE
+}
+
+/// Reads a JSON array, or JSON lines as written by MFTECmd and EvtxECmd. Empty if unreadable.
+pub fn read_json_records(path: &Path) -> Vec<Value> {
+    read_json_records_checked(path).0
+}
+
+// Also says what went wrong. Broken JSON lines are skipped, the rest of the file is kept.
+fn read_json_records_checked(path: &Path) -> (Vec<Value>, Option<String>) {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return (Vec::new(), Some(format!("could not read: {}", e))),
+    };
+    if content.trim_start().starts_with('[') {
+        return match serde_json::from_str(&content) {
+            Ok(records) => (records, None),
+            Err(e) => (Vec::new(), Some(format!("invalid JSON: {}", e))),
+        };
+    }
+
+    let mut records = Vec::new();
+    let mut broken = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(_) => broken += 1,
         }
     }
+    let error = (broken > 0).then(|| format!("{} of {} JSON lines are invalid", broken, broken + records.len()));
+    (records, error)
+}
+
+fn looks_like_timestamp(value: &str) -> bool {
+    !value.is_empty() && value.chars().next().unwrap().is_ascii_digit() && value.contains('T')
+}
 
-    // Sortieren und minifiziert speichern
-    timeline.sort_by(|a, b| a["ts"].as_str().unwrap().cmp(b["ts"].as_str().unwrap()));
-    let _ = std::fs::write(refined_path.join("master_timeline.json"), serde_json::to_string(&timeline).unwrap());
+// The string the source actually wrote, CSV conversion keeps it as <field>_Raw
+fn raw_value(obj: &Map<String, Value>, field: &str, value: &str) -> String {
+    obj.get(&format!("{}_Raw", field))
+        .and_then(|v| v.as_str())
+        .unwrap_or(value)
+        .to_string()
 }
 
-fn find_best_timestamp(obj: &Map<String, Value>) -> Option<String> {
+// Parsed time of a timeline event, None if the string couldn't be read
+type Instant = Option<DateTime<Utc>>;
+
+/// One (instant, raw, fields, meanings) event per distinct time of the record. Fields that share
+/// an instant are merged ("created, modified") so an untouched file doesn't show up four times.
+fn timestamp_events(obj: &Map<String, Value>, fields: Option<&[(String, String)]>) -> Vec<(Instant, String, String, String)> {
+    let mut events: Vec<(Instant, String, Vec<&str>, Vec<&str>)> = Vec::new();
+
+    for (field, meaning) in fields.unwrap_or_default() {
+        let (field, meaning) = (field.as_str(), meaning.as_str());
+        // Lists (e.g. Prefetch run times) contribute every entry
+        let values: Vec<&str> = match obj.get(field) {
+            Some(Value::String(val)) => vec![val.as_str()],
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).collect(),
+            _ => continue,
+        };
+        for val in values.into_iter().filter(|v| looks_like_timestamp(v)) {
+            let instant = parse_instant(val);
+            let same_time = |ts: &Instant, raw: &str| if instant.is_some() { *ts == instant } else { raw == val };
+            match events.iter_mut().find(|(ts, raw, _, _)| same_time(ts, raw)) {
+                Some((_, _, merged_fields, meanings)) => {
+                    merged_fields.push(field);
+                    if !meanings.contains(&meaning) {
+                        meanings.push(meaning);
+                    }
+                },
+                None => events.push((instant, raw_value(obj, field, val), vec![field], vec![meaning])),
+            }
+        }
+    }
+
+    // Mapped sources only use their mapped fields, a record without them has no time
+    // (a FileModified would place a shell history line at the wrong moment)
+    if fields.is_none()
+        && let Some((field, ts)) = find_best_timestamp(obj)
+    {
+        return vec![(parse_instant(&ts), raw_value(obj, field, &ts), field.to_string(), "event".to_string())];
+    }
+
+    events.into_iter()
+        .map(|(instant, raw, fields, meanings)| (instant, raw, fields.join(", "), meanings.join(", ")))
+        .collect()
+}
+
+fn find_best_timestamp(obj: &Map<String, Value>) -> Option<(&'static str, String)> {
     // Prioritized list of timestamp keys because different tools use different conventions and because some timestamps are more reliable
     let priority_keys = [
         "ts_normalized", "LastWriteTimestamp", "Timestamp", 
-        "NameKeyLastWrite", "DriverLastWriteTime", "CreatedOn"
+        "NameKeyLastWrite", "DriverLastWriteTime", "CreatedOn",
+        "LastModified", "SourceModified", "DeletedOn",
+        "VisitTime", "StartTime", "LoginTime", "FileModified"
     ];
     
     for key in priority_keys {
         if let Some(Value::String(val)) = obj.get(key) {
             // Check if the value looks like a timestamp
-            if !val.is_empty() && val.chars().next().unwrap().is_ascii_digit() && val.contains('T') {
-                return Some(val.clone());
+            if looks_like_timestamp(val) {
+                return Some((key, val.clone()));
             }
         }
     }
@@ -238,16 +541,175 @@ fn check_for_future_dates(obj: &serde_json::Map<String, serde_json::Value>, curr
     for value in obj.values() {
         if let Some(s) = value.as_str() {
             // Wir prüfen, ob der String mit 4 Ziffern beginnt (z.B. "2069-...")
-            if s.len() >= 4 && s.chars().take(4).all(|c| c.is_ascii_digit()) {
-                if let Ok(year) = s[0..4].parse::<i32>() {
-                    // Markieren als verdächtig, wenn das Jahr in der Zukunft liegt
-                    // (Aber wir begrenzen es auf 2100, um totalen Datenmüll auszuschließen)
-                    if year > current_year && year < 2100 {
-                        return true;
-                    }
+            if s.len() >= 4
+                && s.chars().take(4).all(|c| c.is_ascii_digit())
+                && let Ok(year) = s[0..4].parse::<i32>()
+            {
+                // Markieren als verdächtig, wenn das Jahr in der Zukunft liegt
+                // (Aber wir begrenzen es auf 2100, um totalen Datenmüll auszuschließen)
+                if year > current_year && year < 2100 {
+                    return true;
                 }
             }
         }
     }
     false
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracenexus-refiner-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn local_time_sources_are_shifted() {
+        let dir = case_dir("local-time");
+        let rules_path = dir.join("categories.json");
+        fs::write(&rules_path, r#"{
+            "rules": [{"name": "firewall", "file": "Firewall", "category": "Network"}],
+            "local_time": [{"name": "firewall-log", "file": "^Firewall_Local"}]
+        }"#).unwrap();
+        let rules = crate::rules::load(Some(&rules_path)).unwrap();
+        fs::remove_file(&rules_path).unwrap();
+        let csv = "Time,Action\n2024-07-01 12:00:00,DROP\n";
+        fs::write(dir.join("Firewall_Local.csv"), csv).unwrap();
+        fs::write(dir.join("Firewall_Utc.csv"), csv).unwrap();
+
+        let options = RefineOptions {
+            zone: Some(chrono_tz::Europe::Berlin),
+            zone_origin: "--source-tz",
+            strings_only: false,
+            rules: &rules,
+            normalizer: None,
+            output_format: OutputFormat::Json,
+        };
+        assert_eq!(run_refinement(dir.to_str().unwrap(), &options), 0);
+
+        // CEST is UTC+2
+        let local = read_json_records(&dir.join("refined/Network/Firewall_Local.json"));
+        assert_eq!(local[0]["Time"], "2024-07-01T10:00:00+00:00");
+        assert_eq!(local[0]["Time_Raw"], "2024-07-01 12:00:00");
+        let utc = read_json_records(&dir.join("refined/Network/Firewall_Utc.json"));
+        assert_eq!(utc[0]["Time"], "2024-07-01T12:00:00+00:00");
+
+        let time_zone: Value = serde_json::from_str(&fs::read_to_string(dir.join("refined").join(timezone::TIME_ZONE_FILE)).unwrap()).unwrap();
+        assert_eq!(time_zone["zone"], "Europe/Berlin");
+        assert_eq!(time_zone["sources"]["Firewall_Local.csv"], "local");
+        assert_eq!(time_zone["sources"]["Firewall_Utc.csv"], "UTC");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn timestamp_events_per_instant() {
+        let rules = crate::rules::load(Some(Path::new(crate::rules::RULES_FILE))).unwrap();
+        let events = |file_name: &str, record: Value| -> Vec<(String, String, String)> {
+            let fields = rules.timestamps(file_name).map(|t| t.fields.as_slice());
+            timestamp_events(record.as_object().unwrap(), fields).into_iter()
+                .map(|(instant, raw, fields, meanings)| (instant.map(|t| t.to_rfc3339()).unwrap_or(raw), fields, meanings))
+                .collect()
+        };
+        let event = |ts: &str, fields: &str, meanings: &str| (ts.to_string(), fields.to_string(), meanings.to_string());
+
+        // An untouched file: created and modified at the same instant, with and without fraction
+        let mft = json!({
+            "Created0x10": "2024-03-01T08:00:00+00:00", "LastModified0x10": "2024-03-01T08:00:00.000+00:00",
+            "LastAccess0x10": "2024-03-05T12:30:00+00:00", "FileSize": 12,
+        });
+        assert_eq!(events("20240306_MFTECmd_$MFT_Output.json", mft), vec![
+            event("2024-03-01T08:00:00+00:00", "Created0x10, LastModified0x10", "created, modified"),
+            event("2024-03-05T12:30:00+00:00", "LastAccess0x10", "accessed"),
+        ]);
+
+        // Every run time of the list is its own event, the first run shares its instant with the creation
+        let prefetch = json!({
+            "LastRunTimes": ["2024-03-02T09:00:00Z", "2024-03-01T07:00:00Z"],
+            "CreationTime": "2024-03-01T07:00:00Z",
+        });
+        assert_eq!(events("Velociraptor_Windows.Forensics.Prefetch.json", prefetch), vec![
+            event("2024-03-02T09:00:00+00:00", "LastRunTimes", "executed"),
+            event("2024-03-01T07:00:00+00:00", "LastRunTimes, CreationTime", "executed, created"),
+        ]);
+
+        let session = json!({"User": "CORP\\bob", "LoginTime": "2024-03-01T06:00:00Z", "CollectedAt": "2024-03-06T10:00:00Z"});
+        assert_eq!(events("Volatile_Sessions.json", session), vec![
+            event("2024-03-01T06:00:00+00:00", "LoginTime", "logon"),
+            event("2024-03-06T10:00:00+00:00", "CollectedAt", "collected"),
+        ]);
+
+        // A mapped source without its fields has no time, unmapped ones fall back to the usual columns
+        assert!(events("Linux_ShellHistory.json", json!({"FileModified": "2024-03-01T00:00:00Z"})).is_empty());
+        assert_eq!(events("Custom.json", json!({"Timestamp": "2024-03-01T00:00:00Z"})), vec![
+            event("2024-03-01T00:00:00+00:00", "Timestamp", "event"),
+        ]);
+    }
+
+    #[test]
+    fn ndjson_round_trip() {
+        let dir = case_dir("ndjson");
+        let path = dir.join("Linux_AuthLog.json");
+        let records = vec![json!({"Process": "sshd", "Message": "line\nbreak"}), json!({"Pid": 42, "Nested": {"a": [1, 2]}})];
+
+        write_records(&path, &records, OutputFormat::Ndjson).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert_eq!(read_json_records_checked(&path), (records.clone(), None));
+
+        // A line cut off mid-write loses only itself
+        fs::write(&path, format!("{}{{\"Process\": \"ss", content)).unwrap();
+        assert_eq!(read_json_records_checked(&path), (records.clone(), Some("1 of 3 JSON lines are invalid".to_string())));
+
+        write_records(&path, &records, OutputFormat::Json).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().starts_with('['));
+        assert_eq!(read_json_records(&path), records);
+        fs::write(&path, "[{\"Pid\": 1}").unwrap();
+        let (broken, error) = read_json_records_checked(&path);
+        assert!(broken.is_empty());
+        assert!(error.unwrap().starts_with("invalid JSON"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refinement_report_counts_errors() {
+        let dir = case_dir("report");
+        let rules = crate::rules::load(Some(Path::new(crate::rules::RULES_FILE))).unwrap();
+        fs::write(dir.join("Custom_Logons.csv"), "Time,User\n2024-03-01 10:00:00,alice\n2024-03-01 11:00:00,bob,extra\n2024-03-01 12:00:00\n").unwrap();
+        fs::write(dir.join("Custom_Broken.json"), "[{\"Time\": \"2024-03-01T10:00:00Z\"").unwrap();
+
+        let options = RefineOptions {
+            zone: None,
+            zone_origin: "default",
+            strings_only: false,
+            rules: &rules,
+            normalizer: None,
+            output_format: OutputFormat::Json,
+        };
+        let errors = run_refinement(dir.to_str().unwrap(), &options);
+        assert_eq!(errors, 2);
+        assert!(report::check_limit(errors, None).is_ok());
+        assert!(report::check_limit(errors, Some(2)).is_ok());
+        assert_eq!(
+            report::check_limit(errors, Some(1)),
+            Err(format!("Refinement had 2 errors (--max-errors 1), see refined/{}", report::REPORT_FILE)),
+        );
+
+        let report: Value = serde_json::from_str(&fs::read_to_string(dir.join("refined").join(report::REPORT_FILE)).unwrap()).unwrap();
+        assert_eq!(report["errors"], 2);
+        let file = |name: &str| report["files"].as_array().unwrap().iter().find(|f| f["file"] == name).unwrap().clone();
+        // the short row is kept, the one with an extra cell is not
+        let csv = file("Custom_Logons.csv");
+        assert_eq!((csv["records"].clone(), csv["error_count"].clone()), (json!(2), json!(1)));
+        assert_eq!(csv["errors"], json!(["row 2: 3 fields, the header has 2"]));
+        let broken = file("Custom_Broken.json");
+        assert_eq!(broken["error_count"], 1);
+        assert!(broken["errors"][0].as_str().unwrap().starts_with("invalid JSON"));
+        // still moved to raw/ with everything else
+        assert!(dir.join("raw").join("Custom_Broken.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
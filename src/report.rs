@@ -0,0 +1,87 @@
+use colored::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Written to refined/, one entry per input file of the refinement
+pub const REPORT_FILE: &str = "refinement_report.json";
+
+// Per file, the count keeps going but the report shouldn't grow with a broken 1 GB CSV
+const MAX_ERROR_MESSAGES: usize = 20;
+
+/// What happened to one input file during refinement.
+#[derive(Serialize, Default)]
+pub struct FileReport {
+    pub file: String,
+    // csv, json or "not refined" for logs and anything else that only goes to raw/
+    pub format: String,
+    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialect: Option<Value>,
+    // ECS or OCSF when the records were normalized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub records: usize,
+    pub timeline_records: usize,
+    pub without_timestamp: usize,
+    pub error_count: usize,
+    pub errors: Vec<String>,
+    // Refined JSON written for the file, matched against the timeline stats
+    #[serde(skip)]
+    pub refined: Option<PathBuf>,
+}
+
+impl FileReport {
+    pub fn error(&mut self, message: String) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_ERROR_MESSAGES {
+            self.errors.push(message);
+        }
+    }
+}
+
+pub fn write(refined_path: &Path, files: &[FileReport]) {
+    let report = json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "files": files,
+        "records": files.iter().map(|f| f.records).sum::<usize>(),
+        "timeline_records": files.iter().map(|f| f.timeline_records).sum::<usize>(),
+        "errors": files.iter().map(|f| f.error_count).sum::<usize>(),
+    });
+    let _ = fs::write(refined_path.join(REPORT_FILE), serde_json::to_string(&report).unwrap());
+}
+
+/// --max-errors: says why the run has to stop when the refinement went over the limit.
+pub fn check_limit(errors: usize, max_errors: Option<usize>) -> Result<(), String> {
+    match max_errors {
+        Some(max_errors) if errors > max_errors => {
+            Err(format!("Refinement had {} errors (--max-errors {}), see refined/{}", errors, max_errors, REPORT_FILE))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Table of every refined file, files with errors in red.
+pub fn print_summary(files: &[FileReport]) {
+    println!("{}", "\n--- [ REFINEMENT SUMMARY ] ---\n".bright_cyan().bold());
+    println!("{:<48} {:<12} {:>9} {:>9} {:>8} {:>7}", "File", "Category", "Records", "Timeline", "No time", "Errors");
+
+    for file in files.iter().filter(|f| f.format != "not refined") {
+        let name: String = if file.file.chars().count() > 47 {
+            format!("{}~", file.file.chars().take(46).collect::<String>())
+        } else {
+            file.file.clone()
+        };
+        let line = format!(
+            "{:<48} {:<12} {:>9} {:>9} {:>8} {:>7}",
+            name, file.category, file.records, file.timeline_records, file.without_timestamp, file.error_count
+        );
+        if file.error_count > 0 {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+    println!();
+}
@@ -0,0 +1,393 @@
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::dialect;
+use crate::refiner;
+
+// Shipped with the binary, rules/categories.json next to tools/ (or --rules) replaces it.
+// Besides the category rules it says which sources are in local time and which time fields make timeline events.
+const DEFAULT_RULES: &str = include_str!("../rules/categories.json");
+pub const RULES_FILE: &str = "rules/categories.json";
+pub const DEFAULT_CATEGORY: &str = "Other";
+
+#[derive(Deserialize)]
+struct RuleFile {
+    rules: Vec<RuleDef>,
+    #[serde(default)]
+    local_time: Vec<SourceDef>,
+    #[serde(default)]
+    timestamps: Vec<TimestampDef>,
+}
+
+#[derive(Deserialize)]
+struct SourceDef {
+    name: String,
+    file: String,
+}
+
+#[derive(Deserialize)]
+struct TimestampDef {
+    name: String,
+    file: String,
+    fields: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+struct RuleDef {
+    name: String,
+    file: String,
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+    category: Option<String>,
+    subcategory: Option<String>,
+    #[serde(default)]
+    attack: Vec<String>,
+}
+
+/// One rule of the category file. Without `fields` it decides the refined folder of every file
+/// whose name matches, with `fields` it only tags the matching records of such files (subcategory
+/// and ATT&CK ids, a category there is rejected when loading).
+/// All patterns are case-insensitive regexes.
+pub struct Rule {
+    pub index: usize,
+    pub name: String,
+    pub file: Regex,
+    pub fields: Vec<(String, Regex)>,
+    pub category: Option<String>,
+    pub subcategory: Option<String>,
+    pub attack: Vec<String>,
+}
+
+/// Which time fields of a source become timeline events and what each one means ("created").
+/// The first entry whose pattern matches the file name wins, sources without one fall back to
+/// the usual timestamp columns.
+pub struct TimestampRule {
+    pub name: String,
+    pub file: Regex,
+    pub fields: Vec<(String, String)>,
+}
+
+pub struct Rules {
+    // Where the rules came from, for the log and `categories test`
+    pub origin: String,
+    pub rules: Vec<Rule>,
+    // Sources that write naive times in the host's local time, everything else is UTC
+    pub local_time: Vec<(String, Regex)>,
+    pub timestamps: Vec<TimestampRule>,
+}
+
+/// The rules that apply to one file name.
+pub struct FileRules<'a> {
+    pub file_rule: Option<&'a Rule>,
+    pub record_rules: Vec<&'a Rule>,
+}
+
+fn compile(pattern: &str, rule: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("rule '{}': {}", rule, e))
+}
+
+/// `--rules` first, then rules/categories.json in the working directory, then the built-in copy.
+pub fn load(path: Option<&Path>) -> Result<Rules, String> {
+    let (origin, content) = match path {
+        Some(path) => (path.display().to_string(), fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+        None => match fs::read_to_string(RULES_FILE) {
+            Ok(content) => (RULES_FILE.to_string(), content),
+            Err(_) => ("built-in".to_string(), DEFAULT_RULES.to_string()),
+        },
+    };
+
+    let file: RuleFile = serde_json::from_str(&content).map_err(|e| format!("{}: {}", origin, e))?;
+    let mut rules = Vec::new();
+    for (index, def) in file.rules.into_iter().enumerate() {
+        // A file rule without a category would send files nowhere
+        if def.fields.is_empty() && def.category.is_none() {
+            return Err(format!("rule '{}' has neither fields nor a category", def.name));
+        }
+        // The folder is decided per file, a category on a record rule would never be used
+        if !def.fields.is_empty() && def.category.is_some() {
+            return Err(format!("rule '{}' has fields and a category, record rules only set subcategory and attack", def.name));
+        }
+        let fields = def.fields.iter()
+            .map(|(field, pattern)| Ok((field.clone(), compile(pattern, &def.name)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        rules.push(Rule {
+            index: index + 1,
+            file: compile(&def.file, &def.name)?,
+            name: def.name,
+            fields,
+            category: def.category,
+            subcategory: def.subcategory,
+            attack: def.attack,
+        });
+    }
+    let local_time = file.local_time.iter()
+        .map(|def| Ok((def.name.clone(), compile(&def.file, &def.name)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let timestamps = file.timestamps.into_iter()
+        .map(|def| Ok(TimestampRule { file: compile(&def.file, &def.name)?, name: def.name, fields: def.fields }))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Rules { origin, rules, local_time, timestamps })
+}
+
+// Numbers and booleans are compared in their JSON form ("4624", "true")
+fn field_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+impl Rule {
+    pub fn is_file_rule(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn matches_record(&self, record: &Map<String, Value>) -> bool {
+        self.fields.iter().all(|(field, pattern)| {
+            record.get(field).and_then(field_text).is_some_and(|text| pattern.is_match(&text))
+        })
+    }
+
+    /// "#12 recmd-autoruns" plus what the rule says about a match
+    pub fn describe(&self) -> String {
+        let mut text = format!("#{} {}", self.index, self.name);
+        if let Some(category) = &self.category {
+            text += &format!(" -> {}", category);
+        }
+        if let Some(subcategory) = &self.subcategory {
+            text += &format!(" / {}", subcategory);
+        }
+        if !self.attack.is_empty() {
+            text += &format!(" [{}]", self.attack.join(", "));
+        }
+        text
+    }
+}
+
+impl Rules {
+    pub fn for_file(&self, file_name: &str) -> FileRules<'_> {
+        let matching: Vec<&Rule> = self.rules.iter().filter(|r| r.file.is_match(file_name)).collect();
+        FileRules {
+            file_rule: matching.iter().find(|r| r.is_file_rule()).copied(),
+            record_rules: matching.into_iter().filter(|r| !r.is_file_rule()).collect(),
+        }
+    }
+
+    pub fn category(&self, file_name: &str) -> &str {
+        self.for_file(file_name).file_rule
+            .and_then(|r| r.category.as_deref())
+            .unwrap_or(DEFAULT_CATEGORY)
+    }
+
+    /// Name of the `local_time` entry that marks the file as written in local time, if any.
+    pub fn local_time(&self, file_name: &str) -> Option<&str> {
+        self.local_time.iter()
+            .find(|(_, file)| file.is_match(file_name))
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn timestamps(&self, file_name: &str) -> Option<&TimestampRule> {
+        self.timestamps.iter().find(|t| t.file.is_match(file_name))
+    }
+}
+
+impl FileRules<'_> {
+    /// First record rule that matches, else the file rule. Used for subcategory and ATT&CK tags.
+    pub fn tag(&self, record: &Map<String, Value>) -> Option<&Rule> {
+        self.record_rules.iter()
+            .find(|r| r.matches_record(record))
+            .copied()
+            .or(self.file_rule)
+    }
+}
+
+/// `categories test <file>`: which rule sends the file where and which records get tagged by what.
+pub fn explain(rules: &Rules, path: &Path, out: &mut impl Write) -> io::Result<()> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    writeln!(out, "Rules: {} ({} rules)", rules.origin, rules.rules.len())?;
+    writeln!(out, "File:  {}\n", file_name)?;
+
+    let file_rules = rules.for_file(&file_name);
+    match file_rules.file_rule {
+        Some(rule) => writeln!(out, "Category: {}\n  matched by {} (file =~ /{}/)", rule.category.as_deref().unwrap_or(DEFAULT_CATEGORY), rule.describe(), rule.file.as_str())?,
+        None => writeln!(out, "Category: {} (no file rule matched)", DEFAULT_CATEGORY)?,
+    }
+    // Later rules that would have matched too, the usual reason for a surprising category
+    let shadowed: Vec<&Rule> = rules.rules.iter()
+        .filter(|r| r.is_file_rule() && r.file.is_match(&file_name))
+        .skip(1)
+        .collect();
+    for rule in shadowed {
+        writeln!(out, "  also matches, but comes later: {}", rule.describe())?;
+    }
+    match rules.local_time(&file_name) {
+        Some(name) => writeln!(out, "Times: local, shifted by the source time zone ({})", name)?,
+        None => writeln!(out, "Times: UTC")?,
+    }
+    match rules.timestamps(&file_name) {
+        Some(timestamps) => {
+            let fields: Vec<String> = timestamps.fields.iter().map(|(f, meaning)| format!("{} ({})", f, meaning)).collect();
+            writeln!(out, "Timeline: {} by {}", fields.join(", "), timestamps.name)?;
+        },
+        None => writeln!(out, "Timeline: first usual timestamp column")?,
+    }
+
+    if file_rules.record_rules.is_empty() {
+        writeln!(out, "\nNo record rules for this file.")?;
+        return Ok(());
+    }
+    writeln!(out, "\nRecord rules:")?;
+    for rule in &file_rules.record_rules {
+        let fields: Vec<String> = rule.fields.iter().map(|(f, p)| format!("{} =~ /{}/", f, p.as_str())).collect();
+        writeln!(out, "  {} ({})", rule.describe(), fields.join(", "))?;
+    }
+
+    let records = read_records(path);
+    if records.is_empty() {
+        writeln!(out, "\n(no records read, only the file name was checked)")?;
+        return Ok(());
+    }
+    let mut hits: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut untagged = 0;
+    for record in records.iter().filter_map(|r| r.as_object()) {
+        match file_rules.record_rules.iter().find(|r| r.matches_record(record)) {
+            Some(rule) => *hits.entry(rule.index).or_default() += 1,
+            None => untagged += 1,
+        }
+    }
+    writeln!(out, "\n{} records:", records.len())?;
+    for rule in &file_rules.record_rules {
+        writeln!(out, "  {:>8}  {}", hits.get(&rule.index).copied().unwrap_or(0), rule.describe())?;
+    }
+    writeln!(out, "  {:>8}  no record rule", untagged)?;
+    Ok(())
+}
+
+// Raw CSVs as strings (before typing), refined or tool JSON as is
+fn read_records(path: &Path) -> Vec<Value> {
+    if path.extension().and_then(|e| e.to_str()) != Some("csv") {
+        return refiner::read_json_records(path);
+    }
+    let Ok((mut rdr, _)) = dialect::open(path) else {
+        return Vec::new();
+    };
+    let Ok(headers) = rdr.headers().cloned() else {
+        return Vec::new();
+    };
+    rdr.records()
+        .filter_map(|r| r.ok())
+        .map(|record| {
+            let map: Map<String, Value> = headers.iter()
+                .zip(record.iter())
+                .map(|(h, v)| (h.to_string(), Value::String(v.trim().to_string())))
+                .collect();
+            Value::Object(map)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracenexus-rules-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn load_str(name: &str, content: &str) -> Result<Rules, String> {
+        let path = temp_dir().join(name);
+        fs::write(&path, content).unwrap();
+        let rules = load(Some(&path));
+        fs::remove_file(&path).unwrap();
+        rules
+    }
+
+    const REGISTRY_RULES: &str = r#"{"rules": [
+        {"name": "runmru", "file": "RECmd", "fields": {"KeyPath": "\\\\RunMRU$"}, "subcategory": "Typed commands", "attack": ["T1204"]},
+        {"name": "run-key", "file": "RECmd", "fields": {"KeyPath": "\\\\Run"}, "subcategory": "Autoruns", "attack": ["T1547.001"]},
+        {"name": "registry", "file": "RECmd_Batch", "category": "Registry"},
+        {"name": "everything-recmd", "file": "RECmd", "category": "Other"}
+    ]}"#;
+
+    fn record(value: serde_json::Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = load_str("order.json", REGISTRY_RULES).unwrap();
+        let file_rules = rules.for_file("20240101_RECmd_Batch_Output.csv");
+        assert_eq!(file_rules.file_rule.unwrap().name, "registry");
+        assert_eq!(rules.category("20240101_RECmd_Batch_Output.csv"), "Registry");
+
+        // "\Run" also matches RunMRU, the more specific rule has to come first
+        let run_mru = record(json!({"KeyPath": "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\RunMRU"}));
+        let run = record(json!({"KeyPath": "Software\\Microsoft\\Windows\\CurrentVersion\\Run"}));
+        assert_eq!(file_rules.tag(&run_mru).unwrap().name, "runmru");
+        assert_eq!(file_rules.tag(&run).unwrap().name, "run-key");
+        // No record rule matches, the file rule still tags it
+        assert_eq!(file_rules.tag(&record(json!({"KeyPath": "System\\Select"}))).unwrap().name, "registry");
+
+        let reordered = r#"{"rules": [
+            {"name": "run-key", "file": "RECmd", "fields": {"KeyPath": "\\\\Run"}, "subcategory": "Autoruns"},
+            {"name": "runmru", "file": "RECmd", "fields": {"KeyPath": "\\\\RunMRU$"}, "subcategory": "Typed commands"}
+        ]}"#;
+        let rules = load_str("reordered.json", reordered).unwrap();
+        assert_eq!(rules.for_file("RECmd_Batch.csv").tag(&run_mru).unwrap().name, "run-key");
+    }
+
+    #[test]
+    fn field_rules_need_every_field() {
+        let rules = load(Some(Path::new(RULES_FILE))).unwrap();
+        let file_rules = rules.for_file("20240101_EvtxECmd_Output.csv");
+        // Numbers are compared in their JSON form
+        assert_eq!(file_rules.tag(&record(json!({"EventId": 4624}))).unwrap().name, "evtx-logon");
+        assert_eq!(file_rules.tag(&record(json!({"EventId": "4625"}))).unwrap().name, "evtx-failed-logon");
+        // Anchored: 46240 is no logon
+        assert_eq!(file_rules.tag(&record(json!({"EventId": 46240}))).unwrap().name, "evtx");
+        // 4104 alone isn't enough, the channel has to match as well
+        let script_block = record(json!({"EventId": 4104, "Channel": "Microsoft-Windows-PowerShell/Operational"}));
+        assert_eq!(file_rules.tag(&script_block).unwrap().name, "evtx-powershell");
+        assert_eq!(file_rules.tag(&record(json!({"EventId": 4104}))).unwrap().name, "evtx");
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let both = r#"{"rules": [{"name": "mixed", "file": "x", "fields": {"a": "b"}, "category": "Logs"}]}"#;
+        assert!(load_str("both.json", both).err().unwrap().contains("mixed"));
+        let neither = r#"{"rules": [{"name": "empty", "file": "x"}]}"#;
+        assert!(load_str("neither.json", neither).err().unwrap().contains("empty"));
+        let regex = r#"{"rules": [{"name": "broken", "file": "(", "category": "Logs"}]}"#;
+        assert!(load_str("regex.json", regex).err().unwrap().contains("broken"));
+    }
+
+    #[test]
+    fn explain_output() {
+        let rules = load_str("explain.json", REGISTRY_RULES).unwrap();
+        let csv = temp_dir().join("20240101_RECmd_Batch_Output.csv");
+        fs::write(&csv, "HivePath,KeyPath\nNTUSER.DAT,Software\\Explorer\\RunMRU\nNTUSER.DAT,Software\\Run\nNTUSER.DAT,Software\\Run\nSYSTEM,Select\n").unwrap();
+        let mut out = Vec::new();
+        explain(&rules, &csv, &mut out).unwrap();
+        fs::remove_file(&csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Category: Registry\n  matched by #3 registry -> Registry (file =~ /RECmd_Batch/)"), "{}", out);
+        assert!(out.contains("also matches, but comes later: #4 everything-recmd -> Other"), "{}", out);
+        assert!(out.contains("4 records:"), "{}", out);
+        assert!(out.contains("       1  #1 runmru / Typed commands [T1204]"), "{}", out);
+        assert!(out.contains("       2  #2 run-key / Autoruns [T1547.001]"), "{}", out);
+        assert!(out.contains("       1  no record rule"), "{}", out);
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::ui;
+
+// Optional schema files, one per tool: schemas/<source>.json with {"<column>": "<type>"}.
+// <source> is matched against the CSV file name (substring), e.g. schemas/EvtxECmd.json.
+pub const SCHEMA_DIR: &str = "schemas";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Timestamp,
+}
+
+pub struct Schema {
+    pub source: String,
+    pub columns: HashMap<String, ColumnType>,
+}
+
+/// Reads every schemas/*.json, broken files are reported and skipped.
+pub fn load_schemas(dir: &Path) -> Vec<Schema> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut schemas = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let columns = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()));
+        match columns {
+            Ok(columns) => schemas.push(Schema {
+                source: path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+                columns,
+            }),
+            Err(e) => ui::warn(&format!("Ignoring schema {}: {}", path.display(), e)),
+        }
+    }
+    schemas
+}
+
+/// Pinned column types for a file, the longest matching source name wins ("SrumECmd_NetworkUsages" over "SrumECmd").
+pub fn find<'a>(schemas: &'a [Schema], file_name: &str) -> Option<&'a Schema> {
+    let file_name = file_name.to_lowercase();
+    schemas.iter()
+        .filter(|s| file_name.contains(&s.source.to_lowercase()))
+        .max_by_key(|s| s.source.len())
+}
+
+fn parse_integer(value: &str) -> Option<Value> {
+    // Leading zeros are identifiers (e.g. "0001"), not numbers
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    value.parse::<i64>().ok().map(Value::from)
+        .or_else(|| value.parse::<u64>().ok().map(Value::from))
+}
+
+fn parse_float(value: &str) -> Option<Value> {
+    // f64 parsing also takes "inf" and "NaN", only plain decimals count here
+    if !value.contains('.') || !value.bytes().all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b)) {
+        return None;
+    }
+    value.parse::<f64>().ok().filter(|f| f.is_finite()).map(Value::from)
+}
+
+// Only values that survive the conversion unchanged: "1.10" would come back as 1.1 (a version,
+// not a number) and integers above 2^53 lose digits
+fn is_exact_float(value: &str) -> bool {
+    match (parse_float(value), parse_integer(value)) {
+        (Some(float), _) => {
+            let rendered = float.to_string();
+            rendered == value
+        },
+        (None, Some(integer)) => integer.as_i64().is_some_and(|i| i as f64 as i64 == i),
+        (None, None) => false,
+    }
+}
+
+fn parse_boolean(value: &str) -> Option<Value> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => None,
+    }
+}
+
+/// Narrowest type every non-empty cell of a column fits into, String if they disagree.
+pub fn infer<'a>(values: impl Iterator<Item = &'a str>) -> ColumnType {
+    let mut candidates = vec![ColumnType::Integer, ColumnType::Float, ColumnType::Boolean];
+    let mut seen = false;
+
+    for value in values.filter(|v| !v.is_empty()) {
+        seen = true;
+        candidates.retain(|t| match t {
+            // Integers are valid floats, a column of "1" and "1.5" becomes Float
+            ColumnType::Float => is_exact_float(value),
+            _ => convert(value, *t).is_some(),
+        });
+        if candidates.is_empty() {
+            return ColumnType::String;
+        }
+    }
+    if seen { candidates[0] } else { ColumnType::String }
+}
+
+/// Typed value of a cell, None if it doesn't fit the type. Timestamps are normalized by the refiner.
+pub fn convert(value: &str, column_type: ColumnType) -> Option<Value> {
+    match column_type {
+        ColumnType::Integer => parse_integer(value),
+        ColumnType::Float => parse_float(value).or_else(|| parse_integer(value).and_then(|v| v.as_f64()).map(Value::from)),
+        ColumnType::Boolean => parse_boolean(value),
+        ColumnType::String | ColumnType::Timestamp => Some(Value::String(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_column_types() {
+        assert_eq!(infer(["1", "-2", ""].into_iter()), ColumnType::Integer);
+        assert_eq!(infer(["1", "1.5", "-0.25"].into_iter()), ColumnType::Float);
+        assert_eq!(infer(["true", "FALSE"].into_iter()), ColumnType::Boolean);
+        assert_eq!(infer(["0001", "0002"].into_iter()), ColumnType::String);
+        assert_eq!(infer(["", ""].into_iter()), ColumnType::String);
+    }
+
+    #[test]
+    fn floats_that_do_not_round_trip_stay_text() {
+        // Versions and zero-padded values would be rewritten
+        assert_eq!(infer(["1.10", "2.0"].into_iter()), ColumnType::String);
+        assert_eq!(infer(["1.5e3"].into_iter()), ColumnType::String);
+        assert_eq!(infer(["+1.5"].into_iter()), ColumnType::String);
+        assert_eq!(infer(["9007199254740993", "0.5"].into_iter()), ColumnType::String);
+        assert_eq!(infer(["100.0", "0.1"].into_iter()), ColumnType::Float);
+    }
+}
@@ -0,0 +1,384 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use crate::bodyfile;
+use crate::casedb;
+use crate::cli::{OutputFormat, TimelineFormat};
+use crate::compressor;
+use crate::dialect;
+use crate::manifest;
+use crate::parquet_export;
+use crate::refiner;
+use crate::timesketch;
+use crate::rules::Rules;
+use crate::timezone;
+use crate::ui;
+
+// Refined category that holds every imported timeline
+const IMPORT_CATEGORY: &str = "Imported";
+
+// --map keys for generic CSV and the field they fill
+const CSV_FIELDS: &[(&str, &str)] = &[
+    ("timestamp", "Timestamp"),
+    ("message", "Message"),
+    ("desc", "TimestampDesc"),
+    ("user", "UserName"),
+    ("host", "Host"),
+    ("path", "Path"),
+];
+
+pub struct ImportOptions<'a> {
+    pub format: &'a TimelineFormat,
+    pub label: Option<&'a str>,
+    pub mapping: &'a [(String, String)],
+    pub time_format: Option<&'a str>,
+    // Zone of naive timestamps, None = UTC
+    pub source_tz: Option<Tz>,
+    pub rules: &'a Rules,
+    // --output-format, None keeps the one of the existing case
+    pub output_format: Option<OutputFormat>,
+}
+
+/// Converts an external timeline into refined/Imported/<label>.json, rebuilds master_timeline.json
+/// and repackages the case so the events show up next to our own.
+pub fn import(file: &Path, out_dir: &str, options: &ImportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let label = options.label
+        .map(str::to_string)
+        .unwrap_or_else(|| file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default());
+
+    let (mut events, skipped) = match options.format {
+        TimelineFormat::Bodyfile => read_bodyfile(file)?,
+        TimelineFormat::L2tcsv => read_l2tcsv(file, options.source_tz)?,
+        TimelineFormat::JsonLine => read_json_line(file)?,
+        TimelineFormat::Csv => read_mapped_csv(file, options.mapping, options.time_format, options.source_tz)?,
+    };
+    if skipped > 0 {
+        ui::warn(&format!("{} lines without a usable timestamp were skipped", skipped));
+    }
+
+    for event in events.iter_mut() {
+        event.insert("Source".to_string(), Value::String(label.clone()));
+        event.insert("Format".to_string(), json!(format!("{:?}", options.format)));
+    }
+
+    let refined_path = Path::new(out_dir).join("refined");
+    let output_format = options.output_format
+        .or_else(|| manifest::case_output_format(&refined_path))
+        .unwrap_or_default();
+    let target_dir = refined_path.join(IMPORT_CATEGORY);
+    fs::create_dir_all(&target_dir)?;
+    // File name doubles as "src" in the master timeline
+    let file_name = label.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_', "_");
+    let events: Vec<Value> = events.into_iter().map(Value::Object).collect();
+    refiner::write_records(&target_dir.join(format!("{}.json", file_name)), &events, output_format)?;
+    ui::success(&format!("Imported {} events from {} as '{}'", events.len(), file.display(), label));
+
+    refiner::create_master_timeline(&refined_path, options.rules, output_format);
+    if output_format == OutputFormat::Parquet {
+        parquet_export::export(&refined_path);
+    }
+
+    // Keep the case ID of an existing case, a fresh output folder becomes a new case
+    let case_id = fs::read_to_string(refined_path.join("case_summary.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+        .and_then(|summary| summary["case_id"].as_str().map(str::to_string));
+    let case_id = match case_id {
+        Some(id) => id,
+        None => manifest::create_case_summary(out_dir, Some(&json!({ "type": "Timeline", "path": file.to_string_lossy() })), output_format),
+    };
+    // A case that came with a database gets it updated with the imported events
+    if refined_path.join(casedb::CASE_DB_FILE).exists() {
+        casedb::build(&refined_path);
+    }
+    if refined_path.join(timesketch::TIMESKETCH_FILE).exists() {
+        timesketch::export(&refined_path);
+    }
+    compressor::create_packages(out_dir, &case_id);
+    Ok(())
+}
+
+type Events = (Vec<Map<String, Value>>, usize);
+
+fn read_bodyfile(file: &Path) -> Result<Events, Box<dyn std::error::Error>> {
+    let mut events = Vec::new();
+    let mut skipped = 0;
+
+    for line in BufReader::new(fs::File::open(file)?).lines().map_while(Result::ok) {
+        let Some(entry) = bodyfile::parse_line(&line) else {
+            skipped += 1;
+            continue;
+        };
+        for (time, flags) in bodyfile::macb_events(&entry) {
+            let event = json!({
+                "Timestamp": time.to_rfc3339(),
+                "Macb": flags,
+                "Message": entry.name,
+                "Path": entry.name,
+                "Inode": entry.inode,
+                "Mode": entry.mode,
+                "Size": entry.size,
+                "Uid": entry.uid,
+                "Gid": entry.gid,
+                "Md5": entry.md5,
+            });
+            events.push(event.as_object().unwrap().clone());
+        }
+    }
+    Ok((events, skipped))
+}
+
+// l2tcsv: date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra
+fn read_l2tcsv(file: &Path, source_tz: Option<Tz>) -> Result<Events, Box<dyn std::error::Error>> {
+    let (mut rdr, _) = dialect::open(file)?;
+    let headers = rdr.headers()?.clone();
+    let col = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(date_col), Some(time_col)) = (col("date"), col("time")) else {
+        return Err("not an l2tcsv file (no date/time columns)".into());
+    };
+
+    let mut events = Vec::new();
+    let mut skipped = 0;
+    for record in rdr.records().filter_map(|r| r.ok()) {
+        let get = |name: &str| col(name).and_then(|i| record.get(i)).unwrap_or("").to_string();
+
+        let raw = format!("{} {}", record.get(date_col).unwrap_or(""), record.get(time_col).unwrap_or(""));
+        let date = NaiveDate::parse_from_str(record.get(date_col).unwrap_or(""), "%m/%d/%Y");
+        let time = NaiveTime::parse_from_str(record.get(time_col).unwrap_or(""), "%H:%M:%S");
+        // date/time are written in the zone of the timezone column
+        let zone = timezone::parse_zone(&get("timezone")).or(source_tz);
+        let timestamp = match (date, time) {
+            (Ok(date), Ok(time)) => timezone::local_to_utc(NaiveDateTime::new(date, time), zone),
+            _ => None,
+        };
+        let Some(timestamp) = timestamp else {
+            skipped += 1;
+            continue;
+        };
+
+        let event = json!({
+            "Timestamp": timestamp.to_rfc3339(),
+            "Timestamp_Raw": raw,
+            "TimeZone": get("timezone"),
+            "Macb": get("MACB"),
+            "TimestampDesc": get("type"),
+            "SourceType": get("sourcetype"),
+            "Message": get("desc"),
+            "Short": get("short"),
+            "UserName": get("user"),
+            "Host": get("host"),
+            "Path": get("filename"),
+            "Inode": get("inode"),
+            "Parser": get("format"),
+            "Extra": get("extra"),
+        });
+        events.push(event.as_object().unwrap().clone());
+    }
+    Ok((events, skipped))
+}
+
+// psort -o json_line: one object per line, "timestamp" is microseconds since the epoch
+fn read_json_line(file: &Path) -> Result<Events, Box<dyn std::error::Error>> {
+    let mut events = Vec::new();
+    let mut skipped = 0;
+
+    for line in BufReader::new(fs::File::open(file)?).lines().map_while(Result::ok) {
+        let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(&line) else {
+            skipped += 1;
+            continue;
+        };
+        let timestamp = fields.get("timestamp")
+            .and_then(|t| t.as_i64())
+            .and_then(DateTime::<Utc>::from_timestamp_micros)
+            .or_else(|| fields.get("datetime").and_then(|t| t.as_str()).and_then(|t| DateTime::parse_from_rfc3339(t).ok()).map(|t| t.to_utc()));
+        let Some(timestamp) = timestamp else {
+            skipped += 1;
+            continue;
+        };
+
+        let str_field = |name: &str| fields.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let path = [str_field("filename"), str_field("display_name")].into_iter().find(|p| !p.is_empty()).unwrap_or_default();
+
+        let event = json!({
+            "Timestamp": timestamp.to_rfc3339(),
+            "TimestampDesc": str_field("timestamp_desc"),
+            "Message": str_field("message"),
+            "DataType": str_field("data_type"),
+            "Parser": str_field("parser"),
+            "Host": str_field("hostname"),
+            "UserName": str_field("username"),
+            "Path": path,
+            "Fields": fields,
+        });
+        events.push(event.as_object().unwrap().clone());
+    }
+    Ok((events, skipped))
+}
+
+/// Generic CSV: every column is kept, --map decides which ones become Timestamp, Message, ...
+fn read_mapped_csv(file: &Path, mapping: &[(String, String)], time_format: Option<&str>, source_tz: Option<Tz>) -> Result<Events, Box<dyn std::error::Error>> {
+    // Exports from other tools are often UTF-16 or use ';'
+    let (mut rdr, dialect) = dialect::open(file)?;
+    if !dialect.is_default() {
+        ui::info(&format!("Reading {} as {} with '{}' as delimiter", file.display(), dialect.encoding, dialect.delimiter as char));
+    }
+    let headers = rdr.headers()?.clone();
+
+    let mut columns = Vec::new();
+    for (key, column) in mapping {
+        let field = CSV_FIELDS.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, f)| *f)
+            .ok_or_else(|| format!("unknown mapping key '{}', expected one of timestamp, message, desc, user, host, path", key))?;
+        let index = headers.iter()
+            .position(|h| h == column)
+            .ok_or_else(|| format!("column '{}' not found in {}", column, file.display()))?;
+        columns.push((field, index));
+    }
+    let time_index = columns.iter()
+        .find(|(field, _)| *field == "Timestamp")
+        .map(|(_, i)| *i)
+        .ok_or("--map timestamp=<column> is required for CSV timelines")?;
+
+    let mut events = Vec::new();
+    let mut skipped = 0;
+    for record in rdr.records().filter_map(|r| r.ok()) {
+        let raw = record.get(time_index).unwrap_or("").trim();
+        let Some(timestamp) = parse_csv_time(raw, time_format, source_tz) else {
+            skipped += 1;
+            continue;
+        };
+
+        let mut event: Map<String, Value> = headers.iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.to_string(), Value::String(v.to_string())))
+            .collect();
+        for (field, index) in &columns {
+            event.insert(field.to_string(), Value::String(record.get(*index).unwrap_or("").to_string()));
+        }
+        event.insert("Timestamp".to_string(), Value::String(timestamp.to_rfc3339()));
+        event.insert("Timestamp_Raw".to_string(), Value::String(raw.to_string()));
+        events.push(event);
+    }
+    Ok((events, skipped))
+}
+
+// Times without an offset are in --source-tz, UTC if it isn't set
+fn parse_csv_time(value: &str, time_format: Option<&str>, source_tz: Option<Tz>) -> Option<DateTime<Utc>> {
+    if let Some(format) = time_format {
+        return NaiveDateTime::parse_from_str(value, format).ok().and_then(|t| timezone::local_to_utc(t, source_tz));
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.to_utc())
+        .or_else(|| refiner::parse_time(value).and_then(|t| timezone::local_to_utc(t, source_tz)))
+        // Plain numbers are epoch seconds
+        .or_else(|| value.parse::<i64>().ok().and_then(|s| DateTime::from_timestamp(s, 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn case_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracenexus-import-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn l2tcsv_timezone_column() {
+        let dir = case_dir("l2tcsv");
+        let file = dir.join("timeline.csv");
+        fs::write(&file, "date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra\n\
+            07/01/2024,12:00:00,Europe/Berlin,M...,FILE,OS:stat,mtime,-,web,passwd,changed,2,/etc/passwd,12,-,filestat,-\n\
+            07/01/2024,12:00:00,,.A..,FILE,OS:stat,atime,-,web,shadow,read,2,/etc/shadow,13,-,filestat,-\n\
+            2024-07-01,12:00:00,UTC,M...,FILE,OS:stat,mtime,-,web,x,x,2,/x,14,-,filestat,-\n").unwrap();
+
+        // The row's own zone wins, --source-tz only fills in where the column is empty
+        let (events, skipped) = read_l2tcsv(&file, Some(chrono_tz::America::New_York)).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(events[0]["Timestamp"], "2024-07-01T10:00:00+00:00");
+        assert_eq!(events[0]["Timestamp_Raw"], "07/01/2024 12:00:00");
+        assert_eq!(events[0]["Path"], "/etc/passwd");
+        assert_eq!(events[0]["Macb"], "M...");
+        assert_eq!(events[1]["Timestamp"], "2024-07-01T16:00:00+00:00");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn json_line_microseconds() {
+        let dir = case_dir("json-line");
+        let file = dir.join("timeline.jsonl");
+        fs::write(&file, "{\"timestamp\": 1719835200123456, \"timestamp_desc\": \"Last Visited Time\", \"message\": \"https://example.com\", \"parser\": \"chrome_history\", \"display_name\": \"OS:/home/bob/History\"}\n\
+            {\"datetime\": \"2024-07-01T12:00:00+02:00\", \"message\": \"no epoch\"}\n\
+            not json\n\
+            {\"message\": \"no time\"}\n").unwrap();
+
+        let (events, skipped) = read_json_line(&file).unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(events[0]["Timestamp"], "2024-07-01T12:00:00.123456+00:00");
+        assert_eq!(events[0]["TimestampDesc"], "Last Visited Time");
+        assert_eq!(events[0]["Path"], "OS:/home/bob/History");
+        assert_eq!(events[0]["Fields"]["parser"], "chrome_history");
+        assert_eq!(events[1]["Timestamp"], "2024-07-01T10:00:00+00:00");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn mapped_csv_with_time_format_and_zone() {
+        let dir = case_dir("csv");
+        let file = dir.join("firewall.csv");
+        fs::write(&file, "When;What;Who\n01.07.2024 12:00;blocked 10.0.0.5;svc\nyesterday;broken;svc\n").unwrap();
+        let mapping = vec![("timestamp".to_string(), "When".to_string()), ("message".to_string(), "What".to_string())];
+
+        let (events, skipped) = read_mapped_csv(&file, &mapping, Some("%d.%m.%Y %H:%M"), Some(chrono_tz::Europe::Berlin)).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(events[0]["Timestamp"], "2024-07-01T10:00:00+00:00");
+        assert_eq!(events[0]["Timestamp_Raw"], "01.07.2024 12:00");
+        assert_eq!(events[0]["Message"], "blocked 10.0.0.5");
+        // Unmapped columns stay as they are
+        assert_eq!(events[0]["Who"], "svc");
+
+        let unknown = vec![("timestamp".to_string(), "Missing".to_string())];
+        assert!(read_mapped_csv(&file, &unknown, None, None).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn import_merges_into_master_timeline() {
+        let dir = case_dir("merge");
+        let refined = dir.join("refined");
+        fs::create_dir_all(refined.join("Other")).unwrap();
+        fs::write(refined.join("Other/Custom.json"), r#"[{"Timestamp": "2024-07-01T09:00:00+00:00", "Note": "ours"}]"#).unwrap();
+        fs::write(refined.join("case_summary.json"), r#"{"case_id": "INC-TEST"}"#).unwrap();
+        let file = dir.join("plaso.jsonl");
+        fs::write(&file, "{\"timestamp\": 1719835200000000, \"message\": \"theirs\"}\n").unwrap();
+
+        let rules = crate::rules::load(Some(Path::new(crate::rules::RULES_FILE))).unwrap();
+        let options = ImportOptions {
+            format: &TimelineFormat::JsonLine,
+            label: Some("plaso run"),
+            mapping: &[],
+            time_format: None,
+            source_tz: None,
+            rules: &rules,
+            output_format: None,
+        };
+        import(&file, dir.to_str().unwrap(), &options).unwrap();
+
+        let timeline = refiner::read_json_records(&refined.join("master_timeline.json"));
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0]["data"]["Note"], "ours");
+        assert_eq!(timeline[1]["src"], "plaso_run.json");
+        assert_eq!(timeline[1]["cat"], IMPORT_CATEGORY);
+        assert_eq!(timeline[1]["data"]["Source"], "plaso run");
+        assert_eq!(timeline[1]["ts"], "2024-07-01T12:00:00+00:00");
+        // The existing case keeps its ID
+        assert!(dir.join("INC-TEST_refined.zip").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
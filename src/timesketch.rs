@@ -0,0 +1,304 @@
+use reqwest::blocking::{Client, multipart};
+use serde_json::{json, Map, Value};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::normalize;
+use crate::refiner;
+use crate::ui;
+
+// JSONL as Timesketch imports it, written next to master_timeline.json
+pub const TIMESKETCH_FILE: &str = "timesketch.jsonl";
+
+// Message per source, matched on the file name (substring, case-insensitive), first match wins.
+// The first template whose {Fields} are all set in the record is used.
+const MESSAGES: &[(&str, &[&str])] = &[
+    ("EvtxECmd", &["[{EventId}] {Channel}: {MapDescription} {PayloadData1}", "[{EventId}] {Channel}: {PayloadData1}", "[{EventId}] {Channel}"]),
+    ("MFTECmd", &["{ParentPath}\\{FileName}", "{Name} {UpdateReasons}", "{FileName}"]),
+    ("LECmd", &["{SourceFile} -> {LocalPath}", "{SourceFile} -> {NetworkPath}", "{SourceFile}"]),
+    ("PECmd", &["{ExecutableName} (run {RunCount} times)", "{ExecutableName}"]),
+    ("Amcache", &["{FullPath} (SHA1 {SHA1})", "{FullPath}", "{Name}"]),
+    ("AppCompat", &["{Path}"]),
+    ("RECmd_Batch", &["{Description}: {KeyPath}\\{ValueName} = {ValueData}", "{Description}: {KeyPath}", "{KeyPath}"]),
+    ("SrumECmd", &["{ExeInfo}"]),
+    ("RecycleBin", &["{UserName} deleted {OriginalPath}", "Deleted {OriginalPath}"]),
+    ("Browser", &["{Title} ({Url})", "{TargetPath} from {Url}", "{Url}"]),
+    ("Volatile_Connections", &["{Protocol} {LocalAddress} -> {RemoteAddress} {State} (PID {OwningPid})"]),
+    ("Linux_AuthLog", &["{Process}[{Pid}]: {Message}", "{Process}: {Message}"]),
+    ("Linux_Wtmp", &["{User} logged on at {Line} from {Host}", "{User} logged on at {Line}", "{Type} {Line}"]),
+    ("Linux_Btmp", &["Failed logon of {User} from {Host}", "Failed logon of {User}"]),
+    ("Linux_Lastlog", &["{User} last logged on from {Host}", "{User} last logged on"]),
+    ("Linux_ShellHistory", &["{User}: {Command}"]),
+    ("Linux_PackageLog", &["{Action}"]),
+    ("Container_", &["{Name} ({Image}) {Status}", "{Event}"]),
+];
+
+// Fields that read as a message on their own, for sources without a template
+const MESSAGE_FIELDS: &[&str] = &["Message", "Description", "Command", "FullPath", "Path", "FileName", "Name", "Url", "Entry", "Action"];
+
+// Set by the export itself, record fields with these names get a "data_" prefix
+const RESERVED: &[&str] = &["message", "datetime", "timestamp", "timestamp_desc", "data_type"];
+
+/// Writes refined/timesketch.jsonl from master_timeline.json. Events without a parseable time are left out,
+/// Timesketch can't place them.
+pub fn export(refined_path: &Path) -> Option<PathBuf> {
+    let events = refiner::read_json_records(&refined_path.join("master_timeline.json"));
+    let target = refined_path.join(TIMESKETCH_FILE);
+
+    let mut written = 0;
+    let mut skipped = 0;
+    let result = File::create(&target).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        for event in &events {
+            match timesketch_event(event) {
+                Some(line) => {
+                    serde_json::to_writer(&mut writer, &line)?;
+                    writer.write_all(b"\n")?;
+                    written += 1;
+                },
+                None => skipped += 1,
+            }
+        }
+        writer.flush()
+    });
+    if let Err(e) = result {
+        ui::error(&format!("Could not write {}: {}", TIMESKETCH_FILE, e));
+        return None;
+    }
+    if skipped > 0 {
+        ui::warn(&format!("{} timeline events without a parseable time are not in {}", skipped, TIMESKETCH_FILE));
+    }
+    ui::success(&format!("Wrote {} events to refined/{}", written, TIMESKETCH_FILE));
+    Some(target)
+}
+
+fn timesketch_event(event: &Value) -> Option<Value> {
+    let instant = refiner::parse_instant(event.get("ts")?.as_str()?)?;
+    let text = |key: &str| event.get(key).and_then(|v| v.as_str()).unwrap_or_default();
+    let source = text("src");
+    let data = event.get("data").and_then(|d| d.as_object()).cloned().unwrap_or_default();
+
+    // "event" is the fallback meaning, the field name says more then
+    let timestamp_desc = match text("ts_desc") {
+        "" | "event" => text("ts_field"),
+        desc => desc,
+    };
+    let mut line = json!({
+        "message": message(source, normalize::original(&data)),
+        "datetime": instant.to_rfc3339(),
+        "timestamp": instant.timestamp_micros(),
+        "timestamp_desc": timestamp_desc,
+        "data_type": format!("tracenexus:{}", source.trim_end_matches(".json").to_lowercase()),
+        "category": text("cat"),
+        "source": source,
+        "time_field": text("ts_field"),
+        "time_raw": text("ts_raw"),
+        "suspicious_time": event.get("suspicious_time").and_then(|v| v.as_bool()).unwrap_or(false),
+    });
+    if let Some(subcategory) = event.get("subcat") {
+        line["subcategory"] = subcategory.clone();
+    }
+    // Timesketch shows tags as labels on the event
+    if let Some(attack) = event.get("attack") {
+        line["tag"] = attack.clone();
+    }
+
+    let attributes = line.as_object_mut().unwrap();
+    let mut flat = Vec::new();
+    refiner::flatten_json("", &Value::Object(data), &mut flat);
+    for (key, value) in flat {
+        let key = if RESERVED.contains(&key.as_str()) || attributes.contains_key(&key) { format!("data_{}", key) } else { key };
+        attributes.entry(key).or_insert(value);
+    }
+    Some(line)
+}
+
+fn field_text(record: &Map<String, Value>, field: &str) -> Option<String> {
+    match record.get(field)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn render(template: &str, record: &Map<String, Value>) -> Option<String> {
+    let mut message = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        message.push_str(&rest[..start]);
+        message.push_str(&field_text(record, &rest[start + 1..end])?);
+        rest = &rest[end + 1..];
+    }
+    message.push_str(rest);
+    Some(message)
+}
+
+fn message(source: &str, record: &Map<String, Value>) -> String {
+    let lower = source.to_lowercase();
+    let templated = MESSAGES.iter()
+        .find(|(key, _)| lower.contains(&key.to_lowercase()))
+        .and_then(|(_, templates)| templates.iter().find_map(|t| render(t, record)));
+    templated
+        .or_else(|| MESSAGE_FIELDS.iter().find_map(|f| field_text(record, f)))
+        .unwrap_or_else(|| format!("{} record", source.trim_end_matches(".json")))
+}
+
+/// Imports timesketch.jsonl the way the Timesketch web UI and API client do: log in with a session and CSRF token,
+/// create a sketch unless TIMESKETCH_SKETCH_ID names one, then POST the file to /api/v1/upload/ into its own index.
+/// TIMESKETCH_URL, TIMESKETCH_USER and TIMESKETCH_PASSWORD come from .env like the server upload. The file goes
+/// in one request, the server's upload size limit has to allow it.
+pub fn upload(path: &Path, case_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    dotenvy::dotenv().ok();
+
+    let base_url = env::var("TIMESKETCH_URL").map_err(|_| "TIMESKETCH_URL not set in .env")?;
+    let base_url = base_url.trim_end_matches('/');
+    let user = env::var("TIMESKETCH_USER").map_err(|_| "TIMESKETCH_USER not set in .env")?;
+    let password = env::var("TIMESKETCH_PASSWORD").map_err(|_| "TIMESKETCH_PASSWORD not set in .env")?;
+
+    // Redirects are handled by hand, the session cookie comes with the redirect after the login
+    let client = Client::builder()
+        .timeout(std::time::Duration::from_secs(300))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    ui::info(&format!("Logging in to Timesketch at {}...", base_url));
+    let mut cookies = Vec::new();
+
+    let response = client.get(format!("{}/login/", base_url)).send()?;
+    remember_cookies(&mut cookies, &response);
+    let csrf_token = csrf_token(&response.text()?).ok_or("no CSRF token on the Timesketch login page")?;
+
+    let response = client.post(format!("{}/login/", base_url))
+        .header("Cookie", cookie_header(&cookies))
+        .header("X-CSRFToken", &csrf_token)
+        .header("Referer", base_url)
+        .form(&[("username", user.as_str()), ("password", password.as_str()), ("csrf_token", csrf_token.as_str())])
+        .send()?;
+    // A failed login shows the form again, a good one redirects to the start page
+    if !response.status().is_redirection() {
+        return Err("Timesketch login failed, check TIMESKETCH_USER and TIMESKETCH_PASSWORD".into());
+    }
+    remember_cookies(&mut cookies, &response);
+
+    let sketch_id = match env::var("TIMESKETCH_SKETCH_ID") {
+        Ok(sketch_id) => sketch_id,
+        Err(_) => {
+            let response = client.post(format!("{}/api/v1/sketches/", base_url))
+                .header("Cookie", cookie_header(&cookies))
+                .header("X-CSRFToken", &csrf_token)
+                .header("Referer", base_url)
+                .header("Content-Type", "application/json")
+                .body(json!({"name": case_id, "description": "Imported by TraceNexus"}).to_string())
+                .send()?;
+            if !response.status().is_success() {
+                return Err(format!("Timesketch could not create a sketch: {}", response.status()).into());
+            }
+            let created: Value = serde_json::from_str(&response.text()?)?;
+            created["objects"][0]["id"].as_u64().ok_or("Timesketch did not return the new sketch id")?.to_string()
+        },
+    };
+
+    // Index names are lower case, one per upload so a second run doesn't mix into the first
+    let index_name = format!(
+        "tracenexus_{}_{}",
+        case_id.to_lowercase().chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>(),
+        chrono::Utc::now().format("%Y%m%d%H%M%S"),
+    );
+    let form = multipart::Form::new()
+        .text("name", case_id.to_string())
+        .text("sketch_id", sketch_id.clone())
+        .text("index_name", index_name)
+        .text("provider", "TraceNexus")
+        .text("data_label", "jsonl")
+        .text("total_file_size", fs::metadata(path)?.len().to_string())
+        .file("file", path)?;
+    ui::info(&format!("Sending timeline to sketch {}...", sketch_id));
+
+    let response = client.post(format!("{}/api/v1/upload/", base_url))
+        .header("Cookie", cookie_header(&cookies))
+        .header("X-CSRFToken", &csrf_token)
+        .header("Referer", base_url)
+        .multipart(form)
+        .send()?;
+    if !response.status().is_success() {
+        return Err(format!("Timesketch returned {}", response.status()).into());
+    }
+    ui::success(&format!("Timeline of case {} imported into Timesketch sketch {}.", case_id, sketch_id));
+    Ok(())
+}
+
+// <input id="csrf_token" name="csrf_token" type="hidden" value="...">
+fn csrf_token(html: &str) -> Option<String> {
+    let input = &html[html.find("name=\"csrf_token\"")?..];
+    let value = &input[input.find("value=\"")? + 7..];
+    Some(value[..value.find('"')?].to_string())
+}
+
+// Just name=value, the session cookie is all Timesketch needs
+fn remember_cookies(cookies: &mut Vec<(String, String)>, response: &reqwest::blocking::Response) {
+    for header in response.headers().get_all("set-cookie") {
+        let Some((name, value)) = header.to_str().ok()
+            .and_then(|h| h.split(';').next())
+            .and_then(|pair| pair.split_once('='))
+        else {
+            continue;
+        };
+        cookies.retain(|(n, _)| n != name.trim());
+        cookies.push((name.trim().to_string(), value.trim().to_string()));
+    }
+}
+
+fn cookie_header(cookies: &[(String, String)]) -> String {
+    cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_templates() {
+        let record = |value: Value| value.as_object().unwrap().clone();
+        // first template with all fields set wins, empty strings count as missing
+        let evtx = record(json!({"EventId": 4624, "Channel": "Security", "MapDescription": " ", "PayloadData1": "Target: root"}));
+        assert_eq!(message("20240301_EvtxECmd_Output.json", &evtx), "[4624] Security: Target: root");
+        assert_eq!(message("RecycleBin.json", &record(json!({"OriginalPath": "C:\\a.txt"}))), "Deleted C:\\a.txt");
+        // no template for the source, a message-like field or the source name
+        assert_eq!(message("Other.json", &record(json!({"Command": "whoami", "Url": "x"}))), "whoami");
+        assert_eq!(message("Other.json", &record(json!({"Flag": true}))), "Other record");
+    }
+
+    #[test]
+    fn events_with_flattened_attributes() {
+        let event = json!({
+            "ts": "2024-03-01T10:00:00Z", "ts_field": "TimeCreated", "ts_desc": "event", "src": "Linux_AuthLog.json", "cat": "Logs",
+            "attack": ["T1078"],
+            "data": {"Process": "sshd", "Pid": 42, "Message": "Accepted", "message": "raw", "category": "auth",
+                     "host": {"name": "web01", "ip": null}, "tags": ["a", "b"]},
+        });
+        let line = timesketch_event(&event).unwrap();
+        assert_eq!(line["message"], "sshd[42]: Accepted");
+        assert_eq!(line["timestamp"], 1_709_287_200_000_000i64);
+        assert_eq!(line["timestamp_desc"], "TimeCreated");
+        assert_eq!(line["data_type"], "tracenexus:linux_authlog");
+        assert_eq!(line["tag"], json!(["T1078"]));
+        // reserved names and our own attributes keep the record's value under data_
+        assert_eq!(line["data_message"], "raw");
+        assert_eq!(line["category"], "Logs");
+        assert_eq!(line["data_category"], "auth");
+        assert_eq!(line["host.name"], "web01");
+        assert!(line.get("host.ip").is_none());
+        assert_eq!(line["tags"], json!(["a", "b"]));
+
+        assert!(timesketch_event(&json!({"ts": "garbage", "data": {}})).is_none());
+    }
+
+    #[test]
+    fn csrf_token_from_login_page() {
+        let html = r#"<form><input id="csrf_token" name="csrf_token" type="hidden" value="IjM1.abc"></form>"#;
+        assert_eq!(csrf_token(html).as_deref(), Some("IjM1.abc"));
+        assert!(csrf_token("<form></form>").is_none());
+    }
+}
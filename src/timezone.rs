@@ -0,0 +1,266 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fs;
+use std::path::Path;
+use crate::dialect;
+use crate::ui;
+
+// Written to refined/, which zone was used and which sources were in local time
+pub const TIME_ZONE_FILE: &str = "time_zone.json";
+
+// TimeZoneKeyName (HKLM\SYSTEM\...\TimeZoneInformation) to IANA, the territory "001" entries of CLDR's
+// windowsZones.xml. Etc/GMT+N is UTC-N.
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Aleutian Standard Time", "America/Adak"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Marquesas Standard Time", "Pacific/Marquesas"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("UTC-09", "Etc/GMT+9"),
+    ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+    ("UTC-08", "Etc/GMT+8"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Yukon Standard Time", "America/Whitehorse"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Easter Island Standard Time", "Pacific/Easter"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time (Mexico)", "America/Cancun"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("Haiti Standard Time", "America/Port-au-Prince"),
+    ("Cuba Standard Time", "America/Havana"),
+    ("US Eastern Standard Time", "America/Indianapolis"),
+    ("Turks And Caicos Standard Time", "America/Grand_Turk"),
+    ("Paraguay Standard Time", "America/Asuncion"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("Venezuela Standard Time", "America/Caracas"),
+    ("Central Brazilian Standard Time", "America/Cuiaba"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Pacific SA Standard Time", "America/Santiago"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("Tocantins Standard Time", "America/Araguaina"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("SA Eastern Standard Time", "America/Cayenne"),
+    ("Argentina Standard Time", "America/Buenos_Aires"),
+    ("Greenland Standard Time", "America/Godthab"),
+    ("Montevideo Standard Time", "America/Montevideo"),
+    ("Magallanes Standard Time", "America/Punta_Arenas"),
+    ("Saint Pierre Standard Time", "America/Miquelon"),
+    ("Bahia Standard Time", "America/Bahia"),
+    ("UTC-02", "Etc/GMT+2"),
+    ("Azores Standard Time", "Atlantic/Azores"),
+    ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("Sao Tome Standard Time", "Africa/Sao_Tome"),
+    ("Morocco Standard Time", "Africa/Casablanca"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("Jordan Standard Time", "Asia/Amman"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("Middle East Standard Time", "Asia/Beirut"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("Syria Standard Time", "Asia/Damascus"),
+    ("West Bank Standard Time", "Asia/Hebron"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("FLE Standard Time", "Europe/Kiev"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Sudan Standard Time", "Africa/Juba"),
+    ("Kaliningrad Standard Time", "Europe/Kaliningrad"),
+    ("Sudan Standard Time", "Africa/Khartoum"),
+    ("Libya Standard Time", "Africa/Tripoli"),
+    ("Namibia Standard Time", "Africa/Windhoek"),
+    ("Arabic Standard Time", "Asia/Baghdad"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Belarus Standard Time", "Europe/Minsk"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("E. Africa Standard Time", "Africa/Nairobi"),
+    ("Volgograd Standard Time", "Europe/Volgograd"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Astrakhan Standard Time", "Europe/Astrakhan"),
+    ("Azerbaijan Standard Time", "Asia/Baku"),
+    ("Russia Time Zone 3", "Europe/Samara"),
+    ("Mauritius Standard Time", "Indian/Mauritius"),
+    ("Saratov Standard Time", "Europe/Saratov"),
+    ("Georgian Standard Time", "Asia/Tbilisi"),
+    ("Caucasus Standard Time", "Asia/Yerevan"),
+    ("Afghanistan Standard Time", "Asia/Kabul"),
+    ("West Asia Standard Time", "Asia/Tashkent"),
+    ("Ekaterinburg Standard Time", "Asia/Yekaterinburg"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("Qyzylorda Standard Time", "Asia/Qyzylorda"),
+    ("India Standard Time", "Asia/Calcutta"),
+    ("Sri Lanka Standard Time", "Asia/Colombo"),
+    ("Nepal Standard Time", "Asia/Katmandu"),
+    ("Central Asia Standard Time", "Asia/Bishkek"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("Omsk Standard Time", "Asia/Omsk"),
+    ("Myanmar Standard Time", "Asia/Rangoon"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("Altai Standard Time", "Asia/Barnaul"),
+    ("W. Mongolia Standard Time", "Asia/Hovd"),
+    ("North Asia Standard Time", "Asia/Krasnoyarsk"),
+    ("N. Central Asia Standard Time", "Asia/Novosibirsk"),
+    ("Tomsk Standard Time", "Asia/Tomsk"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("North Asia East Standard Time", "Asia/Irkutsk"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("Ulaanbaatar Standard Time", "Asia/Ulaanbaatar"),
+    ("Aus Central W. Standard Time", "Australia/Eucla"),
+    ("Transbaikal Standard Time", "Asia/Chita"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("North Korea Standard Time", "Asia/Pyongyang"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Yakutsk Standard Time", "Asia/Yakutsk"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("Vladivostok Standard Time", "Asia/Vladivostok"),
+    ("Lord Howe Standard Time", "Australia/Lord_Howe"),
+    ("Bougainville Standard Time", "Pacific/Bougainville"),
+    ("Russia Time Zone 10", "Asia/Srednekolymsk"),
+    ("Magadan Standard Time", "Asia/Magadan"),
+    ("Norfolk Standard Time", "Pacific/Norfolk"),
+    ("Sakhalin Standard Time", "Asia/Sakhalin"),
+    ("Central Pacific Standard Time", "Pacific/Guadalcanal"),
+    ("Russia Time Zone 11", "Asia/Kamchatka"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("UTC+12", "Etc/GMT-12"),
+    ("Fiji Standard Time", "Pacific/Fiji"),
+    ("Chatham Islands Standard Time", "Pacific/Chatham"),
+    ("UTC+13", "Etc/GMT-13"),
+    ("Tonga Standard Time", "Pacific/Tongatapu"),
+    ("Samoa Standard Time", "Pacific/Apia"),
+    ("Line Islands Standard Time", "Pacific/Kiritimati"),
+];
+
+/// Accepts an IANA name ("Europe/Berlin") or a Windows key name ("W. Europe Standard Time").
+pub fn parse_zone(name: &str) -> Option<Tz> {
+    let name = name.trim();
+    name.parse::<Tz>().ok().or_else(|| {
+        WINDOWS_ZONES.iter()
+            .find(|(windows, _)| windows.eq_ignore_ascii_case(name))
+            .and_then(|(_, iana)| iana.parse().ok())
+    })
+}
+
+/// Zone the source host's local times are in: --source-tz, else the collected TimeZoneInformation
+/// key, else the Linux root's configuration. None means UTC, together with where the answer came from.
+pub fn detect(source_tz: Option<Tz>, out_dir: &Path, linux_root: Option<&Path>) -> (Option<Tz>, &'static str) {
+    if source_tz.is_some() {
+        return (source_tz, "--source-tz");
+    }
+    if let Some(zone) = windows_zone(out_dir) {
+        return (Some(zone), "TimeZoneInformation");
+    }
+    if let Some(zone) = linux_root.and_then(linux_zone) {
+        return (Some(zone), "/etc/timezone");
+    }
+    (None, "none found, assuming UTC")
+}
+
+/// Interprets a naive time in `zone`, None means the value already is UTC.
+/// Times in the DST gap don't exist locally and are dropped, ambiguous ones take the earlier instant.
+pub fn local_to_utc(naive: NaiveDateTime, zone: Option<Tz>) -> Option<DateTime<Utc>> {
+    match zone {
+        Some(zone) => zone.from_local_datetime(&naive).earliest().map(|dt| dt.to_utc()),
+        None => Some(naive.and_utc()),
+    }
+}
+
+/// Configured zone of a Linux root: /etc/timezone (Debian) or the /etc/localtime symlink target.
+pub fn linux_zone(root: &Path) -> Option<Tz> {
+    if let Ok(name) = fs::read_to_string(root.join("etc/timezone"))
+        && let Some(zone) = parse_zone(&name)
+    {
+        return Some(zone);
+    }
+    // e.g. /etc/localtime -> ../usr/share/zoneinfo/Europe/Berlin
+    let target = fs::read_link(root.join("etc/localtime")).ok()?;
+    let target = target.to_string_lossy();
+    let (_, name) = target.split_once("zoneinfo/")?;
+    parse_zone(name)
+}
+
+/// Reads TimeZoneKeyName from the TimeZoneInformation rows of the RECmd output in `out_dir`.
+pub fn windows_zone(out_dir: &Path) -> Option<Tz> {
+    for entry in fs::read_dir(out_dir).ok()?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let registry_output = file_name.contains("RECmd_Batch") || file_name.contains("TimeZoneInfo");
+        if !registry_output || !file_name.ends_with(".csv") {
+            continue;
+        }
+        let Ok((mut rdr, _)) = dialect::open(&entry.path()) else {
+            continue;
+        };
+        let Ok(headers) = rdr.headers().cloned() else {
+            continue;
+        };
+        let col = |name: &str| headers.iter().position(|h| h == name);
+        let (Some(key_col), Some(name_col), Some(data_col)) = (col("KeyPath"), col("ValueName"), col("ValueData")) else {
+            continue;
+        };
+
+        for record in rdr.records().filter_map(|r| r.ok()) {
+            if !record.get(key_col).unwrap_or("").contains("TimeZoneInformation")
+                || record.get(name_col) != Some("TimeZoneKeyName")
+            {
+                continue;
+            }
+            let name = record.get(data_col).unwrap_or("");
+            match parse_zone(name) {
+                Some(zone) => return Some(zone),
+                None => ui::warn(&format!("Unknown Windows time zone '{}' in {}, pass --source-tz", name.trim(), file_name)),
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_windows_zone_is_known() {
+        assert!(WINDOWS_ZONES.len() > 130);
+        for (windows, iana) in WINDOWS_ZONES {
+            assert!(iana.parse::<Tz>().is_ok(), "{} -> {}", windows, iana);
+        }
+        assert_eq!(parse_zone("w. europe standard time"), Some(chrono_tz::Europe::Berlin));
+        assert_eq!(parse_zone("Dateline Standard Time"), Some(chrono_tz::Etc::GMTPlus12));
+        assert_eq!(parse_zone("Asia/Tokyo"), Some(chrono_tz::Asia::Tokyo));
+        assert_eq!(parse_zone("Mars Standard Time"), None);
+    }
+
+    #[test]
+    fn zone_from_registry_output() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-timezone-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("20240101_RECmd_Batch_Output.csv");
+        let row = |data: &str| format!("SYSTEM,ControlSet001\\Control\\TimeZoneInformation,TimeZoneKeyName,{}\n", data);
+        fs::write(&csv, format!("HiveType,KeyPath,ValueName,ValueData\n{}", row("Mars Standard Time"))).unwrap();
+        assert_eq!(windows_zone(&dir), None);
+        fs::write(&csv, format!("HiveType,KeyPath,ValueName,ValueData\n{}", row("Tokyo Standard Time"))).unwrap();
+        assert_eq!(windows_zone(&dir), Some(chrono_tz::Asia::Tokyo));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 // src/tools.rs
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::ui;
 
 pub const REQUIRED_TOOLS: &[&str] = &[
@@ -9,13 +9,16 @@ pub const REQUIRED_TOOLS: &[&str] = &[
     "tools/EvtxECmd/EvtxECmd.exe", 
     "tools/RECmd/RECmd.exe",
     "tools/MFTECmd.exe",       
+    "tools/LECmd.exe",
+    "tools/JLECmd.exe",
+    "tools/SrumECmd.exe",
 ];
 
 pub fn verify_tools() -> Result<(), Vec<String>> {
     let mut missing = Vec::new();
 
     for tool in REQUIRED_TOOLS {
-        if !std::path::Path::new(tool).exists() {
+        if !tool_exists(Path::new(tool)) {
             missing.push(tool.to_string());
         }
     }
@@ -27,6 +30,21 @@ pub fn verify_tools() -> Result<(), Vec<String>> {
     }
 }
 
+// Outside Windows (e.g. processing an image on Linux) the EZ tools have to be the .NET builds
+fn tool_exists(tool: &Path) -> bool {
+    tool.exists() || (!cfg!(windows) && tool.with_extension("dll").exists())
+}
+
+/// Returns the program and leading arguments to start a tool with: the .exe itself on Windows,
+/// `dotnet <tool>.dll` everywhere else.
+pub fn launcher(executable: &Path) -> (PathBuf, Vec<String>) {
+    let dll = executable.with_extension("dll");
+    if !cfg!(windows) && let Ok(dll) = fs::canonicalize(&dll) {
+        return (PathBuf::from("dotnet"), vec![dll.to_string_lossy().to_string()]);
+    }
+    (executable.to_path_buf(), Vec::new())
+}
+
 pub fn unblock_tools() {
     ui::info("Optimization: Unblocking forensic tools...");
     
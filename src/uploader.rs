@@ -40,7 +40,7 @@ pub fn upload_package(zip_path: &str, incident_id: &str) -> Result<(), Box<dyn s
         },
         Err(e) => {
             if e.is_connect() || e.is_timeout() {
-                ui::error(&format!("ERROR: Server is not reachable! (Check your VPN/Internet or if the Pi is online)"));
+                ui::error("ERROR: Server is not reachable! (Check your VPN/Internet or if the Pi is online)");
             } else {
                 ui::error(&format!("An unexpected network error occurred: {}", e));
             }
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::dialect;
+use crate::ui;
+
+pub const PROFILES_FILE: &str = "user_profiles.json";
+
+const PROFILE_LIST_KEY: &str = "HKLM\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\ProfileList";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserProfile {
+    pub sid: String,
+    pub username: String,
+    pub profile_path: String,
+}
+
+impl UserProfile {
+    pub fn ntuser_hive(&self) -> PathBuf {
+        Path::new(&self.profile_path).join("NTUSER.DAT")
+    }
+
+    pub fn usrclass_hive(&self) -> PathBuf {
+        Path::new(&self.profile_path).join("AppData/Local/Microsoft/Windows/UsrClass.dat")
+    }
+
+    /// Holds the LNK files plus the AutomaticDestinations and CustomDestinations folders.
+    pub fn recent_dir(&self) -> PathBuf {
+        Path::new(&self.profile_path).join("AppData/Roaming/Microsoft/Windows/Recent")
+    }
+
+    /// SID in output file names keeps them unique and avoids category clashes with usernames
+    pub fn file_id(&self) -> String {
+        if self.sid.is_empty() { self.username.clone() } else { self.sid.clone() }
+    }
+}
+
+/// Enumerates all user profiles on the host, or below `system_root` for an offline volume.
+/// ProfileList is the primary source because it gives us the SID, C:\Users is the fallback.
+pub fn enumerate_profiles(system_root: Option<&Path>) -> Vec<UserProfile> {
+    // Offline the SIDs are filled in by resolve_offline_sids once RECmd has parsed the SOFTWARE hive
+    let mut profiles = match system_root {
+        Some(root) => scan_users_dir(&root.join("Users")),
+        None => read_profile_list(),
+    };
+
+    if profiles.is_empty() && system_root.is_none() {
+        ui::warn("ProfileList not readable, falling back to C:\\Users");
+        profiles = scan_users_dir(Path::new("C:\\Users"));
+    }
+
+    // Only profiles that actually have a hive on disk are interesting
+    profiles.retain(|p| p.ntuser_hive().exists());
+    profiles
+}
+
+fn read_profile_list() -> Vec<UserProfile> {
+    let output = std::process::Command::new("reg")
+        .args(["query", PROFILE_LIST_KEY, "/s"])
+        .output();
+
+    let Ok(out) = output else {
+        return Vec::new();
+    };
+
+    parse_reg_query(&String::from_utf8_lossy(&out.stdout))
+}
+
+// reg.exe prints the key path followed by indented "Name    TYPE    Data" lines
+fn parse_reg_query(text: &str) -> Vec<UserProfile> {
+    let mut profiles = Vec::new();
+    let mut current_sid = String::new();
+
+    for line in text.lines() {
+        if line.starts_with("HKEY_") {
+            current_sid = line.rsplit('\\').next().unwrap_or("").trim().to_string();
+            continue;
+        }
+
+        let line = line.trim();
+        if !line.starts_with("ProfileImagePath") || !current_sid.starts_with("S-1-5-") {
+            continue;
+        }
+
+        if let Some((_, data)) = line.split_once("REG_EXPAND_SZ").or_else(|| line.split_once("REG_SZ")) {
+            let profile_path = expand_env(data.trim());
+            let username = profile_path.rsplit('\\').next().unwrap_or("").to_string();
+            profiles.push(UserProfile {
+                sid: current_sid.clone(),
+                username,
+                profile_path,
+            });
+        }
+    }
+    profiles
+}
+
+fn scan_users_dir(users_dir: &Path) -> Vec<UserProfile> {
+    let mut profiles = Vec::new();
+    if let Ok(entries) = fs::read_dir(users_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                profiles.push(UserProfile {
+                    sid: String::new(),
+                    username: entry.file_name().to_string_lossy().to_string(),
+                    profile_path: path.to_string_lossy().to_string(),
+                });
+            }
+        }
+    }
+    profiles
+}
+
+// ProfileImagePath often contains %SystemDrive% or %SystemRoot%
+fn expand_env(value: &str) -> String {
+    let mut result = value.to_string();
+    for var in ["SystemDrive", "SystemRoot", "windir"] {
+        let placeholder = format!("%{}%", var);
+        if let Some(pos) = result.to_lowercase().find(&placeholder.to_lowercase()) {
+            let replacement = std::env::var(var).unwrap_or_else(|_| match var {
+                "SystemDrive" => "C:".to_string(),
+                _ => "C:\\Windows".to_string(),
+            });
+            result.replace_range(pos..pos + placeholder.len(), &replacement);
+        }
+    }
+    result
+}
+
+/// SID -> username from the ProfileList rows of the RECmd batch CSVs in `out_dir`.
+pub fn profile_list_sids(out_dir: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let Ok(entries) = fs::read_dir(out_dir) else {
+        return map;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.contains("RECmd_Batch") || !file_name.ends_with(".csv") {
+            continue;
+        }
+
+        let Ok((mut rdr, _)) = dialect::open(&path) else {
+            continue;
+        };
+        let Ok(headers) = rdr.headers().cloned() else {
+            continue;
+        };
+        let col = |name: &str| headers.iter().position(|h| h == name);
+        let (Some(key_col), Some(name_col), Some(data_col)) = (col("KeyPath"), col("ValueName"), col("ValueData")) else {
+            continue;
+        };
+
+        for record in rdr.records().filter_map(|r| r.ok()) {
+            let key_path = record.get(key_col).unwrap_or("");
+            if !key_path.contains("ProfileList") || record.get(name_col) != Some("ProfileImagePath") {
+                continue;
+            }
+            let sid = key_path.rsplit('\\').next().unwrap_or("").to_string();
+            let username = record.get(data_col).unwrap_or("").rsplit('\\').next().unwrap_or("").to_string();
+            if sid.starts_with("S-1-") && !username.is_empty() {
+                map.insert(sid, username);
+            }
+        }
+    }
+    map
+}
+
+/// Profiles found below Users\ of an offline volume have no SID, it's looked up by folder name in ProfileList.
+pub fn resolve_offline_sids(out_dir: &str, profiles: &mut [UserProfile]) {
+    let by_name: HashMap<String, String> = profile_list_sids(out_dir)
+        .into_iter()
+        .map(|(sid, username)| (username.to_lowercase(), sid))
+        .collect();
+
+    for profile in profiles.iter_mut().filter(|p| p.sid.is_empty()) {
+        if let Some(sid) = by_name.get(&profile.username.to_lowercase()) {
+            profile.sid = sid.clone();
+        }
+    }
+    let missing: Vec<&str> = profiles.iter().filter(|p| p.sid.is_empty()).map(|p| p.username.as_str()).collect();
+    if !missing.is_empty() {
+        ui::warn(&format!("No ProfileList SID for {}, their records are tagged by username only", missing.join(", ")));
+    }
+}
+
+/// Stores the profile list in the refined directory so the refiner can tag per-user records.
+pub fn save_profiles(out_dir: &str, profiles: &[UserProfile]) {
+    let refined_path = Path::new(out_dir).join("refined");
+    fs::create_dir_all(&refined_path).ok();
+    let _ = fs::write(refined_path.join(PROFILES_FILE), serde_json::to_string(profiles).unwrap());
+}
+
+pub fn load_profiles(refined_path: &Path) -> Vec<UserProfile> {
+    fs::read_to_string(refined_path.join(PROFILES_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+// Columns that hold the path of the artifact itself (RECmd hives, LECmd/JLECmd source files)
+const SOURCE_PATH_KEYS: &[&str] = &["HivePath", "SourceFile"];
+
+/// Adds the owning user to a record if its source artifact lives inside a known profile.
+pub fn tag_owner(map: &mut Map<String, Value>, profiles: &[UserProfile]) {
+    let Some(source_path) = SOURCE_PATH_KEYS.iter().find_map(|k| map.get(*k).and_then(|v| v.as_str())) else {
+        return;
+    };
+    // Profiles extracted from an image mix '/' and '\\' depending on the tool that wrote the path
+    let source_path = source_path.to_lowercase().replace('/', "\\");
+
+    let owner = profiles.iter().find(|p| {
+        let profile_path = p.profile_path.to_lowercase().replace('/', "\\");
+        let prefix = format!("{}\\", profile_path.trim_end_matches('\\'));
+        source_path.starts_with(&prefix)
+    });
+
+    if let Some(profile) = owner {
+        map.insert("UserName".to_string(), Value::String(profile.username.clone()));
+        map.insert("UserSid".to_string(), Value::String(profile.sid.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_sids_from_profile_list_rows() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-users-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("20240101_RECmd_Batch_DFIRBatch_Output.csv"), "\
+HivePath,KeyPath,ValueName,ValueData
+SOFTWARE,ROOT\\Microsoft\\Windows NT\\CurrentVersion\\ProfileList\\S-1-5-21-1-2-3-1001,ProfileImagePath,C:\\Users\\Bob
+SOFTWARE,ROOT\\Microsoft\\Windows NT\\CurrentVersion\\ProfileList\\S-1-5-18,ProfileImagePath,%systemroot%\\system32\\config\\systemprofile
+").unwrap();
+
+        let profile = |name: &str| UserProfile { sid: String::new(), username: name.to_string(), profile_path: format!("/mnt/Users/{}", name) };
+        let mut profiles = vec![profile("bob"), profile("alice")];
+        resolve_offline_sids(dir.to_str().unwrap(), &mut profiles);
+        assert_eq!(profiles[0].sid, "S-1-5-21-1-2-3-1001");
+        assert_eq!(profiles[1].sid, "");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+// glibc struct utmp on 64-bit Linux, used by utmp, wtmp and btmp
+const RECORD_SIZE: usize = 384;
+
+const USER_PROCESS: i16 = 7;
+const DEAD_PROCESS: i16 = 8;
+const BOOT_TIME: i16 = 2;
+
+fn record_type(ut_type: i16) -> &'static str {
+    match ut_type {
+        1 => "RUN_LVL",
+        BOOT_TIME => "BOOT_TIME",
+        5 => "INIT_PROCESS",
+        6 => "LOGIN_PROCESS",
+        USER_PROCESS => "USER_PROCESS",
+        DEAD_PROCESS => "DEAD_PROCESS",
+        _ => "OTHER",
+    }
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// Parses a utmp/wtmp/btmp file into JSON records. Empty slots are skipped.
+pub fn parse(data: &[u8]) -> Vec<Value> {
+    let mut records = Vec::new();
+
+    for chunk in data.chunks_exact(RECORD_SIZE) {
+        let ut_type = i16::from_le_bytes([chunk[0], chunk[1]]);
+        if ut_type == 0 {
+            continue;
+        }
+
+        let pid = i32::from_le_bytes(chunk[4..8].try_into().unwrap());
+        let tv_sec = i32::from_le_bytes(chunk[340..344].try_into().unwrap());
+        let tv_usec = i32::from_le_bytes(chunk[344..348].try_into().unwrap());
+
+        let time = DateTime::from_timestamp(tv_sec as i64, (tv_usec.clamp(0, 999_999) as u32) * 1000)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+
+        records.push(json!({
+            "Type": record_type(ut_type),
+            "Pid": pid,
+            "Line": c_string(&chunk[8..40]),
+            "User": c_string(&chunk[44..76]),
+            "Host": c_string(&chunk[76..332]),
+            "LoginTime": time,
+        }));
+    }
+    records
+}
+
+/// Only the currently logged on sessions (USER_PROCESS entries).
+pub fn active_sessions(data: &[u8]) -> Vec<Value> {
+    parse(data)
+        .into_iter()
+        .filter(|r| r["Type"] == "USER_PROCESS")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ut_type: i16, line: &str, user: &str, host: &str, tv_sec: i32) -> Vec<u8> {
+        let mut record = vec![0u8; RECORD_SIZE];
+        record[0..2].copy_from_slice(&ut_type.to_le_bytes());
+        record[4..8].copy_from_slice(&4711i32.to_le_bytes());
+        record[8..8 + line.len()].copy_from_slice(line.as_bytes());
+        record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+        record[76..76 + host.len()].copy_from_slice(host.as_bytes());
+        record[340..344].copy_from_slice(&tv_sec.to_le_bytes());
+        record[344..348].copy_from_slice(&250_000i32.to_le_bytes());
+        record
+    }
+
+    #[test]
+    fn parses_records() {
+        let data = [
+            record(BOOT_TIME, "~", "reboot", "6.1.0", 1_700_000_000),
+            vec![0u8; RECORD_SIZE],
+            record(USER_PROCESS, "pts/0", "bob", "10.0.0.5", 1_700_000_100),
+            record(DEAD_PROCESS, "pts/0", "", "", 1_700_000_200),
+            // Trailing partial record
+            vec![7u8; 100],
+        ].concat();
+
+        let records = parse(&data);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["Type"], "BOOT_TIME");
+        assert_eq!(records[1]["User"], "bob");
+        assert_eq!(records[1]["Line"], "pts/0");
+        assert_eq!(records[1]["Host"], "10.0.0.5");
+        assert_eq!(records[1]["Pid"], 4711);
+        assert_eq!(records[1]["LoginTime"], "2023-11-14T22:15:00.250+00:00");
+
+        let active = active_sessions(&data);
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0]["User"], "bob");
+    }
+}
@@ -0,0 +1,173 @@
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use crate::profiles;
+use crate::ui;
+
+/// Imports a Velociraptor offline collection ZIP: results/*.json become refined records,
+/// uploads/ are extracted below raw/ and run through the same Windows pipeline as a live host.
+pub fn import(zip_path: &Path, out_dir: &str, light: bool) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(zip_path)?)?;
+    let upload_root = fs::canonicalize(out_dir)?.join("raw").join("velociraptor");
+
+    let mut artifacts = Map::new();
+    // Lines that weren't valid JSON per result set, so the loss shows up in the case summary
+    let mut skipped_lines = Map::new();
+    let mut uploads = 0;
+    let mut client_info = Value::Null;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+
+        if let Some(upload) = name.strip_prefix("uploads/") {
+            let Some(relative) = upload_path(upload) else {
+                ui::warn(&format!("Skipping upload with unsafe path: {}", name));
+                continue;
+            };
+            let target = upload_root.join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&target)?)?;
+            uploads += 1;
+        } else if let Some(result) = name.strip_prefix("results/").and_then(|r| r.strip_suffix(".json")) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            let artifact = decode_component(result).replace('/', "_");
+            let (count, skipped) = write_results(out_dir, &artifact, &content);
+            if skipped > 0 {
+                ui::warn(&format!("{}: {} lines are not valid JSON and were skipped", artifact, skipped));
+                skipped_lines.insert(artifact.clone(), json!(skipped));
+            }
+            artifacts.insert(artifact, json!(count));
+        } else if name == "client_info.json" {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            client_info = serde_json::from_str(&content).unwrap_or_else(|e| {
+                ui::warn(&format!("client_info.json is not valid JSON: {}", e));
+                Value::Null
+            });
+        }
+    }
+    ui::success(&format!("Velociraptor: {} result sets, {} uploaded files", artifacts.len(), uploads));
+
+    // Raw uploads go through our own parsers when they contain a Windows system drive
+    match find_system_root(&upload_root) {
+        Some(system_root) if light => profiles::run_light(out_dir, Some(&system_root)),
+        Some(system_root) => profiles::run_full(out_dir, Some(&system_root)),
+        None => ui::info("No Windows system drive in the uploads, only the result sets are refined."),
+    }
+
+    Ok(json!({
+        "type": "Velociraptor",
+        "path": zip_path.to_string_lossy(),
+        "client_info": client_info,
+        "uploaded_files": uploads,
+        "artifacts": artifacts,
+        "skipped_lines": skipped_lines,
+    }))
+}
+
+/// Writes one result set as Velociraptor_<artifact>.json (JSON array). Returns the row count and
+/// how many lines were skipped because they weren't valid JSON.
+fn write_results(out_dir: &str, artifact: &str, content: &str) -> (usize, usize) {
+    let mut skipped = 0;
+    // Offline collectors write JSONL, older exports a plain array
+    let rows: Vec<Value> = if content.trim_start().starts_with('[') {
+        // A broken array (e.g. truncated upload) loses the whole set
+        serde_json::from_str(content).unwrap_or_else(|_| {
+            skipped = content.lines().filter(|l| !l.trim().is_empty()).count();
+            Vec::new()
+        })
+    } else {
+        content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                let row = serde_json::from_str(line).ok();
+                if row.is_none() {
+                    skipped += 1;
+                }
+                row
+            })
+            .collect()
+    };
+
+    let target = Path::new(out_dir).join(format!("Velociraptor_{}.json", artifact));
+    let _ = fs::write(target, serde_json::to_string(&rows).unwrap());
+    (rows.len(), skipped)
+}
+
+// Velociraptor escapes path components with %XX (e.g. "C%3A", "%5C%5C.%5CC%3A")
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = component.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Maps "<accessor>/C%3A/Windows/..." or "ntfs/%5C%5C.%5CC%3A/..." to "C/Windows/...".
+fn upload_path(upload: &str) -> Option<PathBuf> {
+    let mut components = upload.split('/').skip(1).map(decode_component);
+    let device = components.next()?;
+
+    // Device is "C:" or "\\.\C:", everything else (shadow copies, ...) keeps a sanitized name
+    let drive = match device.trim_end_matches(':').chars().last() {
+        Some(letter) if device.ends_with(':') && letter.is_ascii_alphabetic() => letter.to_ascii_uppercase().to_string(),
+        _ => device.replace(['\\', ':', '?'], "_"),
+    };
+
+    let mut path = PathBuf::from(drive);
+    for component in components {
+        // $UsnJrnl:$J is what the pipeline expects as $Extend/$J
+        let component = if component.eq_ignore_ascii_case("$UsnJrnl:$J") { "$J".to_string() } else { component };
+        path.push(component.replace(':', "_"));
+    }
+
+    // No escaping the extraction folder through ".." or absolute components
+    path.components().all(|c| matches!(c, Component::Normal(_))).then_some(path)
+}
+
+fn find_system_root(upload_root: &Path) -> Option<PathBuf> {
+    fs::read_dir(upload_root).ok()?
+        .flatten()
+        .map(|e| e.path())
+        .find(|drive| drive.join("Windows").join("System32").join("config").is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_result_lines_are_counted() {
+        let dir = std::env::temp_dir().join(format!("tracenexus-velociraptor-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out_dir = dir.to_str().unwrap();
+
+        let jsonl = "{\"Pid\": 4, \"CreateTime\": \"2024-01-01T00:00:00Z\"}\n{\"Pid\": 5, \"Crea\n\n{\"Pid\": 6}\n";
+        assert_eq!(write_results(out_dir, "Windows.System.Pslist", jsonl), (2, 1));
+        let rows: Vec<Value> = serde_json::from_str(&fs::read_to_string(dir.join("Velociraptor_Windows.System.Pslist.json")).unwrap()).unwrap();
+        // The timeline reads CreateTime through the timestamp rules, the rows stay as Velociraptor wrote them
+        assert_eq!(rows[0], json!({"Pid": 4, "CreateTime": "2024-01-01T00:00:00Z"}));
+
+        assert_eq!(write_results(out_dir, "Custom", "[{\"a\": 1},\n{\"a\": 2}]"), (2, 0));
+        assert_eq!(write_results(out_dir, "Custom", "[{\"a\": 1},\n{\"a\""), (0, 2));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,85 @@
+use colored::*;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use crate::ui;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as backend;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows as backend;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod other;
+#[cfg(not(any(target_os = "linux", windows)))]
+use other as backend;
+
+/// Everything that is gone after a reboot, collected by the platform backend.
+#[derive(Default)]
+pub struct VolatileState {
+    pub processes: Vec<Value>,
+    pub connections: Vec<Value>,
+    pub sessions: Vec<Value>,
+    pub arp_cache: Vec<Value>,
+    pub dns_cache: Vec<Value>,
+}
+
+/// Collects the volatile system state. Must run before any disk tool (order of volatility).
+pub fn collect(out_dir: &str) {
+    println!("{}", "\n--- [ PHASE: VOLATILE STATE ] ---\n".bright_cyan().bold());
+    ui::info("Executing: Volatile state snapshot");
+
+    let collected_at = chrono::Utc::now().to_rfc3339();
+    let state = backend::collect();
+
+    // Most volatile first: processes and sockets change by the second
+    let datasets = [
+        ("Volatile_Processes", state.processes),
+        ("Volatile_Connections", state.connections),
+        ("Volatile_Sessions", state.sessions),
+        ("Volatile_ArpCache", state.arp_cache),
+        ("Volatile_DnsCache", state.dns_cache),
+    ];
+
+    for (name, mut records) in datasets {
+        for record in records.iter_mut() {
+            if let Some(obj) = record.as_object_mut() {
+                obj.insert("CollectedAt".into(), Value::String(collected_at.clone()));
+            }
+        }
+
+        let count = records.len();
+        let target = Path::new(out_dir).join(format!("{}.json", name));
+        let _ = fs::write(target, serde_json::to_string(&records).unwrap());
+        ui::success(&format!("{}: {} entries", name, count));
+    }
+}
+
+/// SHA-256 of a process image. Empty if the file is gone or not readable.
+pub fn hash_file(path: &Path) -> String {
+    let Ok(mut file) = File::open(path) else {
+        return String::new();
+    };
+    let mut hasher = Sha256::new();
+    if io::copy(&mut file, &mut hasher).is_err() {
+        return String::new();
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn connection(proto: &str, local: String, remote: String, state: &str, pid: Option<u32>) -> Value {
+    json!({
+        "Protocol": proto,
+        "LocalAddress": local,
+        "RemoteAddress": remote,
+        "State": state,
+        "OwningPid": pid,
+    })
+}
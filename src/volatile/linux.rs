@@ -0,0 +1,268 @@
+use chrono::DateTime;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use super::{connection, hash_file, VolatileState};
+use crate::utmp;
+
+// /proc reports start times in USER_HZ, which the kernel ABI fixes at 100
+const USER_HZ: u64 = 100;
+
+const TCP_STATES: &[&str] = &[
+    "UNKNOWN", "ESTABLISHED", "SYN_SENT", "SYN_RECV", "FIN_WAIT1", "FIN_WAIT2",
+    "TIME_WAIT", "CLOSE", "CLOSE_WAIT", "LAST_ACK", "LISTEN", "CLOSING",
+];
+
+pub fn collect() -> VolatileState {
+    let boot_time = read_boot_time();
+    let mut inode_owner = HashMap::new();
+
+    let processes = collect_processes(boot_time, &mut inode_owner);
+
+    let mut connections = Vec::new();
+    for proto in ["tcp", "tcp6", "udp", "udp6"] {
+        connections.extend(read_sockets(proto, &inode_owner));
+    }
+
+    let sessions = fs::read("/var/run/utmp")
+        .map(|data| utmp::active_sessions(&data))
+        .unwrap_or_default();
+
+    VolatileState {
+        processes,
+        connections,
+        sessions,
+        arp_cache: read_arp(),
+        // No system wide DNS cache on Linux that can be dumped without the resolver's cooperation
+        dns_cache: Vec::new(),
+    }
+}
+
+fn read_boot_time() -> u64 {
+    fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|s| parse_boot_time(&s))
+        .unwrap_or(0)
+}
+
+fn parse_boot_time(stat: &str) -> Option<u64> {
+    stat.lines()
+        .find(|l| l.starts_with("btime "))
+        .and_then(|l| l[6..].trim().parse().ok())
+}
+
+// /proc/<pid>/stat -> (comm, ppid, starttime in ticks)
+fn parse_stat(stat: &str) -> Option<(String, u32, u64)> {
+    // comm can contain spaces and brackets, so the fields start after the last ')'
+    let close = stat.rfind(')')?;
+    let name = stat[stat.find('(').map(|i| i + 1).unwrap_or(0).min(close)..close].to_string();
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    let ppid = fields.get(1).and_then(|f| f.parse().ok()).unwrap_or(0);
+    let start_ticks = fields.get(19).and_then(|f| f.parse().ok()).unwrap_or(0);
+    Some((name, ppid, start_ticks))
+}
+
+/// Walks /proc/<pid>. Socket inodes found in each fd table are mapped to the owning PID.
+fn collect_processes(boot_time: u64, inode_owner: &mut HashMap<u64, u32>) -> Vec<Value> {
+    let mut processes = Vec::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return processes;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let proc_dir = entry.path();
+
+        let Some((name, ppid, start_ticks)) = fs::read_to_string(proc_dir.join("stat")).ok().and_then(|s| parse_stat(&s)) else {
+            continue;
+        };
+
+        let start_time = DateTime::from_timestamp((boot_time + start_ticks / USER_HZ) as i64, 0)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+
+        let command_line = fs::read(proc_dir.join("cmdline"))
+            .map(|raw| {
+                raw.split(|&b| b == 0)
+                    .filter(|part| !part.is_empty())
+                    .map(|part| String::from_utf8_lossy(part).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+
+        let image = fs::read_link(proc_dir.join("exe")).ok();
+        // Hash through /proc/<pid>/exe so deleted binaries can still be hashed
+        let image_hash = if image.is_some() { hash_file(&proc_dir.join("exe")) } else { String::new() };
+
+        let uid = fs::read_to_string(proc_dir.join("status"))
+            .ok()
+            .and_then(|s| {
+                s.lines()
+                    .find(|l| l.starts_with("Uid:"))
+                    .and_then(|l| l.split_whitespace().nth(1).map(|u| u.to_string()))
+            })
+            .unwrap_or_default();
+
+        if let Ok(fds) = fs::read_dir(proc_dir.join("fd")) {
+            for fd in fds.flatten() {
+                if let Ok(target) = fs::read_link(fd.path())
+                    && let Some(inode) = target.to_string_lossy().strip_prefix("socket:[").and_then(|t| t.strip_suffix(']'))
+                    && let Ok(inode) = inode.parse()
+                {
+                    inode_owner.insert(inode, pid);
+                }
+            }
+        }
+
+        processes.push(json!({
+            "Pid": pid,
+            "ParentPid": ppid,
+            "Name": name,
+            "CommandLine": command_line,
+            "ImagePath": image.map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+            "ImageSha256": image_hash,
+            "Uid": uid,
+            "StartTime": start_time,
+        }));
+    }
+    processes
+}
+
+fn read_sockets(proto: &str, inode_owner: &HashMap<u64, u32>) -> Vec<Value> {
+    let Ok(content) = fs::read_to_string(Path::new("/proc/net").join(proto)) else {
+        return Vec::new();
+    };
+    parse_sockets(proto, &content, inode_owner)
+}
+
+fn parse_sockets(proto: &str, content: &str, inode_owner: &HashMap<u64, u32>) -> Vec<Value> {
+    let mut sockets = Vec::new();
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+
+        let state_idx = usize::from_str_radix(fields[3], 16).unwrap_or(0);
+        let state = if proto.starts_with("udp") {
+            if state_idx == 7 { "UNCONN" } else { "ESTABLISHED" }
+        } else {
+            TCP_STATES.get(state_idx).copied().unwrap_or("UNKNOWN")
+        };
+        let inode: u64 = fields[9].parse().unwrap_or(0);
+
+        sockets.push(connection(
+            proto,
+            decode_address(fields[1]),
+            decode_address(fields[2]),
+            state,
+            inode_owner.get(&inode).copied(),
+        ));
+    }
+    sockets
+}
+
+// "0100007F:0035" -> "127.0.0.1:53", addresses are stored as host-endian 32-bit words
+fn decode_address(raw: &str) -> String {
+    let Some((addr, port)) = raw.split_once(':') else {
+        return raw.to_string();
+    };
+    let port = u16::from_str_radix(port, 16).unwrap_or(0);
+
+    let words: Vec<u32> = (0..addr.len() / 8)
+        .filter_map(|i| u32::from_str_radix(&addr[i * 8..i * 8 + 8], 16).ok())
+        .map(u32::swap_bytes)
+        .collect();
+
+    match words.len() {
+        1 => format!("{}:{}", Ipv4Addr::from(words[0]), port),
+        4 => {
+            let mut octets = [0u8; 16];
+            for (i, word) in words.iter().enumerate() {
+                octets[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+            }
+            format!("[{}]:{}", Ipv6Addr::from(octets), port)
+        },
+        _ => raw.to_string(),
+    }
+}
+
+fn read_arp() -> Vec<Value> {
+    fs::read_to_string("/proc/net/arp")
+        .map(|content| parse_arp(&content))
+        .unwrap_or_default()
+}
+
+fn parse_arp(content: &str) -> Vec<Value> {
+    content.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (fields.len() >= 6).then(|| json!({
+                "IpAddress": fields[0],
+                "MacAddress": fields[3],
+                "Interface": fields[5],
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_addresses() {
+        assert_eq!(decode_address("0100007F:0035"), "127.0.0.1:53");
+        assert_eq!(decode_address("00000000:1F90"), "0.0.0.0:8080");
+        assert_eq!(decode_address("00000000000000000000000001000000:0016"), "[::1]:22");
+        assert_eq!(decode_address("0000000000000000FFFF00000100007F:01BB"), "[::ffff:127.0.0.1]:443");
+        assert_eq!(decode_address("garbage"), "garbage");
+    }
+
+    #[test]
+    fn parses_stat() {
+        let stat = "1234 (tmux: server) S 1 1234 1234 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 1 0 4200 0 0";
+        assert_eq!(parse_stat(stat), Some(("tmux: server".to_string(), 1, 4200)));
+        assert_eq!(parse_stat("1 ((sd-pam)) S 1"), Some(("(sd-pam)".to_string(), 1, 0)));
+        assert_eq!(parse_stat("1 truncated"), None);
+        assert_eq!(parse_boot_time("cpu  1 2 3\nbtime 1700000000\nprocesses 5\n"), Some(1_700_000_000));
+    }
+
+    #[test]
+    fn parses_sockets() {
+        let tcp = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0035 00000000:0000 0A 00000000:00000000 00:00000000 00000000   101        0 17717 1
+   1: 0F02000A:0016 0102000A:D431 01 00000000:00000000 02:0000A1B2 00000000     0        0 23456 4
+   2: short line
+";
+        let owners = HashMap::from([(23456, 812)]);
+        let sockets = parse_sockets("tcp", tcp, &owners);
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(sockets[0]["LocalAddress"], "127.0.0.1:53");
+        assert_eq!(sockets[0]["State"], "LISTEN");
+        assert_eq!(sockets[0]["OwningPid"], Value::Null);
+        assert_eq!(sockets[1]["RemoteAddress"], "10.0.2.1:54321");
+        assert_eq!(sockets[1]["State"], "ESTABLISHED");
+        assert_eq!(sockets[1]["OwningPid"], 812);
+
+        let udp = "header\n   0: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 999 2\n";
+        assert_eq!(parse_sockets("udp", udp, &HashMap::new())[0]["State"], "UNCONN");
+    }
+
+    #[test]
+    fn parses_arp() {
+        let arp = "IP address       HW type     Flags       HW address            Mask     Device
+10.0.2.2         0x1         0x2         52:54:00:12:35:02     *        eth0
+";
+        let entries = parse_arp(arp);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["MacAddress"], "52:54:00:12:35:02");
+        assert_eq!(entries[0]["Interface"], "eth0");
+    }
+}
@@ -0,0 +1,8 @@
+use super::VolatileState;
+use crate::ui;
+
+// No collector for this platform (macOS, BSD), the datasets are written empty so the layout stays the same
+pub fn collect() -> VolatileState {
+    ui::warn("No volatile state collector for this platform, writing empty datasets");
+    VolatileState::default()
+}
@@ -0,0 +1,112 @@
+use serde_json::{json, Value};
+use std::path::Path;
+use std::process::Command;
+use super::{connection, hash_file, VolatileState};
+use crate::ui;
+
+const PROCESSES: &str = "Get-CimInstance Win32_Process | \
+    Select-Object ProcessId, ParentProcessId, Name, CommandLine, ExecutablePath, \
+    @{n='CreationDate';e={$_.CreationDate.ToUniversalTime().ToString('o')}}";
+
+const TCP: &str = "Get-NetTCPConnection | \
+    Select-Object LocalAddress, LocalPort, RemoteAddress, RemotePort, @{n='State';e={$_.State.ToString()}}, OwningProcess";
+
+const UDP: &str = "Get-NetUDPEndpoint | Select-Object LocalAddress, LocalPort, OwningProcess";
+
+const SESSIONS: &str = "Get-CimInstance Win32_LoggedOnUser | ForEach-Object { \
+    $s = Get-CimInstance -InputObject $_.Dependent; \
+    [pscustomobject]@{ User = \"$($_.Antecedent.Domain)\\$($_.Antecedent.Name)\"; LogonId = $s.LogonId; \
+    LogonType = $s.LogonType; LoginTime = $s.StartTime.ToUniversalTime().ToString('o') } }";
+
+const ARP: &str = "Get-NetNeighbor | Where-Object State -ne 'Unreachable' | \
+    Select-Object IPAddress, LinkLayerAddress, InterfaceAlias, @{n='State';e={$_.State.ToString()}}";
+
+const DNS: &str = "Get-DnsClientCache | Select-Object Entry, Name, Data, Type, TimeToLive";
+
+pub fn collect() -> VolatileState {
+    let processes = powershell_json(PROCESSES)
+        .into_iter()
+        .map(|p| {
+            let image = p["ExecutablePath"].as_str().unwrap_or("").to_string();
+            let image_hash = if image.is_empty() { String::new() } else { hash_file(Path::new(&image)) };
+            json!({
+                "Pid": p["ProcessId"],
+                "ParentPid": p["ParentProcessId"],
+                "Name": p["Name"],
+                "CommandLine": p["CommandLine"],
+                "ImagePath": image,
+                "ImageSha256": image_hash,
+                "StartTime": p["CreationDate"],
+            })
+        })
+        .collect();
+
+    let mut connections: Vec<Value> = powershell_json(TCP)
+        .into_iter()
+        .map(|c| connection(
+            "tcp",
+            format!("{}:{}", str_field(&c, "LocalAddress"), c["LocalPort"]),
+            format!("{}:{}", str_field(&c, "RemoteAddress"), c["RemotePort"]),
+            c["State"].as_str().unwrap_or("UNKNOWN"),
+            c["OwningProcess"].as_u64().map(|p| p as u32),
+        ))
+        .collect();
+
+    connections.extend(powershell_json(UDP).into_iter().map(|c| connection(
+        "udp",
+        format!("{}:{}", str_field(&c, "LocalAddress"), c["LocalPort"]),
+        String::new(),
+        "UNCONN",
+        c["OwningProcess"].as_u64().map(|p| p as u32),
+    )));
+
+    VolatileState {
+        processes,
+        connections,
+        sessions: powershell_json(SESSIONS),
+        arp_cache: powershell_json(ARP),
+        dns_cache: powershell_json(DNS),
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value[key].as_str().unwrap_or("").to_string()
+}
+
+/// Runs a PowerShell pipeline and parses its ConvertTo-Json output.
+/// A single result comes back as an object instead of an array, so both are accepted.
+fn powershell_json(script: &str) -> Vec<Value> {
+    // Without this the console code page (e.g. 850) mangles umlauts in paths and user names
+    let command = format!("[Console]::OutputEncoding = [Text.Encoding]::UTF8; {} | ConvertTo-Json -Depth 3 -Compress", script);
+    let output = Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &command])
+        .output();
+
+    let query = script.split_whitespace().next().unwrap_or(script);
+    let out = match output {
+        Ok(out) => out,
+        Err(e) => {
+            ui::warn(&format!("{}: could not start powershell.exe: {}", query, e));
+            return Vec::new();
+        }
+    };
+
+    // Anything that still isn't UTF-8 is replaced instead of losing the whole result
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    if stdout.trim().is_empty() {
+        // No results, or the cmdlet failed (e.g. missing module)
+        if !out.status.success() {
+            ui::warn(&format!("{}: {}", query, String::from_utf8_lossy(&out.stderr).trim()));
+        }
+        return Vec::new();
+    }
+    match serde_json::from_str::<Value>(stdout.trim_start_matches('\u{feff}')) {
+        Ok(Value::Array(items)) => items,
+        Ok(item @ Value::Object(_)) => vec![item],
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            ui::warn(&format!("{}: could not parse the PowerShell output: {}", query, e));
+            Vec::new()
+        }
+    }
+}